@@ -1,13 +1,16 @@
 use deadpool_postgres::Pool;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
-use actix_web::{HttpResponse, Responder, delete, get, post, put, web::Data, web::Json, web::Path};
+use actix_web::{
+    HttpResponse, Responder, delete, get, post, put, web::Data, web::Json, web::Path, web::Query,
+};
 use chrono::{DateTime, Utc};
 
 use minerva::entity_set::{
-    EntitySet, EntitySetError, NewEntitySet, load_entity_set, load_entity_sets,
+    EntitySet, EntitySetError, EntitySetRule, NewEntitySet, diff_entity_set_revisions,
+    load_entity_set, load_entity_set_members_at, load_entity_set_revisions, load_entity_sets,
 };
 
 use super::serviceerror::{ExtendedServiceError, ServiceError, ServiceErrorKind};
@@ -22,7 +25,11 @@ pub struct EntitySetData {
     pub entity_type: Option<String>,
     pub owner: String,
     pub description: Option<String>,
+    #[serde(default)]
     pub entities: Vec<String>,
+    #[serde(default)]
+    #[schema(value_type = Vec<Object>)]
+    pub rules: Vec<EntitySetRule>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
@@ -33,11 +40,33 @@ pub struct EntitySetDataFull {
     pub entity_type: String,
     pub owner: String,
     pub description: String,
+    #[serde(default)]
     pub entities: Vec<String>,
+    #[serde(default)]
+    #[schema(value_type = Vec<Object>)]
+    pub rules: Vec<EntitySetRule>,
     pub created: Option<DateTime<Utc>>,
     pub modified: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct EntitySetRevisionData {
+    pub id: i64,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct EntitySetRevisionDiffData {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+pub struct MembersQuery {
+    pub timestamp: DateTime<Utc>,
+}
+
 impl EntitySetDataFull {
     fn entity_set(&self) -> EntitySet {
         EntitySet {
@@ -48,6 +77,7 @@ impl EntitySetDataFull {
             owner: self.owner.to_string(),
             description: self.description.to_string(),
             entities: self.entities.clone(),
+            rules: self.rules.clone(),
             created: self.created.unwrap_or(Utc::now()),
             modified: self.modified.unwrap_or(Utc::now()),
         }
@@ -75,6 +105,7 @@ impl EntitySetData {
             owner: self.owner.to_string(),
             description,
             entities: self.entities.clone(),
+            rules: self.rules.clone(),
         }
     }
 }
@@ -338,6 +369,15 @@ pub(super) async fn delete_entity_set(pool: Data<Pool>, id: Path<i32>) -> impl R
             let preresult = load_entity_set(client, &es_id).await;
             match preresult {
                 Ok(_) => {
+                    if let Err(e) = client
+                        .execute("SELECT entity_set.close_revision($1)", &[&es_id])
+                        .await
+                    {
+                        let mut messages = Map::new();
+                        messages.insert("general".to_string(), Value::String(e.to_string()));
+                        return HttpResponse::InternalServerError().json(messages);
+                    }
+
                     let query =
                         "DELETE FROM attribute_history.minerva_entity_set WHERE entity_id = $1";
                     let result = client.execute(query, &[&es_id]).await;
@@ -362,3 +402,107 @@ pub(super) async fn delete_entity_set(pool: Data<Pool>, id: Path<i32>) -> impl R
         }
     }
 }
+
+fn entity_set_error_response(e: EntitySetError) -> HttpResponse {
+    match e {
+        EntitySetError::NotFound(e) => HttpResponse::NotFound().json(Error {
+            code: 404,
+            message: e.to_string(),
+        }),
+        e => HttpResponse::InternalServerError().json(Error {
+            code: 500,
+            message: minerva::error::Error::from(e).to_string(),
+        }),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/entitysets/{id}/revisions",
+    responses(
+    (status = 200, description = "Revisions of the entity set", body = [EntitySetRevisionData]),
+    (status = 404, description = "Entity set has no revisions", body = Error),
+    (status = 500, description = "Database unreachable", body = Error),
+    )
+)]
+#[get("/entitysets/{id}/revisions")]
+pub(super) async fn get_entity_set_revisions(
+    pool: Data<Pool>,
+    id: Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = pool.get().await.map_err(|_| ServiceError {
+        kind: ServiceErrorKind::PoolError,
+        message: String::new(),
+    })?;
+
+    match load_entity_set_revisions(&**client, id.into_inner()).await {
+        Ok(revisions) => Ok(HttpResponse::Ok().json(
+            revisions
+                .into_iter()
+                .map(|revision| EntitySetRevisionData {
+                    id: revision.id,
+                    valid_from: revision.valid_from,
+                    valid_to: revision.valid_to,
+                })
+                .collect::<Vec<EntitySetRevisionData>>(),
+        )),
+        Err(e) => Ok(entity_set_error_response(e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/entitysets/{id}/members",
+    params(MembersQuery),
+    responses(
+    (status = 200, description = "Members of the entity set at the timestamp", body = [String]),
+    (status = 404, description = "Entity set has no revisions", body = Error),
+    (status = 500, description = "Database unreachable", body = Error),
+    )
+)]
+#[get("/entitysets/{id}/members")]
+pub(super) async fn get_entity_set_members_at(
+    pool: Data<Pool>,
+    id: Path<i32>,
+    query: Query<MembersQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let client = pool.get().await.map_err(|_| ServiceError {
+        kind: ServiceErrorKind::PoolError,
+        message: String::new(),
+    })?;
+
+    match load_entity_set_members_at(&**client, id.into_inner(), &query.timestamp).await {
+        Ok(members) => Ok(HttpResponse::Ok().json(members)),
+        Err(e) => Ok(entity_set_error_response(e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path="/entitysets/{id}/revisions/{from}/diff/{to}",
+    responses(
+    (status = 200, description = "Entities added and removed between the revisions", body = EntitySetRevisionDiffData),
+    (status = 404, description = "Entity set has no revisions", body = Error),
+    (status = 500, description = "Database unreachable", body = Error),
+    )
+)]
+#[get("/entitysets/{id}/revisions/{from}/diff/{to}")]
+pub(super) async fn diff_entity_set_revision(
+    pool: Data<Pool>,
+    path: Path<(i32, i64, i64)>,
+) -> Result<HttpResponse, ServiceError> {
+    let (id, from, to) = path.into_inner();
+
+    let client = pool.get().await.map_err(|_| ServiceError {
+        kind: ServiceErrorKind::PoolError,
+        message: String::new(),
+    })?;
+
+    match diff_entity_set_revisions(&**client, id, from, to).await {
+        Ok(diff) => Ok(HttpResponse::Ok().json(EntitySetRevisionDiffData {
+            added: diff.added,
+            removed: diff.removed,
+        })),
+        Err(e) => Ok(entity_set_error_response(e)),
+    }
+}
@@ -54,7 +54,9 @@ use trigger::{
 
 mod entityset;
 use entityset::{
-    EntitySetData, change_entity_set, create_entity_set, delete_entity_set, get_entity_sets,
+    EntitySetData, EntitySetRevisionData, EntitySetRevisionDiffData, change_entity_set,
+    create_entity_set, delete_entity_set, diff_entity_set_revision, get_entity_set_members_at,
+    get_entity_set_revisions, get_entity_sets,
 };
 
 mod header;
@@ -118,6 +120,9 @@ async fn main() -> Result<(), serviceerror::ServiceError> {
             entityset::change_entity_set,
             entityset::create_entity_set,
             entityset::delete_entity_set,
+            entityset::get_entity_set_revisions,
+            entityset::get_entity_set_members_at,
+            entityset::diff_entity_set_revision,
             header::get_header
         ),
         components(
@@ -128,7 +133,8 @@ async fn main() -> Result<(), serviceerror::ServiceError> {
                 TrendFull, GeneratedTrendFull, TrendStorePartFull, TrendStoreFull,
                 DataSource, EntityType, KpiRawData, KpiImplementedData,
                 TriggerData, ShortTemplateData, TemplateData,
-                TemplatedTriggerDefinition, EntitySetData,
+                TemplatedTriggerDefinition, EntitySetData, EntitySetRevisionData,
+                EntitySetRevisionDiffData,
             )
         ),
         tags(
@@ -210,6 +216,9 @@ async fn main() -> Result<(), serviceerror::ServiceError> {
             .service(change_entity_set)
             .service(create_entity_set)
             .service(delete_entity_set)
+            .service(get_entity_set_revisions)
+            .service(get_entity_set_members_at)
+            .service(diff_entity_set_revision)
             .service(get_header)
     })
     .bind((service_address, service_port))
@@ -78,6 +78,8 @@ impl BaselineDumpOpt {
             "entity",
             "relation",
             "relation_def",
+            "relation_history",
+            "entity_set",
             "alias",
             "alias_def",
             "virtual_entity",
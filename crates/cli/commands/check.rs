@@ -0,0 +1,69 @@
+use std::env;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use minerva::check::{CheckSeverity, check_instance_dir};
+use minerva::error::{ConfigurationError, Error, RuntimeError};
+
+use super::common::{Cmd, CmdResult, ENV_MINERVA_INSTANCE_ROOT};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct CheckOpt {
+    #[arg(help = "Minerva instance root directory")]
+    instance_root: Option<PathBuf>,
+    #[arg(long, help = "Treat warnings as errors")]
+    deny_warnings: bool,
+}
+
+impl CheckOpt {
+    fn check(&self) -> CmdResult {
+        let minerva_instance_root = match &self.instance_root {
+            Some(root) => root.clone(),
+            None => match env::var(ENV_MINERVA_INSTANCE_ROOT) {
+                Ok(v) => PathBuf::from(v),
+                Err(e) => {
+                    return Err(Error::Configuration(ConfigurationError {
+                        msg: format!(
+                            "Environment variable '{ENV_MINERVA_INSTANCE_ROOT}' could not be read: {e}"
+                        ),
+                    }));
+                }
+            },
+        };
+
+        let issues = check_instance_dir(&minerva_instance_root)?;
+
+        for issue in &issues {
+            println!("{issue}");
+        }
+
+        let error_count = issues
+            .iter()
+            .filter(|issue| {
+                issue.severity == CheckSeverity::Error
+                    || (self.deny_warnings && issue.severity == CheckSeverity::Warning)
+            })
+            .count();
+
+        if error_count > 0 {
+            return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                "Found {error_count} problem(s) in instance definition"
+            ))));
+        }
+
+        if issues.is_empty() {
+            println!("No problems found");
+        } else {
+            println!("Found {} warning(s) in instance definition", issues.len());
+        }
+
+        Ok(())
+    }
+}
+
+impl Cmd for CheckOpt {
+    fn run(&self) -> CmdResult {
+        self.check()
+    }
+}
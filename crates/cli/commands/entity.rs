@@ -0,0 +1,391 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use comfy_table::{ContentLineStyle, LineStyle, TableStyle};
+
+use minerva::change::Change;
+use minerva::changes::entity::{MergeEntity, RenameEntity, RetireEntity};
+use minerva::entity::{
+    find_entities, load_entity_details, load_entity_inventory_from_file, register_entities,
+};
+use minerva::entity_type::materialize_entity_type_aliases;
+use minerva::error::{Error, RuntimeError};
+
+use super::common::{Cmd, CmdResult, connect_db};
+use super::update::{change_log_dir, update_variation};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityChangeOpt {
+    #[arg(short, long, help = "ask for confirmation before applying the change")]
+    interactive: bool,
+    #[arg(long, help = "directory to log the applied change to for reverting")]
+    log_dir: Option<PathBuf>,
+}
+
+impl EntityChangeOpt {
+    async fn apply(&self, change: Box<dyn Change>) -> CmdResult {
+        let log_dir = change_log_dir(self.log_dir.as_ref())?;
+
+        let mut client = connect_db().await?;
+
+        update_variation(&mut client, &log_dir, vec![change], self.interactive).await
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityRename {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "current name of the entity")]
+    name: String,
+    #[arg(help = "new name of the entity")]
+    new_name: String,
+    #[command(flatten)]
+    change: EntityChangeOpt,
+}
+
+impl Cmd for EntityRename {
+    fn run(&self) -> CmdResult {
+        let change = RenameEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+            new_name: self.new_name.clone(),
+        };
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.change.apply(Box::new(change)))
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityMerge {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "name of the entity to merge and retire")]
+    from: String,
+    #[arg(help = "name of the entity to merge into")]
+    to: String,
+    #[command(flatten)]
+    change: EntityChangeOpt,
+}
+
+impl Cmd for EntityMerge {
+    fn run(&self) -> CmdResult {
+        let change = MergeEntity {
+            entity_type: self.entity_type.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        };
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.change.apply(Box::new(change)))
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityRetire {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "name of the entity")]
+    name: String,
+    #[command(flatten)]
+    change: EntityChangeOpt,
+}
+
+impl Cmd for EntityRetire {
+    fn run(&self) -> CmdResult {
+        let change = RetireEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        };
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.change.apply(Box::new(change)))
+    }
+}
+
+fn new_table() -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    let style = TableStyle::new()
+        .top_border(LineStyle::none())
+        .header_lines(ContentLineStyle::none().junction('┆'))
+        .header_separator(LineStyle::none().junction('╪').fill('═'))
+        .content_lines(ContentLineStyle::none().junction('┆'))
+        .row_separator(LineStyle::none())
+        .bottom_border(LineStyle::none());
+    table.load_style(style);
+    table
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityFind {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "name or primary alias pattern, with '*' and '?' wildcards")]
+    pattern: String,
+    #[arg(
+        long,
+        default_value_t = 100,
+        help = "maximum number of entities to show"
+    )]
+    limit: i64,
+}
+
+impl EntityFind {
+    async fn find(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let entities = find_entities(&client, &self.entity_type, &self.pattern, self.limit)
+            .await
+            .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        let mut table = new_table();
+        table.set_header(vec!["Id", "Name", "Primary alias"]);
+
+        for entity in &entities {
+            table.add_row(vec![
+                entity.id.to_string(),
+                entity.name.clone(),
+                entity.alias.clone().unwrap_or_default(),
+            ]);
+        }
+
+        println!("{table}");
+
+        Ok(())
+    }
+}
+
+impl Cmd for EntityFind {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.find())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityShow {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "name of the entity")]
+    name: String,
+}
+
+impl EntityShow {
+    async fn show(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let details = load_entity_details(&client, &self.entity_type, &self.name)
+            .await
+            .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        println!("Id: {}", details.id);
+        println!("Name: {}", details.name);
+        println!(
+            "Primary alias: {}",
+            details.primary_alias.as_deref().unwrap_or("")
+        );
+        println!("Retired: {}", details.retired);
+
+        println!();
+        println!("Trend data:");
+
+        let mut table = new_table();
+        table.set_header(vec!["Trend store part", "Last timestamp"]);
+
+        for trend_data in &details.trend_data {
+            table.add_row(vec![
+                trend_data.trend_store_part.clone(),
+                trend_data.last_timestamp.to_rfc3339(),
+            ]);
+        }
+
+        println!("{table}");
+
+        for attributes in &details.attributes {
+            println!();
+            println!("Attributes ({}):", attributes.attribute_store);
+
+            let mut table = new_table();
+            table.set_header(vec!["Attribute", "Value"]);
+
+            for (name, value) in &attributes.attributes {
+                let value = match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    serde_json::Value::Null => String::new(),
+                    value => value.to_string(),
+                };
+
+                table.add_row(vec![name.clone(), value]);
+            }
+
+            println!("{table}");
+        }
+
+        println!();
+        println!("Relations:");
+
+        let mut table = new_table();
+        table.set_header(vec!["Relation", "Direction", "Related"]);
+
+        for relations in &details.relations {
+            let direction = if relations.entity_is_source {
+                "source"
+            } else {
+                "target"
+            };
+
+            table.add_row(vec![
+                relations.relation.clone(),
+                direction.to_string(),
+                relations.related.join(", "),
+            ]);
+        }
+
+        println!("{table}");
+
+        println!();
+        println!("Entity sets: {}", details.entity_sets.join(", "));
+
+        Ok(())
+    }
+}
+
+impl Cmd for EntityShow {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.show())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityImport {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(help = "inventory CSV file with a 'name' column and a column per alias type")]
+    file: PathBuf,
+}
+
+impl EntityImport {
+    async fn import(&self) -> CmdResult {
+        let inventory = load_entity_inventory_from_file(&self.file)?;
+
+        let mut client = connect_db().await?;
+
+        let tx = client.transaction().await?;
+
+        let result = register_entities(&tx, &self.entity_type, &inventory).await?;
+
+        tx.commit().await?;
+
+        println!(
+            "Registered {} new entities ({} already existed) and {} aliases",
+            result.created, result.existing, result.aliases
+        );
+
+        Ok(())
+    }
+}
+
+impl Cmd for EntityImport {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.import())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityMaterializeAliases {
+    #[arg(help = "entity type")]
+    entity_type: String,
+    #[arg(long, help = "only materialize the alias type with this name")]
+    alias: Option<String>,
+}
+
+impl EntityMaterializeAliases {
+    async fn materialize(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let tx = client.transaction().await?;
+
+        let results =
+            materialize_entity_type_aliases(&tx, &self.entity_type, self.alias.as_deref()).await?;
+
+        tx.commit().await?;
+
+        for (alias_type, count) in &results {
+            println!("Materialized {count} '{alias_type}' aliases");
+        }
+
+        Ok(())
+    }
+}
+
+impl Cmd for EntityMaterializeAliases {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.materialize())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntityOpt {
+    #[command(subcommand)]
+    command: EntityOptCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum EntityOptCommands {
+    #[command(about = "find entities by name or primary alias")]
+    Find(EntityFind),
+    #[command(about = "show the data, attributes, relations and entity sets of an entity")]
+    Show(EntityShow),
+    #[command(about = "register entities and aliases from an inventory CSV file")]
+    Import(EntityImport),
+    #[command(about = "materialize the aliases of an entity type from their source queries")]
+    MaterializeAliases(EntityMaterializeAliases),
+    #[command(about = "rename an entity")]
+    Rename(EntityRename),
+    #[command(about = "merge the data of an entity into another entity and retire it")]
+    Merge(EntityMerge),
+    #[command(about = "mark an entity as inactive")]
+    Retire(EntityRetire),
+}
+
+impl EntityOpt {
+    /// # Errors
+    ///
+    /// Will return `Err` if a subcommand returns an error.
+    pub fn run(&self) -> CmdResult {
+        match &self.command {
+            EntityOptCommands::Find(find) => find.run(),
+            EntityOptCommands::Show(show) => show.run(),
+            EntityOptCommands::Import(import) => import.run(),
+            EntityOptCommands::MaterializeAliases(materialize) => materialize.run(),
+            EntityOptCommands::Rename(rename) => rename.run(),
+            EntityOptCommands::Merge(merge) => merge.run(),
+            EntityOptCommands::Retire(retire) => retire.run(),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use clap::{Parser, Subcommand};
+
+use minerva::entity_set::evaluate_dynamic_entity_sets;
+
+use super::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntitySetEvaluate {
+    #[arg(
+        long,
+        help = "only evaluate sets with attribute stores that changed since the last evaluation"
+    )]
+    changed_only: bool,
+}
+
+impl EntitySetEvaluate {
+    async fn evaluate(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let evaluations = evaluate_dynamic_entity_sets(&mut client, self.changed_only).await?;
+
+        for evaluation in &evaluations {
+            if evaluation.diff.added.is_empty() && evaluation.diff.removed.is_empty() {
+                println!("{}:{}: unchanged", evaluation.owner, evaluation.name);
+            } else {
+                println!(
+                    "{}:{}: {} added, {} removed",
+                    evaluation.owner,
+                    evaluation.name,
+                    evaluation.diff.added.len(),
+                    evaluation.diff.removed.len()
+                );
+
+                for name in &evaluation.diff.added {
+                    println!("  + {name}");
+                }
+
+                for name in &evaluation.diff.removed {
+                    println!("  - {name}");
+                }
+            }
+        }
+
+        println!("Evaluated {} dynamic entity sets", evaluations.len());
+
+        Ok(())
+    }
+}
+
+impl Cmd for EntitySetEvaluate {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.evaluate())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct EntitySetOpt {
+    #[command(subcommand)]
+    command: EntitySetOptCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum EntitySetOptCommands {
+    #[command(about = "re-evaluate the rules of dynamic entity sets and update their members")]
+    Evaluate(EntitySetEvaluate),
+}
+
+impl EntitySetOpt {
+    /// # Errors
+    ///
+    /// Will return `Err` if a subcommand returns an error.
+    pub fn run(&self) -> CmdResult {
+        match &self.command {
+            EntitySetOptCommands::Evaluate(evaluate) => evaluate.run(),
+        }
+    }
+}
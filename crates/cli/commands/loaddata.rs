@@ -16,6 +16,16 @@ pub struct LoadDataOpt {
     parser_config: Option<PathBuf>,
     #[arg(long, help = "Create partitions for timestamps in data")]
     create_partitions: bool,
+    #[arg(
+        long,
+        help = "Reject data for unknown entities instead of creating them"
+    )]
+    strict_entities: bool,
+    #[arg(
+        long,
+        help = "Alias type of the entity type that the entity column contains instead of names"
+    )]
+    entity_alias: Option<String>,
     #[arg(help = "File to load")]
     file: PathBuf,
 }
@@ -24,7 +34,7 @@ impl LoadDataOpt {
     async fn load_data(&self) -> CmdResult {
         let mut client = connect_db().await?;
 
-        let parser_config: ParserConfig = match &self.parser_config {
+        let mut parser_config: ParserConfig = match &self.parser_config {
             None => ParserConfig {
                 entity_type: "node".into(),
                 granularity: "15m".into(),
@@ -34,6 +44,8 @@ impl LoadDataOpt {
                 }),
                 extra: None,
                 null_value: NULL_VALUE.to_string(),
+                strict_entities: false,
+                entity_alias: None,
             },
             Some(path) => {
                 let config_file = std::fs::File::open(path)
@@ -42,6 +54,14 @@ impl LoadDataOpt {
             }
         };
 
+        if self.strict_entities {
+            parser_config.strict_entities = true;
+        }
+
+        if let Some(entity_alias) = &self.entity_alias {
+            parser_config.entity_alias = Some(entity_alias.clone());
+        }
+
         let data_source = match &self.data_source {
             None => "minerva-cli".to_string(),
             Some(d) => d.to_string(),
@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
+use comfy_table::Table;
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use log::{error, info};
+
+use minerva::maintenance::{
+    MaintenanceConfig, MaintenanceTask, load_maintenance_status, record_maintenance_status,
+    release_maintenance_lock, run_maintenance_task, try_acquire_maintenance_lock,
+};
+
+use super::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Task {
+    CreatePartitions,
+    ColumnarizePartitions,
+    RemoveExpiredPartitions,
+    CompactAttributeStores,
+    MaterializeCurrPtr,
+    RemoveExpiredChangeEvents,
+    RemoveExpiredEntitySetRevisions,
+}
+
+impl From<Task> for MaintenanceTask {
+    fn from(value: Task) -> Self {
+        match value {
+            Task::CreatePartitions => MaintenanceTask::CreatePartitions,
+            Task::ColumnarizePartitions => MaintenanceTask::ColumnarizePartitions,
+            Task::RemoveExpiredPartitions => MaintenanceTask::RemoveExpiredPartitions,
+            Task::CompactAttributeStores => MaintenanceTask::CompactAttributeStores,
+            Task::MaterializeCurrPtr => MaintenanceTask::MaterializeCurrPtr,
+            Task::RemoveExpiredChangeEvents => MaintenanceTask::RemoveExpiredChangeEvents,
+            Task::RemoveExpiredEntitySetRevisions => {
+                MaintenanceTask::RemoveExpiredEntitySetRevisions
+            }
+        }
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct MaintenanceService {
+    #[arg(
+        long,
+        default_value = "5m",
+        value_parser = humantime::parse_duration,
+        help = "time between maintenance runs"
+    )]
+    interval: Duration,
+    #[arg(
+        long,
+        default_value_t = MaintenanceConfig::default().lookahead_partitions,
+        help = "number of partitions to create ahead of time for each trend store"
+    )]
+    lookahead_partitions: u32,
+    #[arg(
+        long,
+        default_value = "3d",
+        value_parser = humantime::parse_duration,
+        help = "minimum period to create partitions for ahead of time"
+    )]
+    min_lookahead: Duration,
+    #[arg(
+        long,
+        default_value = "7d",
+        value_parser = humantime::parse_duration,
+        help = "period to keep change events for"
+    )]
+    change_event_retention: Duration,
+    #[arg(long, value_enum, help = "skip a maintenance task, can be repeated")]
+    skip: Vec<Task>,
+    #[arg(long, help = "run the maintenance tasks once and exit")]
+    once: bool,
+}
+
+impl MaintenanceService {
+    fn config(&self) -> MaintenanceConfig {
+        let skip: Vec<MaintenanceTask> = self.skip.iter().map(|task| (*task).into()).collect();
+
+        MaintenanceConfig {
+            lookahead_partitions: self.lookahead_partitions,
+            min_lookahead: self.min_lookahead,
+            change_event_retention: self.change_event_retention,
+            tasks: MaintenanceTask::ALL
+                .into_iter()
+                .filter(|task| !skip.contains(task))
+                .collect(),
+        }
+    }
+
+    async fn run_tasks(&self, config: &MaintenanceConfig, instance: &str) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        if !try_acquire_maintenance_lock(&client).await? {
+            info!("Maintenance lock is held by another instance, skipping run");
+
+            return Ok(());
+        }
+
+        let result = Self::run_locked_tasks(&mut client, config, instance).await;
+
+        // Release the lock also when a run fails, instead of relying on the connection being
+        // closed
+        let release_result = release_maintenance_lock(&client).await;
+
+        result?;
+        release_result?;
+
+        Ok(())
+    }
+
+    async fn run_locked_tasks(
+        client: &mut tokio_postgres::Client,
+        config: &MaintenanceConfig,
+        instance: &str,
+    ) -> CmdResult {
+        for task in &config.tasks {
+            let started = Utc::now();
+
+            let result = run_maintenance_task(client, *task, config).await;
+
+            match &result {
+                Ok(summary) => info!("{task}: {summary}"),
+                Err(e) => error!("{task} failed: {e}"),
+            }
+
+            record_maintenance_status(client, *task, instance, started, &result).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn start(&self) -> CmdResult {
+        let config = self.config();
+
+        let instance = format!(
+            "{}:{}",
+            std::env::var("HOSTNAME").unwrap_or("unknown".to_string()),
+            std::process::id()
+        );
+
+        if self.once {
+            return self.run_tasks(&config, &instance).await;
+        }
+
+        info!("Starting maintenance service '{instance}'");
+
+        let mut interval = tokio::time::interval(self.interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    // A failing run, e.g. because the database is not reachable, should not
+                    // stop the service, so the error is only logged.
+                    if let Err(e) = self.run_tasks(&config, &instance).await {
+                        error!("Maintenance run failed: {e}");
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Stopping maintenance service");
+
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Cmd for MaintenanceService {
+    fn run(&self) -> CmdResult {
+        env_logger::init();
+
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.start())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct MaintenanceStatusOpt {}
+
+impl MaintenanceStatusOpt {
+    async fn status(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let statuses = load_maintenance_status(&client).await?;
+
+        let mut table = Table::new();
+        table
+            .load_style(UTF8_FULL_CONDENSED.with_rounded_corners())
+            .set_header(vec![
+                "Task", "Instance", "Started", "Finished", "Result", "Message",
+            ]);
+
+        for status in statuses {
+            table.add_row(vec![
+                status.task,
+                status.instance,
+                status.started.to_string(),
+                status.finished.to_string(),
+                if status.success { "ok" } else { "failed" }.to_string(),
+                status.message.unwrap_or_default(),
+            ]);
+        }
+
+        println!("{table}");
+
+        Ok(())
+    }
+}
+
+impl Cmd for MaintenanceStatusOpt {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.status())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct MaintenanceOpt {
+    #[command(subcommand)]
+    command: MaintenanceOptCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum MaintenanceOptCommands {
+    #[command(about = "run partition and attribute store maintenance on a schedule")]
+    Service(MaintenanceService),
+    #[command(about = "show the result of the last run of each maintenance task")]
+    Status(MaintenanceStatusOpt),
+}
+
+impl MaintenanceOpt {
+    /// # Errors
+    ///
+    /// Will return `Err` if a subcommand returns an error.
+    pub fn run(&self) -> CmdResult {
+        match &self.command {
+            MaintenanceOptCommands::Service(service) => service.run(),
+            MaintenanceOptCommands::Status(status) => status.run(),
+        }
+    }
+}
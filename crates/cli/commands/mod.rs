@@ -1,17 +1,22 @@
 pub mod aggregation;
 pub mod attributestore;
 pub mod baselinedump;
+pub mod check;
 pub mod common;
 pub mod define;
 pub mod diff;
 pub mod dump;
+pub mod entity;
+pub mod entityset;
 pub mod graph;
 pub mod initialize;
 pub mod loaddata;
+pub mod maintenance;
 pub mod relation;
 pub mod revert;
 pub mod schema;
 pub mod start;
+pub mod trend;
 pub mod trendmaterialization;
 pub mod trendstore;
 pub mod trigger;
@@ -2,9 +2,15 @@ use std::path::PathBuf;
 
 use tokio_postgres::Client;
 
+use comfy_table::{ContentLineStyle, LineStyle, TableStyle};
 use minerva::change::Change;
+
+use minerva::error::{Error, RuntimeError};
 use minerva::relation::{
-    AddRelation, UpdateRelationView, load_relation_from_file, materialize_relation,
+    AddRelation, MaterializeRelationOptions, RelatedEntities, RelationDefinitionKind,
+    RelationFilter, UpdateRelationProcedure, UpdateRelationView, load_related_entities,
+    load_relation_from_file, load_relation_path, materialize_relation,
+    materialize_relation_by_procedure, relation_definition_kind,
 };
 
 use clap::{Parser, Subcommand};
@@ -67,9 +73,22 @@ impl RelationUpdate {
 
             let message = change.apply(&mut client).await?;
 
+            println!("{message}");
+        } else if let Some(procedure_src) = relation.procedure {
+            println!("Loaded definition, updating relation procedure");
+
+            let mut client = connect_db().await?;
+
+            let change = UpdateRelationProcedure {
+                relation_name: relation.name,
+                procedure_src,
+            };
+
+            let message = change.apply(&mut client).await?;
+
             println!("{message}");
         } else {
-            println!("Relation has no view definition, nothing to update");
+            println!("Relation has no view or procedure definition, nothing to update");
         }
 
         Ok(())
@@ -90,6 +109,11 @@ impl Cmd for RelationUpdate {
 pub struct RelationMaterialize {
     #[arg(help = "relation name")]
     name: Option<String>,
+    #[arg(
+        long,
+        help = "publish the added and removed relations as change events"
+    )]
+    publish_changes: bool,
 }
 
 impl RelationMaterialize {
@@ -98,53 +122,70 @@ impl RelationMaterialize {
 
         let mut client = connect_db().await?;
 
+        let options = MaterializeRelationOptions {
+            publish_changes: self.publish_changes,
+        };
+
         let relation_names = match &self.name {
             Some(name) => vec![name.clone()],
             None => get_relation_names(&client).await,
         };
 
         for name in relation_names {
-            let mut tx = client.transaction().await?;
-
-            let materialization_definition_query = concat!(
-                "SELECT 1 FROM pg_catalog.pg_class c ",
-                "JOIN pg_catalog.pg_namespace ns ",
-                "ON c.relnamespace = ns.oid ",
-                "WHERE relname = $1 AND nspname = $2"
-            );
-
-            match tx
-                .query_one(materialization_definition_query, &[&name, &"relation_def"])
+            let definition_kind = relation_definition_kind(&client, &name)
                 .await
-            {
-                Ok(_) => match materialize_relation(&mut tx, &name).await {
-                    Ok(changed) => {
-                        tx.commit().await?;
-                        println!(
-                            "Materialized relation '{name}' (deleted {}, inserted {})",
-                            changed.deleted_count, changed.inserted_count
-                        );
+                .map_err(|e| {
+                    Error::Database(minerva::error::DatabaseError::from_msg(format!(
+                        "Could not determine definition of relation '{name}': {e}"
+                    )))
+                })?;
+
+            let result = match definition_kind {
+                Some(RelationDefinitionKind::View) => {
+                    let mut tx = client.transaction().await?;
+
+                    match materialize_relation(&mut tx, &name, &options).await {
+                        Ok(changed) => {
+                            tx.commit().await?;
+                            Ok(changed)
+                        }
+                        Err(e) => {
+                            tx.rollback().await?;
+                            Err(e)
+                        }
                     }
-                    Err(e) => {
-                        tx.rollback().await?;
-                        println!("Error materializing relation '{name}': {e}");
-                        error_count += 1;
-                    }
-                },
-                Err(_) => {
+                }
+                // Procedures can commit between stages, so the transaction, if any, is managed
+                // by materialize_relation_by_procedure
+                Some(RelationDefinitionKind::Procedure) => {
+                    materialize_relation_by_procedure(&mut client, &name, &options).await
+                }
+                None => {
                     println!(
-                        "WARNING: Relation {name} does not have a defining view and is skipped."
-                    )
+                        "WARNING: Relation {name} does not have a defining view or procedure and is skipped."
+                    );
+                    continue;
+                }
+            };
+
+            match result {
+                Ok(changed) => {
+                    println!(
+                        "Materialized relation '{name}' (added {}, removed {})",
+                        changed.added_count, changed.removed_count
+                    );
+                }
+                Err(e) => {
+                    println!("Error materializing relation '{name}': {e}");
+                    error_count += 1;
                 }
             }
         }
 
         if error_count > 0 {
-            return Err(minerva::error::Error::Runtime(
-                minerva::error::RuntimeError::from_msg(format!(
-                    "{error_count} relations failed to materialize"
-                )),
-            ));
+            return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                "{error_count} relations failed to materialize"
+            ))));
         }
 
         Ok(())
@@ -170,6 +211,127 @@ impl Cmd for RelationMaterialize {
     }
 }
 
+fn relation_filter(source: &Option<String>, target: &Option<String>) -> RelationFilter {
+    match (source, target) {
+        (Some(source), _) => RelationFilter::Source(source.clone()),
+        (None, Some(target)) => RelationFilter::Target(target.clone()),
+        (None, None) => RelationFilter::All,
+    }
+}
+
+fn print_related_entities(related_entities: &[RelatedEntities]) {
+    let mut table = comfy_table::Table::new();
+    let style = TableStyle::new()
+        .top_border(LineStyle::none())
+        .header_lines(ContentLineStyle::none().junction('┆'))
+        .header_separator(LineStyle::none().junction('╪').fill('═'))
+        .content_lines(ContentLineStyle::none().junction('┆'))
+        .row_separator(LineStyle::none())
+        .bottom_border(LineStyle::none());
+    table.load_style(style);
+    table.set_header(vec!["Source Id", "Source", "Target Id", "Target"]);
+
+    for related in related_entities {
+        table.add_row(vec![
+            related.source_id.to_string(),
+            related.source_name.clone(),
+            related.target_id.to_string(),
+            related.target_name.clone(),
+        ]);
+    }
+
+    println!("{table}");
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct RelationShow {
+    #[arg(help = "relation name")]
+    relation: String,
+    #[arg(long, help = "only show relations of this source entity")]
+    source: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "source",
+        help = "only show relations of this target entity"
+    )]
+    target: Option<String>,
+}
+
+impl RelationShow {
+    async fn show(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let related_entities = load_related_entities(
+            &client,
+            std::slice::from_ref(&self.relation),
+            &relation_filter(&self.source, &self.target),
+        )
+        .await
+        .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        print_related_entities(&related_entities);
+
+        Ok(())
+    }
+}
+
+impl Cmd for RelationShow {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.show())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct RelationPath {
+    #[arg(help = "entity type to start from")]
+    from_type: String,
+    #[arg(help = "entity type to reach")]
+    to_type: String,
+    #[arg(long, help = "only show entities reached from this source entity")]
+    source: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "source",
+        help = "only show entities that reach this target entity"
+    )]
+    target: Option<String>,
+}
+
+impl RelationPath {
+    async fn path(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let (path, related_entities) = load_relation_path(
+            &client,
+            &self.from_type,
+            &self.to_type,
+            &relation_filter(&self.source, &self.target),
+        )
+        .await
+        .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        println!("Path: {}", path.join(", "));
+
+        print_related_entities(&related_entities);
+
+        Ok(())
+    }
+}
+
+impl Cmd for RelationPath {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.path())
+    }
+}
+
 #[derive(Debug, Parser, PartialEq)]
 pub struct RelationOpt {
     #[command(subcommand)]
@@ -184,6 +346,10 @@ pub enum RelationOptCommands {
     Update(RelationUpdate),
     #[command(about = "materialize a relation")]
     Materialize(RelationMaterialize),
+    #[command(about = "show the related entities of a relation")]
+    Show(RelationShow),
+    #[command(about = "show the entities reached over a chain of relations")]
+    Path(RelationPath),
 }
 
 impl RelationOpt {
@@ -195,6 +361,8 @@ impl RelationOpt {
             RelationOptCommands::Create(create) => create.run(),
             RelationOptCommands::Update(update) => update.run(),
             RelationOptCommands::Materialize(materialize) => materialize.run(),
+            RelationOptCommands::Show(show) => show.run(),
+            RelationOptCommands::Path(path) => path.run(),
         }
     }
 }
@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use comfy_table::{ContentLineStyle, LineStyle, TableStyle};
+use serde_json::{Map, Value};
+
+use minerva::error::{Error, RuntimeError};
+use minerva::trend_query::{TrendQuery, TrendQueryResult, meas_value_to_json, meas_value_to_text};
+
+use super::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendQueryOpt {
+    #[arg(help = "data source of the trend store")]
+    data_source: String,
+    #[arg(help = "entity type of the trend store")]
+    entity_type: String,
+    #[arg(
+        help="granularity of the trend store",
+        value_parser=humantime::parse_duration
+    )]
+    granularity: Duration,
+    #[arg(
+        long = "trend",
+        required = true,
+        help = "trend to query, can be repeated"
+    )]
+    trends: Vec<String>,
+    #[arg(
+        long = "alias-type",
+        help = "alias type to resolve the aliases of, can be repeated"
+    )]
+    alias_types: Vec<String>,
+    #[arg(
+        long = "entity",
+        conflicts_with = "entity_set",
+        help = "name of an entity to query, can be repeated"
+    )]
+    entities: Vec<String>,
+    #[arg(long, help = "entity set to query as '<owner>:<name>'")]
+    entity_set: Option<String>,
+    #[arg(long, help = "start of the time range (inclusive)")]
+    start: Option<DateTime<Utc>>,
+    #[arg(long, help = "end of the time range (inclusive)")]
+    end: Option<DateTime<Utc>>,
+    #[arg(long, help = "maximum number of rows")]
+    limit: Option<i64>,
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, help = "output format")]
+    format: OutputFormat,
+}
+
+impl TrendQueryOpt {
+    fn trend_query(&self) -> Result<TrendQuery, Error> {
+        let mut query = TrendQuery::new(&self.data_source, &self.entity_type, self.granularity)
+            .with_trends(&self.trends)
+            .with_alias_types(&self.alias_types);
+
+        if let Some(entity_set) = &self.entity_set {
+            let (owner, name) = entity_set.split_once(':').ok_or_else(|| {
+                RuntimeError::from_msg(format!(
+                    "Entity set '{entity_set}' is not specified as '<owner>:<name>'"
+                ))
+            })?;
+
+            query = query.with_entity_set(owner, name);
+        } else if !self.entities.is_empty() {
+            query = query.with_entities(&self.entities);
+        }
+
+        query.start = self.start;
+        query.end = self.end;
+        query.limit = self.limit;
+
+        Ok(query)
+    }
+
+    async fn query(&self) -> CmdResult {
+        let query = self.trend_query()?;
+
+        let client = connect_db().await?;
+
+        let result = query
+            .execute(&client)
+            .await
+            .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        match self.format {
+            OutputFormat::Table => print_table(&result),
+            OutputFormat::Csv => print_csv(&result)?,
+            OutputFormat::Json => print_json(&result)?,
+        }
+
+        Ok(())
+    }
+}
+
+fn header(result: &TrendQueryResult) -> Vec<String> {
+    let mut header = vec![
+        "entity".to_string(),
+        "alias".to_string(),
+        "timestamp".to_string(),
+    ];
+
+    header.extend(result.alias_types.iter().cloned());
+    header.extend(result.trends.iter().cloned());
+
+    header
+}
+
+fn text_rows(result: &TrendQueryResult) -> impl Iterator<Item = Vec<String>> + '_ {
+    result.rows.iter().map(|row| {
+        let mut values = vec![
+            row.entity.clone(),
+            row.alias.clone().unwrap_or_default(),
+            row.timestamp.to_rfc3339(),
+        ];
+
+        values.extend(
+            row.aliases
+                .iter()
+                .map(|alias| alias.clone().unwrap_or_default()),
+        );
+        values.extend(row.values.iter().map(meas_value_to_text));
+
+        values
+    })
+}
+
+fn print_table(result: &TrendQueryResult) {
+    let mut table = comfy_table::Table::new();
+    let style = TableStyle::new()
+        .top_border(LineStyle::none())
+        .header_lines(ContentLineStyle::none().junction('┆'))
+        .header_separator(LineStyle::none().junction('╪').fill('═'))
+        .content_lines(ContentLineStyle::none().junction('┆'))
+        .row_separator(LineStyle::none())
+        .bottom_border(LineStyle::none());
+    table.load_style(style);
+    table.set_header(header(result));
+
+    for row in text_rows(result) {
+        table.add_row(row);
+    }
+
+    println!("{table}");
+}
+
+fn print_csv(result: &TrendQueryResult) -> CmdResult {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    writer
+        .write_record(header(result))
+        .and_then(|()| {
+            text_rows(result).try_for_each(|row| writer.write_record(row))?;
+            writer.flush().map_err(csv::Error::from)
+        })
+        .map_err(|e| RuntimeError::from_msg(format!("Could not write CSV: {e}")))?;
+
+    Ok(())
+}
+
+fn print_json(result: &TrendQueryResult) -> CmdResult {
+    let rows: Vec<Value> = result
+        .rows
+        .iter()
+        .map(|row| {
+            let mut object = Map::new();
+
+            object.insert("entity".to_string(), Value::from(row.entity.clone()));
+            object.insert(
+                "alias".to_string(),
+                row.alias.clone().map_or(Value::Null, Value::from),
+            );
+            object.insert(
+                "timestamp".to_string(),
+                Value::from(row.timestamp.to_rfc3339()),
+            );
+
+            for (alias_type, alias) in result.alias_types.iter().zip(&row.aliases) {
+                object.insert(
+                    alias_type.clone(),
+                    alias.clone().map_or(Value::Null, Value::from),
+                );
+            }
+
+            for (trend, value) in result.trends.iter().zip(&row.values) {
+                object.insert(trend.clone(), meas_value_to_json(value));
+            }
+
+            Value::Object(object)
+        })
+        .collect();
+
+    let output = serde_json::to_string_pretty(&rows)
+        .map_err(|e| RuntimeError::from_msg(format!("Could not serialize result: {e}")))?;
+
+    println!("{output}");
+
+    Ok(())
+}
+
+impl Cmd for TrendQueryOpt {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.query())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendOpt {
+    #[command(subcommand)]
+    command: TrendOptCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum TrendOptCommands {
+    #[command(about = "query trend data of a trend store")]
+    Query(TrendQueryOpt),
+}
+
+impl TrendOpt {
+    /// # Errors
+    ///
+    /// Will return `Err` if a subcommand returns an error.
+    pub fn run(&self) -> CmdResult {
+        match &self.command {
+            TrendOptCommands::Query(query) => query.run(),
+        }
+    }
+}
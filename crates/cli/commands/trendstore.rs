@@ -4,13 +4,19 @@ pub mod check;
 pub mod clean;
 pub mod create;
 pub mod delete;
+pub mod deletedata;
 pub mod deletetimestamp;
 pub mod diff;
 pub mod dump;
+pub mod export;
+pub mod health;
 pub mod list;
+pub mod movetrend;
 pub mod part;
 pub mod partition;
+pub mod recommendtypes;
 pub mod renametrend;
+pub mod retention;
 pub mod statistics;
 pub mod update;
 
@@ -19,13 +25,19 @@ use check::TrendStoreCheck;
 use clean::TrendStoreClean;
 use create::TrendStoreCreate;
 use delete::TrendStoreDelete;
+use deletedata::TrendStoreDeleteData;
 use deletetimestamp::TrendStoreDeleteTimestamp;
 use diff::TrendStoreDiff;
 use dump::TrendStoreDump;
+use export::TrendStoreExport;
+use health::TrendStoreHealth;
 use list::TrendStoreList;
+use movetrend::TrendStoreMoveTrend;
 use part::{TrendStorePartOpt, TrendStorePartOptCommands};
 use partition::{TrendStorePartition, TrendStorePartitionCommands};
+use recommendtypes::TrendStoreRecommendTypes;
 use renametrend::TrendStoreRenameTrend;
+use retention::{TrendStoreRetention, TrendStoreRetentionCommands};
 use statistics::TrendStoreStatistics;
 use update::TrendStoreUpdate;
 
@@ -55,14 +67,28 @@ pub enum TrendStoreOptCommands {
     Part(TrendStorePartOpt),
     #[command(about = "rename a trend")]
     RenameTrend(TrendStoreRenameTrend),
+    #[command(about = "move a trend with its data to another part of the same trend store")]
+    MoveTrend(TrendStoreMoveTrend),
     #[command(about = "delete all data for a specific timestamp")]
     DeleteTimestamp(TrendStoreDeleteTimestamp),
+    #[command(
+        about = "delete data for a range of timestamps, optionally for specific entities and trends"
+    )]
+    DeleteData(TrendStoreDeleteData),
     #[command(about = "dump the definition of a trend store")]
     Dump(TrendStoreDump),
+    #[command(about = "export the data of a trend store or trend store part")]
+    Export(TrendStoreExport),
     #[command(about = "recalculate all trend statistics")]
     Statistics(TrendStoreStatistics),
     #[command(about = "remove extraneous trends")]
     Clean(TrendStoreClean),
+    #[command(about = "retention management commands")]
+    Retention(TrendStoreRetention),
+    #[command(about = "report the completeness of the data of a trend store part")]
+    Health(TrendStoreHealth),
+    #[command(about = "recommend data types of trends based on their statistics")]
+    RecommendTypes(TrendStoreRecommendTypes),
 }
 
 impl TrendStoreOpt {
@@ -84,12 +110,22 @@ impl TrendStoreOpt {
             TrendStoreOptCommands::Check(check) => check.run(),
             TrendStoreOptCommands::Part(part) => match &part.command {
                 TrendStorePartOptCommands::Analyze(analyze) => analyze.run(),
+                TrendStorePartOptCommands::Split(split) => split.run(),
+                TrendStorePartOptCommands::Merge(merge) => merge.run(),
             },
             TrendStoreOptCommands::RenameTrend(rename_trend) => rename_trend.run(),
+            TrendStoreOptCommands::MoveTrend(move_trend) => move_trend.run(),
             TrendStoreOptCommands::DeleteTimestamp(delete_timestamp) => delete_timestamp.run(),
+            TrendStoreOptCommands::DeleteData(delete_data) => delete_data.run(),
             TrendStoreOptCommands::Dump(dump) => dump.run(),
+            TrendStoreOptCommands::Export(export) => export.run(),
             TrendStoreOptCommands::Statistics(stats) => stats.run(),
             TrendStoreOptCommands::Clean(clean) => clean.run(),
+            TrendStoreOptCommands::Retention(retention) => match &retention.command {
+                TrendStoreRetentionCommands::Apply(apply) => apply.run(),
+            },
+            TrendStoreOptCommands::Health(health) => health.run(),
+            TrendStoreOptCommands::RecommendTypes(recommend_types) => recommend_types.run(),
         }
     }
 }
@@ -1,17 +1,15 @@
 use erased_serde::{Serialize, Serializer};
 use std::collections::HashMap;
-use std::fs::create_dir_all;
 use std::path::PathBuf;
 
 use clap::Parser;
 
 use minerva::change::Change;
 use minerva::changes::trend_store::RemoveTrends;
-use minerva::error::RuntimeError;
 use minerva::trend_store::get_trends_to_delete;
 
 use crate::commands::common::{Cmd, CmdResult, connect_db};
-use crate::commands::update::update_variation;
+use crate::commands::update::{change_log_dir, update_variation};
 
 #[derive(Debug, Parser, PartialEq)]
 pub struct TrendStoreClean {
@@ -25,17 +23,7 @@ pub struct TrendStoreClean {
 
 impl TrendStoreClean {
     async fn clean(&self) -> CmdResult {
-        let default_log_dir = PathBuf::from("/var/lib/minerva/log");
-        let log_dir = self.log_dir.clone().unwrap_or(default_log_dir);
-
-        if !log_dir.exists() {
-            create_dir_all(&log_dir).map_err(|e| {
-                RuntimeError::from_msg(format!(
-                    "Could not create log directory '{}': {e}",
-                    log_dir.to_string_lossy()
-                ))
-            })?;
-        }
+        let log_dir = change_log_dir(self.log_dir.as_ref())?;
 
         let mut client = connect_db().await?;
 
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use clap::Parser;
+
+use minerva::trend_store::delete::DeleteTrendData;
+
+use crate::commands::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreDeleteData {
+    #[arg(help = "name of trend store part")]
+    trend_store_part: String,
+    #[arg(long, help = "start of the range to delete (inclusive)")]
+    from: DateTime<Utc>,
+    #[arg(long, help = "end of the range to delete (inclusive)")]
+    to: DateTime<Utc>,
+    #[arg(
+        long = "entity",
+        help = "name of an entity to delete the data of, all entities when omitted"
+    )]
+    entities: Vec<String>,
+    #[arg(
+        long = "trend",
+        help = "trend to delete the values of, complete rows when omitted"
+    )]
+    trends: Vec<String>,
+}
+
+impl TrendStoreDeleteData {
+    async fn delete_data(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let delete = DeleteTrendData {
+            trend_store_part: self.trend_store_part.clone(),
+            from: self.from,
+            to: self.to,
+            entities: (!self.entities.is_empty()).then(|| self.entities.clone()),
+            trends: (!self.trends.is_empty()).then(|| self.trends.clone()),
+        };
+
+        let deleted = delete.execute(&mut client).await?;
+
+        for (timestamp, count) in &deleted.timestamps {
+            println!("{timestamp}: {count} rows");
+        }
+
+        println!(
+            "Deleted data of {} rows in '{}', marked {} timestamps modified",
+            deleted.row_count(),
+            self.trend_store_part,
+            deleted.timestamps.len()
+        );
+
+        Ok(())
+    }
+}
+
+impl Cmd for TrendStoreDeleteData {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.delete_data())
+    }
+}
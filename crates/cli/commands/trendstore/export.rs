@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, ValueEnum};
+
+use minerva::error::{ConfigurationError, Error, RuntimeError};
+use minerva::trend_export::{TrendExport, TrendExportFormat, TrendExportSource};
+use minerva::trend_store::TrendStoreRef;
+
+use crate::commands::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl From<ExportFormat> for TrendExportFormat {
+    fn from(value: ExportFormat) -> Self {
+        match value {
+            ExportFormat::Csv => TrendExportFormat::Csv,
+            ExportFormat::Jsonl => TrendExportFormat::Jsonl,
+            ExportFormat::Parquet => TrendExportFormat::Parquet,
+        }
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreExport {
+    #[arg(
+        num_args = 1..=3,
+        required = true,
+        help = "name of a trend store part, or data source, entity type and granularity of a trend store"
+    )]
+    source: Vec<String>,
+    #[arg(long, help = "start of the period to export (inclusive)")]
+    from: Option<DateTime<Utc>>,
+    #[arg(long, help = "end of the period to export (inclusive)")]
+    to: Option<DateTime<Utc>>,
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ExportFormat::Csv,
+        help = "output format, only csv exports can be loaded again with load-data"
+    )]
+    format: ExportFormat,
+    #[arg(long, help = "file to write the export to instead of stdout")]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "file to write a parser config to for loading a CSV export with load-data"
+    )]
+    parser_config: Option<PathBuf>,
+}
+
+impl TrendStoreExport {
+    fn export_source(&self) -> Result<TrendExportSource, Error> {
+        match self.source.as_slice() {
+            [part] => Ok(TrendExportSource::TrendStorePart(part.clone())),
+            [data_source, entity_type, granularity] => {
+                let granularity: Duration =
+                    humantime::parse_duration(granularity).map_err(|e| {
+                        ConfigurationError::from_msg(format!(
+                            "Could not parse granularity '{granularity}': {e}"
+                        ))
+                    })?;
+
+                Ok(TrendExportSource::TrendStore(TrendStoreRef {
+                    data_source: data_source.clone(),
+                    entity_type: entity_type.clone(),
+                    granularity,
+                }))
+            }
+            _ => Err(Error::Configuration(ConfigurationError::from_msg(
+                "Specify a trend store part name, or a data source, entity type and granularity"
+                    .to_string(),
+            ))),
+        }
+    }
+
+    async fn export(&self) -> CmdResult {
+        let export = TrendExport::new(self.export_source()?, self.format.into())
+            .with_time_range(self.from, self.to);
+
+        let client = connect_db().await?;
+
+        if let Some(path) = &self.parser_config {
+            let parser_config = export
+                .parser_config(&client)
+                .await
+                .map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+            let file = std::fs::File::create(path).map_err(|e| {
+                RuntimeError::from_msg(format!(
+                    "Could not create parser config file '{}': {e}",
+                    path.to_string_lossy()
+                ))
+            })?;
+
+            serde_json::to_writer_pretty(file, &parser_config).map_err(|e| {
+                RuntimeError::from_msg(format!("Could not write parser config: {e}"))
+            })?;
+        }
+
+        let result = match &self.output {
+            Some(path) => {
+                let mut file = tokio::fs::File::create(path).await.map_err(|e| {
+                    RuntimeError::from_msg(format!(
+                        "Could not create output file '{}': {e}",
+                        path.to_string_lossy()
+                    ))
+                })?;
+
+                export.write(&client, &mut file).await
+            }
+            None => export.write(&client, &mut tokio::io::stdout()).await,
+        };
+
+        let byte_count =
+            result.map_err(|e| Error::Runtime(RuntimeError::from_msg(e.to_string())))?;
+
+        if let Some(path) = &self.output {
+            println!(
+                "Exported {byte_count} bytes to '{}'",
+                path.to_string_lossy()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Cmd for TrendStoreExport {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.export())
+    }
+}
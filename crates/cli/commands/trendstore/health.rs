@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{CellAlignment, Table};
+
+use minerva::trend_store::health::{TimestampHealth, trend_store_part_health};
+
+use crate::commands::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreHealth {
+    #[arg(help = "name of trend store part")]
+    name: String,
+    #[arg(long, help = "start of the period (inclusive)")]
+    start: Option<DateTime<Utc>>,
+    #[arg(
+        long,
+        help = "end of the period (inclusive), defaults to the last timestamp with data"
+    )]
+    end: Option<DateTime<Utc>>,
+    #[arg(
+        long,
+        default_value_t = 4,
+        help = "number of preceding periods to derive the expected entity count from"
+    )]
+    baseline_periods: u32,
+    #[arg(
+        long,
+        default_value_t = 0.95,
+        help = "completeness below which a timestamp is marked as incomplete"
+    )]
+    threshold: f64,
+}
+
+fn format_delay(delay: Option<std::time::Duration>) -> String {
+    delay
+        .map(|delay| {
+            humantime::format_duration(std::time::Duration::from_secs(delay.as_secs())).to_string()
+        })
+        .unwrap_or_default()
+}
+
+impl TrendStoreHealth {
+    fn status(&self, health: &TimestampHealth) -> &'static str {
+        match health.completeness() {
+            Some(completeness) if completeness < self.threshold => "incomplete",
+            Some(_) => "ok",
+            None if health.entity_count == 0 => "no data",
+            None => "",
+        }
+    }
+
+    async fn health(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let health = trend_store_part_health(
+            &client,
+            &self.name,
+            self.start,
+            self.end,
+            self.baseline_periods,
+        )
+        .await?;
+
+        let (Some(start), Some(end)) = (health.start, health.end) else {
+            println!("No data in trend store part '{}'", self.name);
+
+            return Ok(());
+        };
+
+        println!("Data health of '{}' from {start} to {end}", self.name);
+
+        let mut table = Table::new();
+        table
+            .load_style(UTF8_FULL_CONDENSED.with_rounded_corners())
+            .set_header(vec![
+                "Timestamp",
+                "Entities",
+                "Expected",
+                "Completeness",
+                "Jobs",
+                "Redelivered",
+                "Avg delay",
+                "Max delay",
+                "Status",
+            ]);
+
+        for timestamp in &health.timestamps {
+            table.add_row(vec![
+                timestamp.timestamp.to_string(),
+                timestamp.entity_count.to_string(),
+                timestamp
+                    .expected_count
+                    .map(|count| count.to_string())
+                    .unwrap_or_default(),
+                timestamp
+                    .completeness()
+                    .map(|completeness| format!("{:.1}%", completeness * 100.0))
+                    .unwrap_or_default(),
+                timestamp.job_count.to_string(),
+                timestamp.redelivered_count.to_string(),
+                format_delay(timestamp.avg_delay),
+                format_delay(timestamp.max_delay),
+                self.status(timestamp).to_string(),
+            ]);
+        }
+
+        for column in 1..6 {
+            if let Some(column) = table.column_mut(column) {
+                column.set_cell_alignment(CellAlignment::Right);
+            }
+        }
+
+        println!("{table}");
+
+        let mut table = Table::new();
+        table
+            .load_style(UTF8_FULL_CONDENSED.with_rounded_corners())
+            .set_header(vec!["Trend", "NULL ratio"]);
+
+        for trend in &health.trends {
+            table.add_row(vec![
+                trend.trend.clone(),
+                format!("{:.1}%", trend.null_ratio * 100.0),
+            ]);
+        }
+
+        if let Some(column) = table.column_mut(1) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+
+        println!("{table}");
+
+        Ok(())
+    }
+}
+
+impl Cmd for TrendStoreHealth {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.health())
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use minerva::change::Change;
+use minerva::changes::trend_store::MoveTrend;
+
+use crate::commands::common::{Cmd, CmdResult, connect_db};
+use crate::commands::update::{change_log_dir, update_variation};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreMoveTrend {
+    #[arg(help = "name of trend store part containing the trend")]
+    trend_store_part: String,
+    #[arg(help = "name of trend")]
+    trend: String,
+    #[arg(help = "name of trend store part to move the trend to")]
+    to: String,
+    #[arg(short, long)]
+    interactive: bool,
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+}
+
+impl TrendStoreMoveTrend {
+    async fn move_trend(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let changes: Vec<Box<dyn Change>> = vec![Box::new(MoveTrend {
+            trend_store_part_name: self.trend_store_part.clone(),
+            trend_name: self.trend.clone(),
+            target_part_name: self.to.clone(),
+        })];
+
+        update_variation(
+            &mut client,
+            &change_log_dir(self.log_dir.as_ref())?,
+            changes,
+            self.interactive,
+        )
+        .await
+    }
+}
+
+impl Cmd for TrendStoreMoveTrend {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.move_trend())
+    }
+}
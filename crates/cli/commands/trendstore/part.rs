@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
 use clap::Subcommand;
+use regex::Regex;
 
 use term_table::{
     Table, TableStyle,
@@ -8,9 +11,13 @@ use term_table::{
     table_cell::{Alignment, TableCell},
 };
 
-use minerva::trend_store::analyze_trend_store_part;
+use minerva::change::Change;
+use minerva::changes::trend_store::{MergeTrendStoreParts, SplitTrendStorePart};
+use minerva::error::{ConfigurationError, Error, RuntimeError};
+use minerva::trend_store::{analyze_trend_store_part, load_trend_store_part};
 
 use crate::commands::common::{Cmd, CmdResult, connect_db};
+use crate::commands::update::{change_log_dir, update_variation};
 
 #[derive(Debug, Parser, PartialEq)]
 pub struct TrendStorePartAnalyze {
@@ -66,6 +73,124 @@ impl Cmd for TrendStorePartAnalyze {
     }
 }
 
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStorePartSplit {
+    #[arg(help = "name of trend store part to move trends from")]
+    name: String,
+    #[arg(
+        long,
+        help = "regular expression matching the names of the trends to move"
+    )]
+    trends: String,
+    #[arg(
+        long,
+        help = "name of new or existing trend store part to move the trends into"
+    )]
+    into: String,
+    #[arg(short, long)]
+    interactive: bool,
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+}
+
+impl TrendStorePartSplit {
+    async fn split(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let trends_regex = Regex::new(&self.trends).map_err(|e| {
+            ConfigurationError::from_msg(format!(
+                "Invalid trend regular expression '{}': {e}",
+                self.trends
+            ))
+        })?;
+
+        let trend_store_part = load_trend_store_part(&client, &self.name)
+            .await
+            .map_err(|e| RuntimeError::from_msg(e.to_string()))?;
+
+        let trends: Vec<String> = trend_store_part
+            .trends
+            .into_iter()
+            .map(|trend| trend.name)
+            .filter(|name| trends_regex.is_match(name))
+            .collect();
+
+        if trends.is_empty() {
+            return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                "No trends of '{}' match '{}'",
+                self.name, self.trends
+            ))));
+        }
+
+        let changes: Vec<Box<dyn Change>> = vec![Box::new(SplitTrendStorePart {
+            trend_store_part_name: self.name.clone(),
+            trends,
+            target_part_name: self.into.clone(),
+            references: Vec::new(),
+        })];
+
+        update_variation(
+            &mut client,
+            &change_log_dir(self.log_dir.as_ref())?,
+            changes,
+            self.interactive,
+        )
+        .await
+    }
+}
+
+impl Cmd for TrendStorePartSplit {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.split())
+    }
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStorePartMerge {
+    #[arg(help = "name of trend store part to merge and remove")]
+    name: String,
+    #[arg(long, help = "name of trend store part to move the trends into")]
+    into: String,
+    #[arg(short, long)]
+    interactive: bool,
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+}
+
+impl TrendStorePartMerge {
+    async fn merge(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        let changes: Vec<Box<dyn Change>> = vec![Box::new(MergeTrendStoreParts {
+            trend_store_part_name: self.name.clone(),
+            target_part_name: self.into.clone(),
+            references: Vec::new(),
+        })];
+
+        update_variation(
+            &mut client,
+            &change_log_dir(self.log_dir.as_ref())?,
+            changes,
+            self.interactive,
+        )
+        .await
+    }
+}
+
+impl Cmd for TrendStorePartMerge {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.merge())
+    }
+}
+
 #[derive(Debug, Parser, PartialEq)]
 pub struct TrendStorePartOpt {
     #[command(subcommand)]
@@ -76,4 +201,8 @@ pub struct TrendStorePartOpt {
 pub enum TrendStorePartOptCommands {
     #[command(about = "analyze range of values for trends in a trend store part")]
     Analyze(TrendStorePartAnalyze),
+    #[command(about = "move trends with their data into a new or existing trend store part")]
+    Split(TrendStorePartSplit),
+    #[command(about = "move all trends with their data into another trend store part")]
+    Merge(TrendStorePartMerge),
 }
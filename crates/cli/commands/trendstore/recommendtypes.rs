@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use comfy_table::Table;
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+
+use minerva::change::Change;
+use minerva::error::RuntimeError;
+use minerva::trend_store::recommend::{
+    RecommendOptions, recommend_trend_data_types, recommendations_to_change,
+};
+
+use crate::commands::common::{Cmd, CmdResult, connect_db};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreRecommendTypes {
+    #[arg(help = "name of trend store part")]
+    name: String,
+    #[arg(
+        long,
+        default_value_t = 0.8,
+        help = "fraction of the range of a type from which values are at risk of overflowing"
+    )]
+    overflow_ratio: f64,
+    #[arg(
+        long,
+        default_value_t = 0.1,
+        help = "fraction of the range of a smaller type within which values must stay to recommend it"
+    )]
+    downsize_ratio: f64,
+    #[arg(
+        long,
+        default_value = "7d",
+        value_parser = humantime::parse_duration,
+        help = "period before now of which the values are checked to convert losslessly"
+    )]
+    check_period: Duration,
+    #[arg(
+        short,
+        long,
+        help = "write the lossless recommendations to a diff file for 'minerva update --from-diff'"
+    )]
+    output: Option<PathBuf>,
+}
+
+impl TrendStoreRecommendTypes {
+    async fn recommend_types(&self) -> CmdResult {
+        let client = connect_db().await?;
+
+        let options = RecommendOptions {
+            overflow_ratio: self.overflow_ratio,
+            downsize_ratio: self.downsize_ratio,
+            check_period: self.check_period,
+        };
+
+        let (recommendations, total_trend_count) =
+            recommend_trend_data_types(&client, &self.name, &options).await?;
+
+        if recommendations.is_empty() {
+            println!("No data type changes recommended for '{}'", self.name);
+
+            return Ok(());
+        }
+
+        let mut table = Table::new();
+        table
+            .load_style(UTF8_FULL_CONDENSED.with_rounded_corners())
+            .set_header(vec![
+                "Trend",
+                "Type",
+                "Min",
+                "Max",
+                "Recommended",
+                "Reason",
+                "Lossless",
+            ]);
+
+        for recommendation in &recommendations {
+            table.add_row(vec![
+                recommendation.trend.clone(),
+                recommendation.from_type.to_string(),
+                recommendation
+                    .min
+                    .map(|min| min.to_string())
+                    .unwrap_or_default(),
+                recommendation
+                    .max
+                    .map(|max| max.to_string())
+                    .unwrap_or_default(),
+                recommendation.to_type.to_string(),
+                recommendation.reason.to_string(),
+                match &recommendation.conversion_error {
+                    None => "yes".to_string(),
+                    Some(e) => format!("no: {e}"),
+                },
+            ]);
+        }
+
+        println!("{table}");
+
+        if let Some(path) = &self.output {
+            let changes: Vec<Box<dyn Change + Send>> =
+                recommendations_to_change(&self.name, &recommendations, total_trend_count)
+                    .map(|change| Box::new(change) as Box<dyn Change + Send>)
+                    .into_iter()
+                    .collect();
+
+            if changes.is_empty() {
+                println!("No lossless data type changes to write");
+
+                return Ok(());
+            }
+
+            let file = std::fs::File::create(path).map_err(|e| {
+                RuntimeError::from_msg(format!(
+                    "Could not create diff file '{}': {e}",
+                    path.to_string_lossy()
+                ))
+            })?;
+
+            serde_json::to_writer_pretty(file, &changes)
+                .map_err(|e| RuntimeError::from_msg(format!("Could not write diff file: {e}")))?;
+
+            println!(
+                "Written diff to '{}', apply it with 'minerva update --from-diff'",
+                path.to_string_lossy()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Cmd for TrendStoreRecommendTypes {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.recommend_types())
+    }
+}
@@ -0,0 +1,266 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{CellAlignment, Table};
+
+use minerva::change::Change;
+use minerva::changes::trend_store::ModifyTrendStoreData;
+use minerva::changes::trend_store::RemoveTrendStorePartition;
+use minerva::error::{ConfigurationError, Error, RuntimeError};
+use minerva::instance::load_instance_config;
+use minerva::trend_store::retention::{
+    ExpiredPartition, expired_partitions_with_retention, retention_default_changes,
+};
+use minerva::trend_store::{TrendStoreRef, load_trend_stores};
+
+use crate::commands::common::{Cmd, CmdResult, ENV_MINERVA_INSTANCE_ROOT, connect_db};
+use crate::commands::update::{change_log_dir, update_variation};
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreRetention {
+    #[command(subcommand)]
+    pub command: TrendStoreRetentionCommands,
+}
+
+#[derive(Debug, Subcommand, PartialEq)]
+pub enum TrendStoreRetentionCommands {
+    #[command(about = "remove partitions with data older than the retention period")]
+    Apply(TrendStoreRetentionApply),
+}
+
+#[derive(Debug, Parser, PartialEq)]
+pub struct TrendStoreRetentionApply {
+    #[arg(
+        long,
+        num_args = 3,
+        value_names = ["DATA_SOURCE", "ENTITY_TYPE", "GRANULARITY"],
+        help = "only apply retention to this trend store"
+    )]
+    trend_store: Option<Vec<String>>,
+    #[arg(long, help = "only report the partitions that would be removed")]
+    dry_run: bool,
+    #[arg(
+        long,
+        help = "directory to archive the data of partitions to as CSV before removing them"
+    )]
+    archive_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "first set the retention period of trend stores to the defaults from the instance config"
+    )]
+    apply_defaults: bool,
+    #[arg(short = 'r', long, help = "Minerva instance root directory")]
+    instance_root: Option<PathBuf>,
+    #[arg(short, long)]
+    interactive: bool,
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+}
+
+impl TrendStoreRetentionApply {
+    fn trend_store_ref(&self) -> Result<Option<TrendStoreRef>, Error> {
+        match self.trend_store.as_deref() {
+            None => Ok(None),
+            Some([data_source, entity_type, granularity]) => {
+                let granularity = humantime::parse_duration(granularity).map_err(|e| {
+                    ConfigurationError::from_msg(format!(
+                        "Could not parse granularity '{granularity}': {e}"
+                    ))
+                })?;
+
+                Ok(Some(TrendStoreRef {
+                    data_source: data_source.clone(),
+                    entity_type: entity_type.clone(),
+                    granularity,
+                }))
+            }
+            Some(_) => Err(Error::Configuration(ConfigurationError::from_msg(
+                "Specify the trend store as data source, entity type and granularity".to_string(),
+            ))),
+        }
+    }
+
+    /// Changes that set the retention periods of the selected trend stores to the defaults
+    async fn default_changes(
+        &self,
+        client: &mut tokio_postgres::Client,
+    ) -> Result<Vec<ModifyTrendStoreData>, Error> {
+        let instance_root = match &self.instance_root {
+            Some(path) => path.clone(),
+            None => match std::env::var(ENV_MINERVA_INSTANCE_ROOT) {
+                Ok(path) => PathBuf::from(path),
+                Err(_) => std::env::current_dir().map_err(|e| {
+                    RuntimeError::from_msg(format!("Could not determine current directory: {e}"))
+                })?,
+            },
+        };
+
+        let instance_config = load_instance_config(&instance_root)
+            .map_err(|e| format!("Could not load instance config: {e}"))?;
+
+        let trend_store_ref = self.trend_store_ref()?;
+
+        let trend_stores: Vec<_> = load_trend_stores(client)
+            .await?
+            .into_iter()
+            .filter(|trend_store| match &trend_store_ref {
+                Some(trend_store_ref) => {
+                    trend_store.data_source == trend_store_ref.data_source
+                        && trend_store.entity_type == trend_store_ref.entity_type
+                        && trend_store.granularity == trend_store_ref.granularity
+                }
+                None => true,
+            })
+            .collect();
+
+        Ok(retention_default_changes(&trend_stores, &instance_config))
+    }
+
+    async fn apply(&self) -> CmdResult {
+        let mut client = connect_db().await?;
+
+        // In a dry run the defaults are not applied, so the partitions are determined with the
+        // default retention periods instead
+        let mut retention_periods: Vec<(TrendStoreRef, std::time::Duration)> = Vec::new();
+
+        if self.apply_defaults {
+            let changes = self.default_changes(&mut client).await?;
+
+            if changes.is_empty() {
+                println!("Retention periods of trend stores match the defaults");
+            } else if self.dry_run {
+                for change in &changes {
+                    println!("Would apply {change}");
+
+                    if let Some(retention_period) = change.retention_period {
+                        retention_periods.push((change.trend_store.clone(), retention_period));
+                    }
+                }
+            } else {
+                let changes: Vec<Box<dyn Change>> = changes
+                    .into_iter()
+                    .map(|change| Box::new(change) as Box<dyn Change>)
+                    .collect();
+
+                update_variation(
+                    &mut client,
+                    &change_log_dir(self.log_dir.as_ref())?,
+                    changes,
+                    self.interactive,
+                )
+                .await?;
+            }
+        }
+
+        let trend_store_ref = self.trend_store_ref()?;
+
+        let partitions = expired_partitions_with_retention(
+            &client,
+            trend_store_ref.as_ref(),
+            &retention_periods,
+        )
+        .await?;
+
+        print_partitions(&partitions);
+
+        if self.dry_run || partitions.is_empty() {
+            return Ok(());
+        }
+
+        let mut changes: Vec<Box<dyn Change>> = Vec::new();
+
+        // The archives are written by the changes, so only for partitions of which the removal
+        // is confirmed
+        for partition in &partitions {
+            changes.push(Box::new(RemoveTrendStorePartition {
+                partition: partition.name.clone(),
+                archive: self.archive_dir.as_ref().map(|archive_dir| {
+                    archive_dir
+                        .join(format!("{}.csv", partition.name))
+                        .to_string_lossy()
+                        .to_string()
+                }),
+            }));
+        }
+
+        update_variation(
+            &mut client,
+            &change_log_dir(self.log_dir.as_ref())?,
+            changes,
+            self.interactive,
+        )
+        .await
+    }
+}
+
+fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn print_partitions(partitions: &[ExpiredPartition]) {
+    let mut table = Table::new();
+    table
+        .load_style(UTF8_FULL_CONDENSED.with_rounded_corners())
+        .set_header(vec![
+            "Trend store part",
+            "Partition",
+            "From",
+            "To",
+            "Rows (estimate)",
+            "Size",
+        ]);
+
+    for partition in partitions {
+        table.add_row(vec![
+            partition.trend_store_part.clone(),
+            partition.name.clone(),
+            partition.from.to_string(),
+            partition.to.to_string(),
+            partition.row_count.to_string(),
+            format_size(partition.size),
+        ]);
+    }
+
+    for column in [4, 5] {
+        if let Some(column) = table.column_mut(column) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+
+    println!("{table}");
+
+    let row_count: i64 = partitions.iter().map(|p| p.row_count).sum();
+    let size: i64 = partitions.iter().map(|p| p.size).sum();
+
+    println!(
+        "{} partitions with {row_count} rows (estimate) and {} to be removed",
+        partitions.len(),
+        format_size(size)
+    );
+}
+
+impl Cmd for TrendStoreRetentionApply {
+    fn run(&self) -> CmdResult {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(self.apply())
+    }
+}
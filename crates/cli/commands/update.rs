@@ -51,19 +51,28 @@ pub struct UpdateOpt {
     log_dir: Option<PathBuf>,
 }
 
+/// Directory to log applied changes to for reverting, by default '/var/lib/minerva/log'. The
+/// directory is created if it does not exist.
+pub fn change_log_dir(log_dir: Option<&PathBuf>) -> Result<PathBuf, Error> {
+    let log_dir = log_dir
+        .cloned()
+        .unwrap_or_else(|| PathBuf::from("/var/lib/minerva/log"));
+
+    if !log_dir.exists() {
+        create_dir_all(&log_dir).map_err(|e| {
+            RuntimeError::from_msg(format!(
+                "Could not create log directory '{}': {e}",
+                log_dir.to_string_lossy()
+            ))
+        })?;
+    }
+
+    Ok(log_dir)
+}
+
 impl UpdateOpt {
     async fn update(&self) -> CmdResult {
-        let default_log_dir = PathBuf::from("/var/lib/minerva/log");
-        let log_dir = self.log_dir.clone().unwrap_or(default_log_dir);
-
-        if !log_dir.exists() {
-            create_dir_all(&log_dir).map_err(|e| {
-                RuntimeError::from_msg(format!(
-                    "Could not create log directory '{}': {e}",
-                    log_dir.to_string_lossy()
-                ))
-            })?;
-        }
+        let log_dir = change_log_dir(self.log_dir.as_ref())?;
 
         let mut client = connect_db().await?;
 
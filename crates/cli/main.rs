@@ -11,17 +11,22 @@ pub mod interact;
 use crate::commands::aggregation::AggregationOpt;
 use crate::commands::attributestore::AttributeStoreOpt;
 use crate::commands::baselinedump::BaselineDumpOpt;
+use crate::commands::check::CheckOpt;
 use crate::commands::common::Cmd;
 use crate::commands::define::DefineOpt;
 use crate::commands::diff::DiffOpt;
 use crate::commands::dump::DumpOpt;
+use crate::commands::entity::EntityOpt;
+use crate::commands::entityset::EntitySetOpt;
 use crate::commands::graph::GraphOpt;
 use crate::commands::initialize::InitializeOpt;
 use crate::commands::loaddata::LoadDataOpt;
+use crate::commands::maintenance::MaintenanceOpt;
 use crate::commands::relation::RelationOpt;
 use crate::commands::revert::RevertOpt;
 use crate::commands::schema::SchemaOpt;
 use crate::commands::start::StartOpt;
+use crate::commands::trend::TrendOpt;
 use crate::commands::trendmaterialization::TrendMaterializationOpt;
 use crate::commands::trendstore::TrendStoreOpt;
 use crate::commands::trigger::TriggerOpt;
@@ -47,6 +52,8 @@ enum Commands {
     Dump(DumpOpt),
     #[command(about = "Create a diff between Minerva instance definitions")]
     Diff(DiffOpt),
+    #[command(about = "Check an instance definition without a database")]
+    Check(CheckOpt),
     #[command(about = "Create a graph of a Minerva instance")]
     Graph(GraphOpt),
     #[command(about = "Update a Minerva database from an instance definition")]
@@ -55,6 +62,8 @@ enum Commands {
     Initialize(InitializeOpt),
     #[command(about = "Manage trend stores")]
     TrendStore(TrendStoreOpt),
+    #[command(about = "Query trend data")]
+    Trend(TrendOpt),
     #[command(about = "Manage triggers")]
     Trigger(TriggerOpt),
     #[command(about = "Manage attribute stores")]
@@ -65,12 +74,18 @@ enum Commands {
     LoadData(LoadDataOpt),
     #[command(about = "Manage relations")]
     Relation(RelationOpt),
+    #[command(about = "Manage entities")]
+    Entity(EntityOpt),
+    #[command(about = "Manage entity sets")]
+    EntitySet(EntitySetOpt),
     #[command(about = "Revert previously applied changes")]
     Revert(RevertOpt),
     #[command(about = "Start Minerva instance")]
     Start(StartOpt),
     #[command(about = "Generate standard aggregations")]
     Aggregation(AggregationOpt),
+    #[command(about = "Run scheduled partition and attribute store maintenance")]
+    Maintenance(MaintenanceOpt),
     #[command(about = "Manage virtual entities")]
     VirtualEntity(VirtualEntityOpt),
     #[command(about = "Define Minerva instance")]
@@ -111,18 +126,23 @@ fn main() -> ExitCode {
         Some(Commands::Schema(schema)) => schema.run(),
         Some(Commands::Dump(dump)) => dump.run(),
         Some(Commands::Diff(diff)) => diff.run(),
+        Some(Commands::Check(check)) => check.run(),
         Some(Commands::Graph(graph)) => graph.run(),
         Some(Commands::Update(update)) => update.run(),
         Some(Commands::Initialize(initialize)) => initialize.run(),
         Some(Commands::TrendStore(trend_store)) => trend_store.run(),
+        Some(Commands::Trend(trend)) => trend.run(),
         Some(Commands::Trigger(trigger)) => trigger.run(),
         Some(Commands::AttributeStore(attribute_store)) => attribute_store.run(),
         Some(Commands::TrendMaterialization(trend_materialization)) => trend_materialization.run(),
         Some(Commands::LoadData(load_data)) => load_data.run(),
         Some(Commands::Relation(relation)) => relation.run(),
+        Some(Commands::Entity(entity)) => entity.run(),
+        Some(Commands::EntitySet(entity_set)) => entity_set.run(),
         Some(Commands::Revert(revert)) => revert.run(),
         Some(Commands::Start(start)) => start.run(),
         Some(Commands::Aggregation(aggregation)) => aggregation.run(),
+        Some(Commands::Maintenance(maintenance)) => maintenance.run(),
         Some(Commands::VirtualEntity(virtual_entity)) => virtual_entity.run(),
         Some(Commands::Define(define)) => define.run(),
         Some(Commands::BaselineDump(baseline_dump)) => baseline_dump.run(),
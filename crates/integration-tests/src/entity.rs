@@ -1,13 +1,24 @@
+use chrono::{DateTime, Utc};
 use log::debug;
+use tokio_postgres::GenericClient;
 
+use minerva::attribute_storage::{AttributeDataRow, RawAttributeStore};
+use minerva::attribute_store::{AddAttributeStore, AttributeStore};
+use minerva::change::Change;
+use minerva::changes::entity::MergeEntity;
+use minerva::changes::trend_store::AddTrendStore;
 use minerva::cluster::MinervaClusterConnector;
-use minerva::entity::{DbEntityMapping, EntityMapping};
+use minerva::entity::{
+    CachingEntityMapping, DbEntityMapping, EntityMapping, EntityMappingError,
+    read_entity_inventory, register_entities,
+};
 use minerva::schema::create_schema;
+use minerva::trend_store::{TrendStore, create_partitions_for_timestamp};
 
 pub async fn db_entity_mapping(
     cluster: MinervaClusterConnector,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let entity_mapping = DbEntityMapping {};
+    let entity_mapping = DbEntityMapping::default();
 
     let test_database = cluster.create_db().await?;
 
@@ -80,7 +91,7 @@ pub async fn db_entity_mapping(
             "n0001".to_string(),
         ];
 
-        let entity_mapping = DbEntityMapping {};
+        let entity_mapping = DbEntityMapping::default();
         let entities = entity_mapping
             .names_to_entities(&client, &"node".to_string(), &names)
             .await?;
@@ -94,3 +105,232 @@ pub async fn db_entity_mapping(
 
     Ok(())
 }
+
+pub async fn strict_entity_mapping(
+    cluster: MinervaClusterConnector,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let test_database = cluster.create_db().await?;
+
+    debug!("Created database '{}'", test_database.name);
+
+    let mut client = test_database.connect().await?;
+    create_schema(&mut client).await?;
+
+    client
+        .query("SELECT directory.create_entity_type('node')", &[])
+        .await?;
+
+    let inventory = read_entity_inventory("name\nn0001\nn0002\nn0001\n".as_bytes())?;
+
+    let result = register_entities(&client, "node", &inventory).await?;
+
+    assert_eq!(result.created, 2);
+    assert_eq!(result.existing, 0);
+
+    let entity_mapping = DbEntityMapping { strict: true };
+
+    let known = vec!["n0001".to_string(), "n0002".to_string()];
+
+    let entity_ids = entity_mapping
+        .names_to_entity_ids(&client, &"node".to_string(), &known)
+        .await?;
+
+    assert_eq!(entity_ids.len(), 2);
+
+    let unknown = vec!["n0001".to_string(), "n0003".to_string()];
+
+    let result = entity_mapping
+        .names_to_entity_ids(&client, &"node".to_string(), &unknown)
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(EntityMappingError::UnknownEntityError(name)) if name == "n0003"
+    ));
+
+    let entity_mapping = CachingEntityMapping::new(100).with_strict(true);
+
+    let result = entity_mapping
+        .names_to_entity_ids(&client, &"node".to_string(), &unknown)
+        .await;
+
+    assert!(result.is_err());
+
+    let row = client
+        .query_one("SELECT count(*) FROM entity.node", &[])
+        .await?;
+
+    let count: i64 = row.get(0);
+
+    assert_eq!(count, 2);
+
+    Ok(())
+}
+
+const MERGE_TREND_STORE_DEFINITION: &str = r"
+data_source: hub
+entity_type: node
+granularity: 15m
+partition_size: 1d
+parts:
+  - name: hub_node_main_15m
+    trends:
+      - name: power_kwh
+        data_type: integer
+";
+
+const MERGE_ATTRIBUTE_STORE_DEFINITION: &str = r"
+data_source: hub
+entity_type: node
+attributes:
+  - name: equipment_type
+    data_type: text
+";
+
+async fn entity_row_count<T: GenericClient>(
+    client: &T,
+    query: &str,
+    name: &str,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    let row = client.query_one(query, &[&name]).await?;
+
+    Ok(row.get(0))
+}
+
+async fn merge_row_counts<T: GenericClient>(
+    client: &T,
+    name: &str,
+) -> Result<(i64, i64, i64), Box<dyn std::error::Error>> {
+    let trend_count = entity_row_count(
+        client,
+        "SELECT count(*) FROM trend.hub_node_main_15m t JOIN entity.node e ON e.id = t.entity_id WHERE e.name = $1",
+        name,
+    )
+    .await?;
+
+    let attribute_count = entity_row_count(
+        client,
+        "SELECT count(*) FROM attribute_history.hub_node a JOIN entity.node e ON e.id = a.entity_id WHERE e.name = $1",
+        name,
+    )
+    .await?;
+
+    let relation_count = entity_row_count(
+        client,
+        "SELECT count(*) FROM relation.\"node->site\" r JOIN entity.node e ON e.id = r.source_id WHERE e.name = $1",
+        name,
+    )
+    .await?;
+
+    Ok((trend_count, attribute_count, relation_count))
+}
+
+/// Merge an entity with trend, attribute and relation data into another entity and revert it
+pub async fn merge_entity(
+    cluster: MinervaClusterConnector,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let test_database = cluster.create_db().await?;
+
+    debug!("Created database '{}'", test_database.name);
+
+    let mut client = test_database.connect().await?;
+    create_schema(&mut client).await?;
+
+    let trend_store: TrendStore = serde_yaml::from_str(MERGE_TREND_STORE_DEFINITION)?;
+
+    AddTrendStore { trend_store }.apply(&mut client).await?;
+
+    let attribute_store: AttributeStore = serde_yaml::from_str(MERGE_ATTRIBUTE_STORE_DEFINITION)?;
+
+    AddAttributeStore {
+        attribute_store: attribute_store.clone(),
+    }
+    .apply(&mut client)
+    .await?;
+
+    let t1: DateTime<Utc> = DateTime::parse_from_rfc3339("2025-03-01T10:00:00+00:00")?.to_utc();
+    let t2: DateTime<Utc> = DateTime::parse_from_rfc3339("2025-03-01T10:15:00+00:00")?.to_utc();
+
+    let entity_mapping = CachingEntityMapping::new(100);
+
+    let tx = client.transaction().await?;
+    attribute_store
+        .store(
+            &tx,
+            &entity_mapping,
+            vec!["equipment_type".to_string()],
+            vec![
+                AttributeDataRow {
+                    timestamp: t1,
+                    entity_name: "node_1".to_string(),
+                    values: vec![Some("X".to_string())],
+                },
+                AttributeDataRow {
+                    timestamp: t2,
+                    entity_name: "node_2".to_string(),
+                    values: vec![Some("Y".to_string())],
+                },
+            ],
+        )
+        .await?;
+    tx.commit().await?;
+
+    create_partitions_for_timestamp(&mut client, t1).await?;
+
+    client
+        .execute(
+            concat!(
+                "INSERT INTO trend.hub_node_main_15m(entity_id, timestamp, created, job_id, power_kwh) ",
+                "SELECT e.id, v.timestamp, now(), 1, 10 FROM entity.node e ",
+                "JOIN (VALUES ('node_1', $1::timestamptz), ('node_1', $2::timestamptz), ('node_2', $1::timestamptz)) v(name, timestamp) ",
+                "ON v.name = e.name"
+            ),
+            &[&t1, &t2],
+        )
+        .await?;
+
+    client
+        .batch_execute(concat!(
+            "SELECT directory.create_entity_type('site'); ",
+            "SELECT entity.to_site('site_a'); ",
+            "SELECT entity.to_site('site_b'); ",
+            "CREATE TABLE relation.\"node->site\"(source_id integer, target_id integer, PRIMARY KEY (source_id, target_id)); ",
+            "SELECT relation_directory.name_to_type('node->site'); ",
+            "INSERT INTO relation.\"node->site\"(source_id, target_id) ",
+            "SELECT n.id, s.id FROM entity.node n, entity.site s ",
+            "WHERE (n.name, s.name) IN (('node_1', 'site_a'), ('node_2', 'site_b'))"
+        ))
+        .await?;
+
+    let merged = MergeEntity {
+        entity_type: "node".to_string(),
+        from: "node_1".to_string(),
+        to: "node_2".to_string(),
+    }
+    .apply(&mut client)
+    .await?;
+
+    // The trend row of node_1 at t1 is left in place, because node_2 already has a row there
+    assert_eq!(merge_row_counts(&client, "node_1").await?, (1, 0, 0));
+    assert_eq!(merge_row_counts(&client, "node_2").await?, (2, 2, 2));
+
+    let row = client
+        .query_one(
+            "SELECT directory.is_retired('node', id) FROM entity.node WHERE name = 'node_1'",
+            &[],
+        )
+        .await?;
+
+    assert!(row.get::<_, bool>(0));
+
+    merged
+        .revert()
+        .expect("merge should be revertible")
+        .apply(&mut client)
+        .await?;
+
+    assert_eq!(merge_row_counts(&client, "node_1").await?, (2, 1, 1));
+    assert_eq!(merge_row_counts(&client, "node_2").await?, (1, 1, 1));
+
+    Ok(())
+}
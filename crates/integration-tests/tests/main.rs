@@ -183,5 +183,16 @@ fn setup_tests(connector: Option<MinervaClusterConnector>) -> Vec<Trial> {
                 integration_tests::entity::db_entity_mapping,
             ),
         ),
+        Trial::test(
+            "strict_entity_mapping",
+            setup_test(
+                connector.clone(),
+                integration_tests::entity::strict_entity_mapping,
+            ),
+        ),
+        Trial::test(
+            "merge_entity",
+            setup_test(connector.clone(), integration_tests::entity::merge_entity),
+        ),
     ]
 }
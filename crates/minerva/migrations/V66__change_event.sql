@@ -0,0 +1,18 @@
+CREATE TABLE "directory"."change_event"
+(
+  "id" bigserial NOT NULL,
+  "timestamp" timestamp with time zone NOT NULL DEFAULT now(),
+  "type" text NOT NULL,
+  "data" jsonb NOT NULL,
+  PRIMARY KEY (id)
+);
+
+COMMENT ON TABLE "directory"."change_event" IS 'Log of changes to the data in the database, so that dependent data can be updated for only what changed.';
+
+CREATE INDEX "change_event_type_timestamp_idx" ON "directory"."change_event" USING btree ("type", "timestamp");
+
+GRANT SELECT ON TABLE "directory"."change_event" TO minerva;
+
+GRANT INSERT,UPDATE,DELETE ON TABLE "directory"."change_event" TO minerva_writer;
+
+GRANT USAGE,SELECT ON SEQUENCE "directory"."change_event_id_seq" TO minerva_writer;
@@ -0,0 +1,51 @@
+CREATE TABLE "directory"."retired_entity"
+(
+  "entity_type_id" integer NOT NULL,
+  "entity_id" bigint NOT NULL,
+  "retired" timestamp with time zone NOT NULL DEFAULT now(),
+  PRIMARY KEY (entity_type_id, entity_id)
+);
+
+COMMENT ON TABLE "directory"."retired_entity" IS 'Entities that are no longer active, so that they can be excluded from entity sets, triggers and other processing.';
+
+ALTER TABLE "directory"."retired_entity"
+  ADD CONSTRAINT "retired_entity_entity_type_id_fkey" FOREIGN KEY (entity_type_id) REFERENCES "directory"."entity_type" (id) ON DELETE CASCADE;
+
+GRANT SELECT ON TABLE "directory"."retired_entity" TO minerva;
+
+GRANT INSERT,UPDATE,DELETE ON TABLE "directory"."retired_entity" TO minerva_writer;
+
+
+CREATE FUNCTION "directory"."is_retired"("entity_type_name" text, "entity_id" bigint)
+    RETURNS boolean
+AS $$
+SELECT EXISTS(
+  SELECT 1
+  FROM directory.retired_entity re
+  JOIN directory.entity_type et ON et.id = re.entity_type_id
+  WHERE et.name = $1 AND re.entity_id = $2
+);
+$$ LANGUAGE sql STABLE;
+
+
+CREATE TABLE "directory"."entity_merge"
+(
+  "id" serial NOT NULL,
+  "entity_type_id" integer NOT NULL,
+  "from_entity_id" bigint NOT NULL,
+  "to_entity_id" bigint NOT NULL,
+  "merged" timestamp with time zone NOT NULL DEFAULT now(),
+  "reverted" timestamp with time zone,
+  PRIMARY KEY (id)
+);
+
+COMMENT ON TABLE "directory"."entity_merge" IS 'Log of the entities of which the data is merged into another entity.';
+
+ALTER TABLE "directory"."entity_merge"
+  ADD CONSTRAINT "entity_merge_entity_type_id_fkey" FOREIGN KEY (entity_type_id) REFERENCES "directory"."entity_type" (id) ON DELETE CASCADE;
+
+GRANT SELECT ON TABLE "directory"."entity_merge" TO minerva;
+
+GRANT INSERT,UPDATE,DELETE ON TABLE "directory"."entity_merge" TO minerva_writer;
+
+GRANT USAGE,SELECT ON SEQUENCE "directory"."entity_merge_id_seq" TO minerva_writer;
@@ -0,0 +1,249 @@
+CREATE SCHEMA IF NOT EXISTS "entity_set";
+COMMENT ON SCHEMA "entity_set" IS 'Stores the revisions of entity sets, with per entity type a table with the members and a table with the revision validity periods.
+';
+GRANT USAGE,CREATE ON SCHEMA "entity_set" TO "minerva_writer";
+GRANT USAGE ON SCHEMA "entity_set" TO "minerva";
+ALTER DEFAULT PRIVILEGES IN SCHEMA "entity_set" GRANT SELECT,INSERT,UPDATE,DELETE ON tables TO "minerva_writer";
+
+ALTER DEFAULT PRIVILEGES IN SCHEMA "entity_set" GRANT SELECT ON tables TO "minerva";
+
+CREATE TABLE "directory"."entity_set"
+(
+  "id" serial NOT NULL,
+  "name" text NOT NULL,
+  "entity_type_id" integer NOT NULL REFERENCES "directory"."entity_type"("id") ON DELETE CASCADE,
+  "entity_id" integer NOT NULL,
+  "owner" text NOT NULL,
+  "group" text,
+  "current_revision_id" bigint,
+  "revision_retention_period" interval NOT NULL DEFAULT '3 mons',
+  PRIMARY KEY (id),
+  UNIQUE (entity_id)
+);
+
+COMMENT ON TABLE "directory"."entity_set" IS 'Entity sets with revision history. The entity_id refers to the entity in entity.entity_set that is also used in attribute.minerva_entity_set.';
+
+GRANT SELECT ON TABLE "directory"."entity_set" TO minerva;
+GRANT INSERT,DELETE,UPDATE ON TABLE "directory"."entity_set" TO minerva_writer;
+GRANT SELECT,UPDATE ON SEQUENCE "directory"."entity_set_id_seq" TO minerva_writer;
+
+
+CREATE FUNCTION "entity_set"."create_entity_set_tables"("entity_type_name" text)
+    RETURNS void
+AS $$
+BEGIN
+  IF to_regclass(format('entity_set.%I', entity_type_name || '_revision')) IS NULL THEN
+    EXECUTE format(
+      'CREATE TABLE entity_set.%I('
+      'id bigserial PRIMARY KEY, '
+      'entity_set_id integer NOT NULL, '
+      'validity_period tstzrange NOT NULL)',
+      entity_type_name || '_revision'
+    );
+    EXECUTE format(
+      'CREATE INDEX ON entity_set.%I USING btree(entity_set_id)',
+      entity_type_name || '_revision'
+    );
+    EXECUTE format(
+      'CREATE TABLE entity_set.%I('
+      'entity_set_id integer NOT NULL, '
+      'revision_id bigint NOT NULL, '
+      'entity_id integer NOT NULL, '
+      'PRIMARY KEY (revision_id, entity_id))',
+      entity_type_name
+    );
+    PERFORM create_reference_table(format('entity_set.%I', entity_type_name || '_revision'));
+    PERFORM create_reference_table(format('entity_set.%I', entity_type_name));
+  END IF;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE FUNCTION "entity_set"."close_revision"("minerva_entity_set_id" integer)
+    RETURNS void
+AS $$
+DECLARE
+  set directory.entity_set;
+  entity_type_name text;
+BEGIN
+  SELECT * FROM directory.entity_set WHERE entity_id = $1 INTO set;
+
+  IF set.current_revision_id IS NULL THEN
+    RETURN;
+  END IF;
+
+  SELECT name FROM directory.entity_type WHERE id = set.entity_type_id INTO entity_type_name;
+
+  EXECUTE format(
+    'UPDATE entity_set.%I SET validity_period = tstzrange(lower(validity_period), greatest(lower(validity_period), now())) WHERE id = $1',
+    entity_type_name || '_revision'
+  ) USING set.current_revision_id;
+
+  UPDATE directory.entity_set SET current_revision_id = NULL WHERE id = set.id;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "entity_set"."close_revision"("minerva_entity_set_id" integer) IS 'End the validity of the current revision of an entity set, e.g. when the entity set is removed';
+
+
+CREATE FUNCTION "entity_set"."record_revision"("minerva_entity_set_id" integer)
+    RETURNS bigint
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+  revision_id bigint;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+
+  INSERT INTO directory.entity_set(name, entity_type_id, entity_id, owner, "group")
+    SELECT set.name, et.id, set.entity_id, set.owner, set."group"
+    FROM directory.entity_type et WHERE et.name = set.source_entity_type
+    ON CONFLICT (entity_id) DO UPDATE SET name = EXCLUDED.name, owner = EXCLUDED.owner, "group" = EXCLUDED."group";
+
+  PERFORM entity_set.create_entity_set_tables(set.source_entity_type);
+  PERFORM entity_set.close_revision($1);
+
+  EXECUTE format(
+    'INSERT INTO entity_set.%I(entity_set_id, validity_period) '
+    'SELECT id, tstzrange(now(), NULL) FROM directory.entity_set WHERE entity_id = $1 '
+    'RETURNING id',
+    set.source_entity_type || '_revision'
+  ) INTO revision_id USING $1;
+
+  EXECUTE format(
+    'INSERT INTO entity_set.%I(entity_set_id, revision_id, entity_id) '
+    'SELECT es.id, $2, r.source_id FROM relation.%I r, directory.entity_set es '
+    'WHERE r.target_id = $1 AND es.entity_id = $1',
+    set.source_entity_type,
+    set.source_entity_type || '->entity_set'
+  ) USING $1, revision_id;
+
+  UPDATE directory.entity_set SET current_revision_id = revision_id WHERE entity_id = $1;
+
+  RETURN revision_id;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "entity_set"."record_revision"("minerva_entity_set_id" integer) IS 'Store the current members of an entity set as a new revision that is valid from now on';
+
+
+CREATE FUNCTION "entity_set"."remove_expired_revisions"()
+    RETURNS bigint
+AS $$
+DECLARE
+  entity_type_name text;
+  removed_count bigint;
+  total_count bigint = 0;
+BEGIN
+  FOR entity_type_name IN
+    SELECT DISTINCT et.name FROM directory.entity_set es JOIN directory.entity_type et ON et.id = es.entity_type_id
+  LOOP
+    EXECUTE format(
+      'WITH expired AS ('
+      'DELETE FROM entity_set.%I r USING directory.entity_set es '
+      'WHERE es.id = r.entity_set_id AND upper(r.validity_period) < now() - es.revision_retention_period '
+      'RETURNING r.id) '
+      'DELETE FROM entity_set.%I m USING expired WHERE m.revision_id = expired.id',
+      entity_type_name || '_revision',
+      entity_type_name
+    );
+    GET DIAGNOSTICS removed_count = ROW_COUNT;
+    total_count = total_count + removed_count;
+  END LOOP;
+
+  RETURN total_count;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "entity_set"."remove_expired_revisions"() IS 'Remove revisions that ended longer ago than the retention period of their entity set';
+
+
+CREATE FUNCTION "entity_set"."get_members_at"("minerva_entity_set_id" integer, "timestamp" timestamptz)
+    RETURNS SETOF integer
+AS $$
+DECLARE
+  entity_type_name text;
+BEGIN
+  SELECT et.name FROM directory.entity_set es
+    JOIN directory.entity_type et ON et.id = es.entity_type_id
+    WHERE es.entity_id = $1 INTO entity_type_name;
+
+  IF entity_type_name IS NULL THEN
+    RETURN;
+  END IF;
+
+  RETURN QUERY EXECUTE format(
+    'SELECT m.entity_id FROM entity_set.%I m '
+    'JOIN entity_set.%I r ON r.id = m.revision_id '
+    'JOIN directory.entity_set es ON es.id = r.entity_set_id '
+    'WHERE es.entity_id = $1 AND r.validity_period @> $2',
+    entity_type_name,
+    entity_type_name || '_revision'
+  ) USING $1, $2;
+END;
+$$ LANGUAGE plpgsql STABLE;
+
+COMMENT ON FUNCTION "entity_set"."get_members_at"("minerva_entity_set_id" integer, "timestamp" timestamptz) IS 'Return the Ids of the members of an entity set as they were at the specified timestamp, e.g. for reprocessing historical data';
+
+
+CREATE OR REPLACE FUNCTION "relation_directory"."change_set_entities"("minerva_entity_set_id" integer, "entities" text[])
+    RETURNS void
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+  PERFORM action(FORMAT(
+    'DELETE FROM relation."%s->entity_set" '
+    'WHERE target_id = %s;',
+    set.source_entity_type,
+    set.entity_id
+  ));
+  PERFORM relation_directory.add_entities_to_set($1, $2);
+  PERFORM entity_set.record_revision($1);
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+-- Record the current state of the existing entity sets as their first revision
+DO $$
+BEGIN
+  IF to_regclass('attribute.minerva_entity_set') IS NOT NULL THEN
+    PERFORM entity_set.record_revision(es.entity_id) FROM attribute.minerva_entity_set es;
+  END IF;
+END;
+$$;
+
+
+CREATE OR REPLACE FUNCTION directory.change_ownership_for_all_schemas(new_owner text)
+  RETURNS void AS $$
+    SELECT directory.change_ownership_for_schema('alias', new_owner);
+    SELECT directory.change_ownership_for_schema('alias_def', new_owner);
+    SELECT directory.change_ownership_for_schema('alias_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('attribute', new_owner);
+    SELECT directory.change_ownership_for_schema('attribute_base', new_owner);
+    SELECT directory.change_ownership_for_schema('attribute_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('attribute_history', new_owner);
+    SELECT directory.change_ownership_for_schema('attribute_staging', new_owner);
+    SELECT directory.change_ownership_for_schema('cached', new_owner);
+    SELECT directory.change_ownership_for_schema('cached_def', new_owner);
+    SELECT directory.change_ownership_for_schema('directory', new_owner);
+    SELECT directory.change_ownership_for_schema('entity', new_owner);
+    SELECT directory.change_ownership_for_schema('entity_set', new_owner);
+    SELECT directory.change_ownership_for_schema('handover', new_owner);
+    SELECT directory.change_ownership_for_schema('handover_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('logging', new_owner);
+    SELECT directory.change_ownership_for_schema('notification', new_owner);
+    SELECT directory.change_ownership_for_schema('notification_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('relation', new_owner);
+    SELECT directory.change_ownership_for_schema('relation_def', new_owner);
+    SELECT directory.change_ownership_for_schema('relation_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('relation_history', new_owner);
+    SELECT directory.change_ownership_for_schema('staging', new_owner);
+    SELECT directory.change_ownership_for_schema('trend', new_owner);
+    SELECT directory.change_ownership_for_schema('trend_directory', new_owner);
+    SELECT directory.change_ownership_for_schema('trend_partition', new_owner);
+    SELECT directory.change_ownership_for_schema('trigger', new_owner);
+    SELECT directory.change_ownership_for_schema('trigger_rule', new_owner);
+    SELECT directory.change_ownership_for_schema('virtual_entity', new_owner);
+  $$ LANGUAGE sql VOLATILE;
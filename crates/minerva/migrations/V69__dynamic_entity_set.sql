@@ -0,0 +1,5 @@
+ALTER TABLE "directory"."entity_set" ADD COLUMN "rules" jsonb;
+ALTER TABLE "directory"."entity_set" ADD COLUMN "evaluated" timestamp with time zone;
+
+COMMENT ON COLUMN "directory"."entity_set"."rules" IS 'Rules that define the members of a dynamic entity set, NULL for static entity sets';
+COMMENT ON COLUMN "directory"."entity_set"."evaluated" IS 'Last time the rules of a dynamic entity set were evaluated';
@@ -0,0 +1,133 @@
+ALTER TABLE "alias_directory"."alias_type" ADD COLUMN "entity_type_id" integer REFERENCES "directory"."entity_type"("id") ON DELETE CASCADE;
+ALTER TABLE "alias_directory"."alias_type" ADD COLUMN "source" text;
+
+COMMENT ON COLUMN "alias_directory"."alias_type"."entity_type_id" IS 'Entity type that the alias type belongs to, NULL for alias types that are shared by all entity types';
+COMMENT ON COLUMN "alias_directory"."alias_type"."source" IS 'Query returning the columns entity_id and alias from which the aliases are materialized';
+
+DROP INDEX "alias_directory"."alias_type_name_lower_idx";
+
+CREATE UNIQUE INDEX "alias_type_entity_type_name_idx" ON "alias_directory"."alias_type" USING btree (COALESCE(entity_type_id, 0), name);
+
+
+CREATE FUNCTION "alias_directory"."alias_table_name"(alias_directory.alias_type)
+    RETURNS name
+AS $$
+SELECT CASE
+  WHEN $1.entity_type_id IS NULL THEN $1.name::name
+  ELSE (SELECT format('%s_%s', et.name, $1.name)::name FROM directory.entity_type et WHERE et.id = $1.entity_type_id)
+END;
+$$ LANGUAGE sql STABLE;
+
+COMMENT ON FUNCTION "alias_directory"."alias_table_name"(alias_directory.alias_type) IS 'Name of the table in the alias schema with the aliases of the alias type, prefixed with the entity type name for alias types of an entity type';
+
+
+CREATE FUNCTION "alias_directory"."get_entity_type_alias"("entity_type_name" text, "name" text)
+    RETURNS alias_directory.alias_type
+AS $$
+SELECT at.* FROM alias_directory.alias_type at
+JOIN directory.entity_type et ON et.id = at.entity_type_id
+WHERE et.name = $1 AND at.name = $2;
+$$ LANGUAGE sql STABLE;
+
+
+CREATE FUNCTION "alias_directory"."create_entity_type_alias"("entity_type_name" text, "name" text, "source" text)
+    RETURNS alias_directory.alias_type
+AS $$
+DECLARE
+  result alias_directory.alias_type;
+  table_name name;
+BEGIN
+  INSERT INTO alias_directory.alias_type(name, entity_type_id, source)
+    SELECT $2, et.id, $3 FROM directory.entity_type et WHERE et.name = $1
+    RETURNING * INTO result;
+
+  IF NOT FOUND THEN
+    RAISE EXCEPTION 'No entity type with name ''%''', $1;
+  END IF;
+
+  table_name = alias_directory.alias_table_name(result);
+
+  EXECUTE format('CREATE TABLE alias.%I(entity_id integer PRIMARY KEY, alias text NOT NULL)', table_name);
+  EXECUTE format('CREATE INDEX ON alias.%I USING btree(alias)', table_name);
+  PERFORM create_reference_table(format('alias.%I', table_name));
+  EXECUTE format('CREATE VIEW alias_def.%I AS %s', table_name, $3);
+
+  RETURN result;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "alias_directory"."create_entity_type_alias"("entity_type_name" text, "name" text, "source" text) IS 'Define an alias type of an entity type with the table for the aliases and the view with the source query';
+
+
+CREATE FUNCTION "alias_directory"."change_entity_type_alias_source"("entity_type_name" text, "name" text, "source" text)
+    RETURNS alias_directory.alias_type
+AS $$
+DECLARE
+  result alias_directory.alias_type;
+BEGIN
+  UPDATE alias_directory.alias_type SET source = $3
+    WHERE id = (alias_directory.get_entity_type_alias($1, $2)).id
+    RETURNING * INTO result;
+
+  IF NOT FOUND THEN
+    RAISE EXCEPTION 'No alias type ''%'' for entity type ''%''', $2, $1;
+  END IF;
+
+  EXECUTE format('DROP VIEW alias_def.%I', alias_directory.alias_table_name(result));
+  EXECUTE format('CREATE VIEW alias_def.%I AS %s', alias_directory.alias_table_name(result), $3);
+
+  RETURN result;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE FUNCTION "alias_directory"."delete_entity_type_alias"("entity_type_name" text, "name" text)
+    RETURNS void
+AS $$
+DECLARE
+  alias_type alias_directory.alias_type;
+BEGIN
+  alias_type = alias_directory.get_entity_type_alias($1, $2);
+
+  IF alias_type.id IS NULL THEN
+    RAISE EXCEPTION 'No alias type ''%'' for entity type ''%''', $2, $1;
+  END IF;
+
+  EXECUTE format('DROP VIEW IF EXISTS alias_def.%I', alias_directory.alias_table_name(alias_type));
+  EXECUTE format('DROP TABLE IF EXISTS alias.%I', alias_directory.alias_table_name(alias_type));
+
+  DELETE FROM alias_directory.alias_type WHERE id = alias_type.id;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE FUNCTION "alias_directory"."materialize_entity_type_alias"("entity_type_name" text, "name" text)
+    RETURNS bigint
+AS $$
+DECLARE
+  alias_type alias_directory.alias_type;
+  table_name name;
+  alias_count bigint;
+BEGIN
+  alias_type = alias_directory.get_entity_type_alias($1, $2);
+
+  IF alias_type.id IS NULL THEN
+    RAISE EXCEPTION 'No alias type ''%'' for entity type ''%''', $2, $1;
+  END IF;
+
+  table_name = alias_directory.alias_table_name(alias_type);
+
+  EXECUTE format('DELETE FROM alias.%I', table_name);
+  EXECUTE format(
+    'INSERT INTO alias.%I(entity_id, alias) '
+    'SELECT DISTINCT ON (entity_id) entity_id, alias FROM alias_def.%I WHERE alias IS NOT NULL',
+    table_name, table_name
+  );
+
+  GET DIAGNOSTICS alias_count = ROW_COUNT;
+
+  RETURN alias_count;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "alias_directory"."materialize_entity_type_alias"("entity_type_name" text, "name" text) IS 'Replace the aliases of an alias type of an entity type with the result of its source query and return the number of aliases';
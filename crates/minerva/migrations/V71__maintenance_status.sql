@@ -0,0 +1,15 @@
+CREATE TABLE "system"."maintenance_status"
+(
+  "task" text NOT NULL,
+  "instance" text NOT NULL,
+  "started" timestamp with time zone NOT NULL,
+  "finished" timestamp with time zone NOT NULL,
+  "success" boolean NOT NULL,
+  "message" text,
+  PRIMARY KEY (task)
+);
+
+COMMENT ON TABLE "system"."maintenance_status" IS 'Result of the last run of each task of the maintenance service, with the service instance that ran it.';
+
+GRANT SELECT ON TABLE "system"."maintenance_status" TO minerva;
+GRANT INSERT,DELETE,UPDATE ON TABLE "system"."maintenance_status" TO minerva_writer;
@@ -0,0 +1,3 @@
+CREATE INDEX "change_event_timestamp_idx" ON "directory"."change_event" USING btree ("timestamp");
+
+COMMENT ON TABLE "directory"."change_event" IS 'Log of changes to the data in the database, so that dependent data can be updated for only what changed. Events are removed by the maintenance service after the change event retention period.';
@@ -0,0 +1,18 @@
+CREATE OR REPLACE FUNCTION "alias_directory"."delete_entity_type_alias"("entity_type_name" text, "name" text)
+    RETURNS void
+AS $$
+DECLARE
+  alias_type alias_directory.alias_type;
+BEGIN
+  alias_type = alias_directory.get_entity_type_alias($1, $2);
+
+  IF alias_type.id IS NULL THEN
+    RAISE EXCEPTION 'No alias type ''%'' for entity type ''%''', $2, $1;
+  END IF;
+
+  EXECUTE format('DROP VIEW IF EXISTS alias_def.%I', alias_directory.alias_table_name(alias_type));
+  EXECUTE format('DROP TABLE IF EXISTS alias.%I', alias_directory.alias_table_name(alias_type));
+
+  DELETE FROM alias_directory.alias_type at WHERE at.id = alias_type.id;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
@@ -0,0 +1,97 @@
+CREATE FUNCTION "relation_directory"."insert_set_entities"("minerva_entity_set_id" integer, "entities" text[])
+    RETURNS bigint
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+  inserted_count bigint;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+  EXECUTE FORMAT(
+    'INSERT INTO relation.%I (source_id, target_id) '
+    'SELECT source.id AS source_id, $1 AS target '
+    'FROM entity.%I source '
+    'WHERE source.name = ANY($2) '
+    'ON CONFLICT DO NOTHING;',
+    set.source_entity_type || '->entity_set',
+    set.source_entity_type
+  ) USING set.entity_id, $2;
+  GET DIAGNOSTICS inserted_count = ROW_COUNT;
+
+  RETURN inserted_count;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+COMMENT ON FUNCTION "relation_directory"."insert_set_entities"("minerva_entity_set_id" integer, "entities" text[]) IS 'Add entities to a set without recording a revision and return the number of new members';
+
+
+CREATE OR REPLACE FUNCTION "relation_directory"."add_entity_to_set"("minerva_entity_set_id" integer, "entity" text)
+    RETURNS attribute.minerva_entity_set
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+  PERFORM relation_directory.update_entity_set_attributes($1);
+  IF relation_directory.insert_set_entities($1, ARRAY[$2]) > 0 THEN
+    PERFORM entity_set.record_revision($1);
+  END IF;
+  RETURN set;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE OR REPLACE FUNCTION "relation_directory"."remove_entity_from_set"("minerva_entity_set_id" integer, "entity" text)
+    RETURNS void
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+  removed_count bigint;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+  PERFORM relation_directory.update_entity_set_attributes($1);
+  EXECUTE FORMAT(
+    'DELETE FROM relation.%I es '
+    'USING entity.%I source '
+    'WHERE es.source_id = source.id AND source.name = $1 AND es.target_id = $2',
+    set.source_entity_type || '->entity_set',
+    set.source_entity_type
+  ) USING $2, set.entity_id;
+  GET DIAGNOSTICS removed_count = ROW_COUNT;
+
+  IF removed_count > 0 THEN
+    PERFORM entity_set.record_revision($1);
+  END IF;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE OR REPLACE FUNCTION "relation_directory"."add_entities_to_set"("minerva_entity_set_id" integer, "entities" text[])
+    RETURNS void
+AS $$
+BEGIN
+  PERFORM relation_directory.update_entity_set_attributes($1);
+  IF relation_directory.insert_set_entities($1, $2) > 0 THEN
+    PERFORM entity_set.record_revision($1);
+  END IF;
+END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+
+CREATE OR REPLACE FUNCTION "relation_directory"."change_set_entities"("minerva_entity_set_id" integer, "entities" text[])
+    RETURNS void
+AS $$
+DECLARE
+  set attribute.minerva_entity_set;
+BEGIN
+  SELECT * FROM attribute.minerva_entity_set WHERE entity_id = $1 INTO set;
+  PERFORM action(FORMAT(
+    'DELETE FROM relation."%s->entity_set" '
+    'WHERE target_id = %s;',
+    set.source_entity_type,
+    set.entity_id
+  ));
+  PERFORM relation_directory.update_entity_set_attributes($1);
+  PERFORM relation_directory.insert_set_entities($1, $2);
+  PERFORM entity_set.record_revision($1);
+END;
+$$ LANGUAGE plpgsql VOLATILE;
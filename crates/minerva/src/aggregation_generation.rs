@@ -73,7 +73,9 @@ fn generate_standard_aggregations(
         .iter()
         .filter_map(|r| {
             // Currently only by convention, the relation name describes source and target entity
-            // types '<SOURCE_TYPE>-><TARGET_TYPE>' and we try to extract these here.
+            // types '<SOURCE_TYPE>-><TARGET_TYPE>' and we try to extract these here. This
+            // includes the relations generated from the entity type hierarchy, so there is an
+            // aggregation to every ancestor entity type.
             let mut split = r.name.split("->");
 
             let source_type = split.next().unwrap();
@@ -14,11 +14,12 @@ use crate::attribute_store::load_attribute_names;
 use crate::attribute_store::materialize_curr_ptr::{
     MaterializeCurrPtrError, MaterializeCurrPtrResult, materialize_curr_ptr_by_name,
 };
-use crate::change::Changed;
+use crate::change::{Changed, MinervaObjectRef};
+use crate::relation::sql_equals;
 
 pub const MATERIALIZATION_VIEW_SCHEMA: &str = "attribute";
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct AttributeMaterializationTarget {
     pub data_source: String,
     pub entity_type: String,
@@ -78,6 +79,63 @@ impl AttributeMaterialization {
         attribute_materialization_view_name(&self.attribute_store)
     }
 
+    #[must_use]
+    pub fn diff(&self, other: &AttributeMaterialization) -> Vec<Box<dyn Change + Send>> {
+        let mut changes: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        if !sql_equals(&self.query, &other.query) {
+            changes.push(Box::new(UpdateAttributeMaterialization {
+                attribute_materialization: other.clone(),
+            }));
+        }
+
+        changes
+    }
+
+    pub async fn drop_view<T: GenericClient + Send + Sync>(
+        &self,
+        client: &mut T,
+    ) -> Result<(), String> {
+        let query = format!(
+            "DROP VIEW IF EXISTS {}.{}",
+            MATERIALIZATION_VIEW_SCHEMA,
+            escape_identifier(&attribute_materialization_view_name(&self.attribute_store)),
+        );
+
+        match client.execute(query.as_str(), &[]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Error dropping view: {e}")),
+        }
+    }
+
+    pub async fn update<T: GenericClient + Send + Sync>(
+        &self,
+        client: &mut T,
+    ) -> Result<(), String> {
+        // The view is recreated instead of replaced, because a replacement can not remove or
+        // rename columns.
+        self.drop_view(client).await?;
+        self.create_view(client).await
+    }
+
+    pub async fn delete<T: GenericClient + Send + Sync>(
+        &self,
+        client: &mut T,
+    ) -> Result<(), String> {
+        let query = concat!(
+            "DELETE FROM attribute_directory.sampled_view_materialization svm ",
+            "USING attribute_directory.attribute_store ast ",
+            "WHERE ast.id = svm.attribute_store_id AND ast::text = $1"
+        );
+
+        client
+            .execute(query, &[&self.attribute_store.to_string()])
+            .await
+            .map_err(|e| format!("Could not delete record for attribute materialization: {e}"))?;
+
+        self.drop_view(client).await
+    }
+
     pub async fn create_view<T: GenericClient + Send + Sync>(
         &self,
         client: &mut T,
@@ -317,6 +375,41 @@ pub async fn load_attribute_materialization_by_name<T: GenericClient + Send + Sy
     })
 }
 
+pub async fn load_attribute_materialization_by_attribute_store<T: GenericClient + Send + Sync>(
+    conn: &T,
+    attribute_store: &AttributeMaterializationRef,
+) -> Result<AttributeMaterialization, String> {
+    let query = concat!(
+        "SELECT ds.name, et.name, pg_get_viewdef(src_view) ",
+        "FROM attribute_directory.sampled_view_materialization svm ",
+        "JOIN attribute_directory.attribute_store ast ON ast.id = svm.attribute_store_id ",
+        "JOIN directory.data_source AS ds ON ds.id = ast.data_source_id ",
+        "JOIN directory.entity_type AS et ON et.id = ast.entity_type_id ",
+        "WHERE ast::text = $1"
+    );
+
+    let rows = conn
+        .query(query, &[&attribute_store.to_string()])
+        .await
+        .map_err(|e| format!("Could not load attribute materialization: {e}"))?;
+
+    if rows.is_empty() {
+        return Err(format!(
+            "No materialization found for attribute store '{attribute_store}'"
+        ));
+    }
+
+    let row = rows.first().unwrap();
+
+    Ok(AttributeMaterialization {
+        attribute_store: AttributeMaterializationTarget {
+            data_source: row.get(0),
+            entity_type: row.get(1),
+        },
+        query: row.get(2),
+    })
+}
+
 pub async fn load_attribute_materializations<T: GenericClient + Send + Sync>(
     conn: &T,
 ) -> Result<Vec<AttributeMaterialization>, String> {
@@ -388,6 +481,12 @@ impl Change for AddAttributeMaterialization {
             ),
         }))
     }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::AttributeMaterialization(
+            self.attribute_materialization.attribute_store.to_string(),
+        ))
+    }
 }
 
 impl From<AttributeMaterialization> for AddAttributeMaterialization {
@@ -424,15 +523,107 @@ impl Display for AddedAttributeMaterialization {
 }
 
 #[derive(Serialize, Deserialize)]
-struct RemoveAttributeMaterialization {
-    attribute_materialization: AttributeMaterializationRef,
+#[serde(tag = "type")]
+pub struct UpdateAttributeMaterialization {
+    pub attribute_materialization: AttributeMaterialization,
+}
+
+impl fmt::Display for UpdateAttributeMaterialization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UpdateAttributeMaterialization({})",
+            self.attribute_materialization
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for UpdateAttributeMaterialization {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let original = load_attribute_materialization_by_attribute_store(
+            &tx,
+            &AttributeMaterializationRef::from(&self.attribute_materialization),
+        )
+        .await
+        .map_err(|e| {
+            Error::Runtime(RuntimeError {
+                msg: format!(
+                    "Error updating attribute materialization '{}': {}",
+                    self.attribute_materialization, e
+                ),
+            })
+        })?;
+
+        self.attribute_materialization
+            .update(&mut tx)
+            .await
+            .map_err(|e| {
+                Error::Runtime(RuntimeError {
+                    msg: format!(
+                        "Error updating attribute materialization '{}': {}",
+                        self.attribute_materialization, e
+                    ),
+                })
+            })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(UpdatedAttributeMaterialization {
+            attribute_materialization: AttributeMaterializationRef::from(
+                &self.attribute_materialization,
+            ),
+            original,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::AttributeMaterialization(
+            self.attribute_materialization.attribute_store.to_string(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdatedAttributeMaterialization {
+    pub attribute_materialization: AttributeMaterializationRef,
+    pub original: AttributeMaterialization,
+}
+
+impl Display for UpdatedAttributeMaterialization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Updated attribute materialization '{}'",
+            self.attribute_materialization
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for UpdatedAttributeMaterialization {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(UpdateAttributeMaterialization {
+            attribute_materialization: self.original.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemoveAttributeMaterialization {
+    pub attribute_materialization: AttributeMaterializationRef,
 }
 
 impl Display for RemoveAttributeMaterialization {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Removed attribute materialization '{}'",
+            "RemoveAttributeMaterialization({})",
             self.attribute_materialization
         )
     }
@@ -441,9 +632,68 @@ impl Display for RemoveAttributeMaterialization {
 #[async_trait]
 #[typetag::serde]
 impl Change for RemoveAttributeMaterialization {
-    async fn apply(&self, _client: &mut Client) -> ChangeResult {
-        Err(Error::Runtime(RuntimeError {
-            msg: "Not implemented".to_string(),
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let attribute_materialization =
+            load_attribute_materialization_by_attribute_store(&tx, &self.attribute_materialization)
+                .await
+                .map_err(|e| {
+                    Error::Runtime(RuntimeError {
+                        msg: format!(
+                            "Error removing attribute materialization '{}': {}",
+                            self.attribute_materialization, e
+                        ),
+                    })
+                })?;
+
+        attribute_materialization
+            .delete(&mut tx)
+            .await
+            .map_err(|e| {
+                Error::Runtime(RuntimeError {
+                    msg: format!(
+                        "Error removing attribute materialization '{}': {}",
+                        self.attribute_materialization, e
+                    ),
+                })
+            })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RemovedAttributeMaterialization {
+            attribute_materialization,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::AttributeMaterialization(
+            self.attribute_materialization.to_string(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemovedAttributeMaterialization {
+    pub attribute_materialization: AttributeMaterialization,
+}
+
+impl Display for RemovedAttributeMaterialization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Removed attribute materialization '{}'",
+            self.attribute_materialization.attribute_store
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RemovedAttributeMaterialization {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(AddAttributeMaterialization {
+            attribute_materialization: self.attribute_materialization.clone(),
         }))
     }
 }
@@ -540,3 +790,41 @@ pub struct MaterializeAttributeResult {
     pub materialized_record_count: u64,
     pub materialized_curr_ptr: MaterializeCurrPtrResult,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute_materialization(query: &str) -> AttributeMaterialization {
+        AttributeMaterialization {
+            attribute_store: AttributeMaterializationTarget {
+                data_source: "hub".to_string(),
+                entity_type: "node".to_string(),
+            },
+            query: query.to_string(),
+        }
+    }
+
+    #[test]
+    fn attribute_materialization_diff_ignores_formatting() {
+        let my_materialization = attribute_materialization("SELECT id FROM entity.node");
+        let other_materialization = attribute_materialization("SELECT\n  id\nFROM entity.node");
+
+        assert!(my_materialization.diff(&other_materialization).is_empty());
+    }
+
+    #[test]
+    fn attribute_materialization_diff_changed_query() {
+        let my_materialization = attribute_materialization("SELECT id FROM entity.node");
+        let other_materialization = attribute_materialization("SELECT id, name FROM entity.node");
+
+        let changes = my_materialization.diff(&other_materialization);
+
+        assert_eq!(changes.len(), 1);
+        assert!(
+            changes[0]
+                .to_string()
+                .starts_with("UpdateAttributeMaterialization(")
+        );
+    }
+}
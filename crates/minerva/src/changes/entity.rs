@@ -0,0 +1,1288 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use postgres_protocol::escape::escape_identifier;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use tokio_postgres::types::{FromSql, ToSql};
+use tokio_postgres::{Client, GenericClient};
+
+use crate::change::{Change, ChangeResult, Changed};
+use crate::error::{DatabaseError, Error, RuntimeError};
+use crate::relation::{load_relation_names, relation_entity_types, relation_has_history};
+
+/// Rows of one table that were moved from one entity to another, identified by the values of the
+/// key column that, together with the entity column, identifies the rows.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind")]
+pub enum MovedEntityRows {
+    Trend {
+        trend_store_part: String,
+        timestamps: Vec<DateTime<Utc>>,
+    },
+    AttributeHistory {
+        attribute_store: String,
+        timestamps: Vec<DateTime<Utc>>,
+    },
+    RelationSource {
+        relation: String,
+        target_ids: Vec<i32>,
+    },
+    RelationTarget {
+        relation: String,
+        source_ids: Vec<i32>,
+    },
+    RelationHistorySource {
+        relation: String,
+        target_ids: Vec<i32>,
+    },
+    RelationHistoryTarget {
+        relation: String,
+        source_ids: Vec<i32>,
+    },
+    EntitySetRevision {
+        entity_type: String,
+        revision_ids: Vec<i64>,
+    },
+    EntitySet {
+        entity_set_ids: Vec<i32>,
+    },
+    Alias {
+        alias_table: String,
+        aliases: Vec<String>,
+    },
+}
+
+impl MovedEntityRows {
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Trend { timestamps, .. } | Self::AttributeHistory { timestamps, .. } => {
+                timestamps.len()
+            }
+            Self::RelationSource { target_ids, .. }
+            | Self::RelationHistorySource { target_ids, .. } => target_ids.len(),
+            Self::RelationTarget { source_ids, .. }
+            | Self::RelationHistoryTarget { source_ids, .. } => source_ids.len(),
+            Self::EntitySetRevision { revision_ids, .. } => revision_ids.len(),
+            Self::EntitySet { entity_set_ids } => entity_set_ids.len(),
+            Self::Alias { aliases, .. } => aliases.len(),
+        }
+    }
+
+    fn table(&self) -> EntityTable<'_> {
+        match self {
+            Self::Trend {
+                trend_store_part, ..
+            } => EntityTable::new("trend", trend_store_part, "entity_id", "timestamp"),
+            Self::AttributeHistory {
+                attribute_store, ..
+            } => EntityTable::new(
+                "attribute_history",
+                attribute_store,
+                "entity_id",
+                "timestamp",
+            ),
+            Self::RelationSource { relation, .. } => {
+                EntityTable::new("relation", relation, "source_id", "target_id")
+            }
+            Self::RelationTarget { relation, .. } => {
+                EntityTable::new("relation", relation, "target_id", "source_id")
+            }
+            Self::RelationHistorySource { relation, .. } => {
+                EntityTable::new("relation_history", relation, "source_id", "target_id")
+            }
+            Self::RelationHistoryTarget { relation, .. } => {
+                EntityTable::new("relation_history", relation, "target_id", "source_id")
+            }
+            Self::EntitySetRevision { entity_type, .. } => {
+                EntityTable::new("entity_set", entity_type, "entity_id", "revision_id")
+            }
+            Self::EntitySet { .. } => {
+                EntityTable::new("directory", "entity_set", "entity_id", "id").unique_entity()
+            }
+            Self::Alias { alias_table, .. } => {
+                EntityTable::new("alias", alias_table, "entity_id", "alias").unique_entity()
+            }
+        }
+    }
+}
+
+impl fmt::Display for MovedEntityRows {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.table(), self.len())
+    }
+}
+
+/// Rows that were left on the entity they were to be moved from, because the other entity
+/// already has rows with the same key. The rows of the other entity are kept as they are.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityRowConflicts {
+    pub table: String,
+    pub count: i64,
+}
+
+impl fmt::Display for EntityRowConflicts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.table, self.count)
+    }
+}
+
+/// Table with rows that refer to an entity
+struct EntityTable<'a> {
+    schema: &'a str,
+    table: &'a str,
+    entity_column: &'a str,
+    /// Column that, together with the entity column, identifies the rows
+    key_column: &'a str,
+    /// The table has at most one row per entity, so rows are only moved to an entity without rows
+    unique_entity: bool,
+}
+
+impl<'a> EntityTable<'a> {
+    fn new(
+        schema: &'a str,
+        table: &'a str,
+        entity_column: &'a str,
+        key_column: &'a str,
+    ) -> EntityTable<'a> {
+        EntityTable {
+            schema,
+            table,
+            entity_column,
+            key_column,
+            unique_entity: false,
+        }
+    }
+
+    fn unique_entity(self) -> EntityTable<'a> {
+        EntityTable {
+            unique_entity: true,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for EntityTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.\"{}\"", self.schema, self.table)
+    }
+}
+
+pub async fn get_entity_id<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    name: &str,
+) -> Result<i64, Error> {
+    let query = format!(
+        "SELECT id::bigint FROM entity.{} WHERE name = $1",
+        escape_identifier(entity_type)
+    );
+
+    let row = client
+        .query_opt(&query, &[&name])
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Could not look up entity '{name}' of type '{entity_type}': {e}"
+            ))
+        })?
+        .ok_or_else(|| {
+            RuntimeError::from_msg(format!("No entity '{name}' of type '{entity_type}'"))
+        })?;
+
+    Ok(row.get(0))
+}
+
+async fn set_retired<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    entity_id: i64,
+    retired: bool,
+) -> Result<(), Error> {
+    let query = if retired {
+        concat!(
+            "INSERT INTO directory.retired_entity(entity_type_id, entity_id) ",
+            "SELECT id, $2 FROM directory.entity_type WHERE name = $1 ",
+            "ON CONFLICT DO NOTHING"
+        )
+    } else {
+        concat!(
+            "DELETE FROM directory.retired_entity re ",
+            "USING directory.entity_type et ",
+            "WHERE et.id = re.entity_type_id AND et.name = $1 AND re.entity_id = $2"
+        )
+    };
+
+    client
+        .execute(query, &[&entity_type, &entity_id])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not update entity retirement: {e}")))?;
+
+    Ok(())
+}
+
+/// Columns of a table that can be inserted, so excluding generated columns
+async fn insertable_columns<T: GenericClient + Sync>(
+    client: &T,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<String>, Error> {
+    let query = concat!(
+        "SELECT column_name::text FROM information_schema.columns ",
+        "WHERE table_schema = $1 AND table_name = $2 AND is_generated = 'NEVER' ",
+        "ORDER BY ordinal_position"
+    );
+
+    let rows = client.query(query, &[&schema, &table]).await.map_err(|e| {
+        DatabaseError::from_msg(format!(
+            "Could not load columns of table '{schema}.{table}': {e}"
+        ))
+    })?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+fn move_entity_rows_query(table: &EntityTable, columns: &[String], with_keys: bool) -> String {
+    let column_list = columns
+        .iter()
+        .map(|column| escape_identifier(column))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let select_list = columns
+        .iter()
+        .map(|column| {
+            if column == table.entity_column {
+                "$2::bigint".to_string()
+            } else {
+                escape_identifier(column)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let key_condition = if with_keys {
+        format!(" AND t.{} = ANY($3)", escape_identifier(table.key_column))
+    } else {
+        String::new()
+    };
+
+    let conflict_condition = if table.unique_entity {
+        String::new()
+    } else {
+        format!(
+            " AND o.{key_column} = t.{key_column}",
+            key_column = escape_identifier(table.key_column)
+        )
+    };
+
+    format!(
+        concat!(
+            "WITH moved AS (",
+            "DELETE FROM {schema}.{table} t ",
+            "WHERE t.{entity_column} = $1::bigint{key_condition} AND NOT EXISTS (",
+            "SELECT 1 FROM {schema}.{table} o ",
+            "WHERE o.{entity_column} = $2::bigint{conflict_condition}",
+            ") ",
+            "RETURNING {column_list}",
+            ") ",
+            "INSERT INTO {schema}.{table}({column_list}) ",
+            "SELECT {select_list} FROM moved ",
+            "RETURNING {key_column}"
+        ),
+        schema = escape_identifier(table.schema),
+        table = escape_identifier(table.table),
+        entity_column = escape_identifier(table.entity_column),
+        key_column = escape_identifier(table.key_column),
+        key_condition = key_condition,
+        conflict_condition = conflict_condition,
+        column_list = column_list,
+        select_list = select_list,
+    )
+}
+
+/// Query that counts the rows of an entity in a table, optionally only those with the keys in $2
+fn entity_rows_count_query(table: &EntityTable, with_keys: bool) -> String {
+    let key_condition = if with_keys {
+        format!(" AND {} = ANY($2)", escape_identifier(table.key_column))
+    } else {
+        String::new()
+    };
+
+    format!(
+        "SELECT count(*) FROM {}.{} WHERE {} = $1::bigint{key_condition}",
+        escape_identifier(table.schema),
+        escape_identifier(table.table),
+        escape_identifier(table.entity_column),
+    )
+}
+
+/// Move the rows of one entity to another entity in a table. Rows for which the other entity
+/// already has a row with the same key, or any row for tables with one row per entity, are left in
+/// place and counted as conflicts. When keys are specified, only the rows with those keys are
+/// moved. The rows are deleted and re-inserted, because the entity column can be the distribution
+/// column of the table, which can not be updated. The entity Ids are cast to bigint in the query,
+/// so that they can be bound as `i64` for both integer and bigint entity columns.
+async fn move_entity_rows<T, K>(
+    client: &T,
+    table: &EntityTable<'_>,
+    from_id: i64,
+    to_id: i64,
+    keys: Option<&[K]>,
+    conflicts: &mut Vec<EntityRowConflicts>,
+) -> Result<Vec<K>, Error>
+where
+    T: GenericClient + Sync,
+    K: ToSql + Sync + Ord + for<'a> FromSql<'a>,
+{
+    let columns = insertable_columns(client, table.schema, table.table).await?;
+
+    let query = move_entity_rows_query(table, &columns, keys.is_some());
+
+    let rows = match keys {
+        Some(keys) => client.query(&query, &[&from_id, &to_id, &keys]).await,
+        None => client.query(&query, &[&from_id, &to_id]).await,
+    }
+    .map_err(|e| DatabaseError::from_msg(format!("Could not move rows of table '{table}': {e}")))?;
+
+    let count_query = entity_rows_count_query(table, keys.is_some());
+
+    let count: i64 = match keys {
+        Some(keys) => client.query_one(&count_query, &[&from_id, &keys]).await,
+        None => client.query_one(&count_query, &[&from_id]).await,
+    }
+    .map_err(|e| DatabaseError::from_msg(format!("Could not count rows of table '{table}': {e}")))?
+    .get(0);
+
+    if count > 0 {
+        conflicts.push(EntityRowConflicts {
+            table: table.to_string(),
+            count,
+        });
+    }
+
+    // Tables like the relation history can have multiple rows with the same key
+    let mut moved_keys: Vec<K> = rows.iter().map(|row| row.get(0)).collect();
+
+    moved_keys.sort();
+    moved_keys.dedup();
+
+    Ok(moved_keys)
+}
+
+async fn mark_trend_store_part_modified<T: GenericClient + Sync>(
+    client: &T,
+    trend_store_part: &str,
+    timestamps: &[DateTime<Utc>],
+) -> Result<(), Error> {
+    let query = concat!(
+        "SELECT trend_directory.mark_modified(tsp.id, t.timestamp) ",
+        "FROM trend_directory.trend_store_part tsp, ",
+        "(SELECT DISTINCT unnest($2::timestamptz[]) AS timestamp) t ",
+        "WHERE tsp.name = $1"
+    );
+
+    client
+        .execute(query, &[&trend_store_part, &timestamps])
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!("Error marking timestamps as modified: {e}"))
+        })?;
+
+    Ok(())
+}
+
+async fn materialize_attribute_store_curr_ptr<T: GenericClient + Sync>(
+    client: &T,
+    attribute_store: &str,
+) -> Result<(), Error> {
+    let query = concat!(
+        "SELECT attribute_directory.materialize_curr_ptr(ast) ",
+        "FROM attribute_directory.attribute_store ast ",
+        "WHERE ast::text = $1"
+    );
+
+    client
+        .execute(query, &[&attribute_store])
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error materializing current attributes of '{attribute_store}': {e}"
+            ))
+        })?;
+
+    Ok(())
+}
+
+/// Move the rows of the specified tables back and forth between two entities
+async fn move_recorded_rows<T: GenericClient + Sync>(
+    client: &T,
+    moved_rows: &[MovedEntityRows],
+    from_id: i64,
+    to_id: i64,
+    conflicts: &mut Vec<EntityRowConflicts>,
+) -> Result<Vec<MovedEntityRows>, Error> {
+    let mut result = Vec::new();
+
+    for moved in moved_rows {
+        let table = moved.table();
+
+        let moved_back = match moved {
+            MovedEntityRows::Trend {
+                trend_store_part,
+                timestamps,
+            } => {
+                let timestamps =
+                    move_entity_rows(client, &table, from_id, to_id, Some(timestamps), conflicts)
+                        .await?;
+
+                mark_trend_store_part_modified(client, trend_store_part, &timestamps).await?;
+
+                MovedEntityRows::Trend {
+                    trend_store_part: trend_store_part.clone(),
+                    timestamps,
+                }
+            }
+            MovedEntityRows::AttributeHistory {
+                attribute_store,
+                timestamps,
+            } => {
+                let timestamps =
+                    move_entity_rows(client, &table, from_id, to_id, Some(timestamps), conflicts)
+                        .await?;
+
+                materialize_attribute_store_curr_ptr(client, attribute_store).await?;
+
+                MovedEntityRows::AttributeHistory {
+                    attribute_store: attribute_store.clone(),
+                    timestamps,
+                }
+            }
+            MovedEntityRows::RelationSource {
+                relation,
+                target_ids,
+            } => MovedEntityRows::RelationSource {
+                relation: relation.clone(),
+                target_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(target_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::RelationTarget {
+                relation,
+                source_ids,
+            } => MovedEntityRows::RelationTarget {
+                relation: relation.clone(),
+                source_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(source_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::RelationHistorySource {
+                relation,
+                target_ids,
+            } => MovedEntityRows::RelationHistorySource {
+                relation: relation.clone(),
+                target_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(target_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::RelationHistoryTarget {
+                relation,
+                source_ids,
+            } => MovedEntityRows::RelationHistoryTarget {
+                relation: relation.clone(),
+                source_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(source_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::EntitySetRevision {
+                entity_type,
+                revision_ids,
+            } => MovedEntityRows::EntitySetRevision {
+                entity_type: entity_type.clone(),
+                revision_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(revision_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::EntitySet { entity_set_ids } => MovedEntityRows::EntitySet {
+                entity_set_ids: move_entity_rows(
+                    client,
+                    &table,
+                    from_id,
+                    to_id,
+                    Some(entity_set_ids),
+                    conflicts,
+                )
+                .await?,
+            },
+            MovedEntityRows::Alias {
+                alias_table,
+                aliases,
+            } => MovedEntityRows::Alias {
+                alias_table: alias_table.clone(),
+                aliases: move_entity_rows(client, &table, from_id, to_id, Some(aliases), conflicts)
+                    .await?,
+            },
+        };
+
+        result.push(moved_back);
+    }
+
+    Ok(result)
+}
+
+/// Move all rows that refer to one entity to another entity: trend data, attribute history,
+/// relations and their history, entity set revisions and aliases
+async fn move_all_rows<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    from_id: i64,
+    to_id: i64,
+    conflicts: &mut Vec<EntityRowConflicts>,
+) -> Result<Vec<MovedEntityRows>, Error> {
+    let mut result = Vec::new();
+
+    let query = concat!(
+        "SELECT tsp.name FROM trend_directory.trend_store_part tsp ",
+        "JOIN trend_directory.trend_store ts ON ts.id = tsp.trend_store_id ",
+        "JOIN directory.entity_type et ON et.id = ts.entity_type_id ",
+        "WHERE et.name = $1 ORDER BY tsp.name"
+    );
+
+    let trend_store_parts: Vec<String> = client
+        .query(query, &[&entity_type])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load trend store parts: {e}")))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    for trend_store_part in trend_store_parts {
+        let timestamps: Vec<DateTime<Utc>> = move_entity_rows(
+            client,
+            &EntityTable::new("trend", &trend_store_part, "entity_id", "timestamp"),
+            from_id,
+            to_id,
+            None,
+            conflicts,
+        )
+        .await?;
+
+        mark_trend_store_part_modified(client, &trend_store_part, &timestamps).await?;
+
+        result.push(MovedEntityRows::Trend {
+            trend_store_part,
+            timestamps,
+        });
+    }
+
+    let query = concat!(
+        "SELECT ast::text FROM attribute_directory.attribute_store ast ",
+        "JOIN directory.entity_type et ON et.id = ast.entity_type_id ",
+        "WHERE et.name = $1 ORDER BY ast::text"
+    );
+
+    let attribute_stores: Vec<String> = client
+        .query(query, &[&entity_type])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load attribute stores: {e}")))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    for attribute_store in attribute_stores {
+        let timestamps: Vec<DateTime<Utc>> = move_entity_rows(
+            client,
+            &EntityTable::new(
+                "attribute_history",
+                &attribute_store,
+                "entity_id",
+                "timestamp",
+            ),
+            from_id,
+            to_id,
+            None,
+            conflicts,
+        )
+        .await?;
+
+        materialize_attribute_store_curr_ptr(client, &attribute_store).await?;
+
+        result.push(MovedEntityRows::AttributeHistory {
+            attribute_store,
+            timestamps,
+        });
+    }
+
+    let relation_names = load_relation_names(client)
+        .await
+        .map_err(|e| DatabaseError::from_msg(e.to_string()))?;
+
+    // Entity set membership is stored in the '<type>->entity_set' relations, so that is moved
+    // along with the other relations.
+    for relation in relation_names {
+        let Some((source_type, target_type)) = relation_entity_types(&relation) else {
+            continue;
+        };
+
+        let has_history = relation_has_history(client, &relation)
+            .await
+            .map_err(|e| DatabaseError::from_msg(e.to_string()))?;
+
+        if source_type == entity_type {
+            let target_ids = move_entity_rows(
+                client,
+                &EntityTable::new("relation", &relation, "source_id", "target_id"),
+                from_id,
+                to_id,
+                None,
+                conflicts,
+            )
+            .await?;
+
+            result.push(MovedEntityRows::RelationSource {
+                relation: relation.clone(),
+                target_ids,
+            });
+
+            if has_history {
+                let target_ids = move_entity_rows(
+                    client,
+                    &EntityTable::new("relation_history", &relation, "source_id", "target_id"),
+                    from_id,
+                    to_id,
+                    None,
+                    conflicts,
+                )
+                .await?;
+
+                result.push(MovedEntityRows::RelationHistorySource {
+                    relation: relation.clone(),
+                    target_ids,
+                });
+            }
+        }
+
+        if target_type == entity_type {
+            let source_ids = move_entity_rows(
+                client,
+                &EntityTable::new("relation", &relation, "target_id", "source_id"),
+                from_id,
+                to_id,
+                None,
+                conflicts,
+            )
+            .await?;
+
+            result.push(MovedEntityRows::RelationTarget {
+                relation: relation.clone(),
+                source_ids,
+            });
+
+            if has_history {
+                let source_ids = move_entity_rows(
+                    client,
+                    &EntityTable::new("relation_history", &relation, "target_id", "source_id"),
+                    from_id,
+                    to_id,
+                    None,
+                    conflicts,
+                )
+                .await?;
+
+                result.push(MovedEntityRows::RelationHistoryTarget {
+                    relation,
+                    source_ids,
+                });
+            }
+        }
+    }
+
+    let has_entity_set_revisions: bool = client
+        .query_one(
+            "SELECT to_regclass(format('entity_set.%I', $1::text)) IS NOT NULL",
+            &[&entity_type],
+        )
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!("Could not check for entity set revisions: {e}"))
+        })?
+        .get(0);
+
+    if has_entity_set_revisions {
+        let revision_ids = move_entity_rows(
+            client,
+            &EntityTable::new("entity_set", entity_type, "entity_id", "revision_id"),
+            from_id,
+            to_id,
+            None,
+            conflicts,
+        )
+        .await?;
+
+        result.push(MovedEntityRows::EntitySetRevision {
+            entity_type: entity_type.to_string(),
+            revision_ids,
+        });
+    }
+
+    // Entity sets are themselves entities of type 'entity_set' with their revision history
+    // registered in 'directory.entity_set'
+    if entity_type == "entity_set" {
+        let entity_set_ids = move_entity_rows(
+            client,
+            &EntityTable::new("directory", "entity_set", "entity_id", "id").unique_entity(),
+            from_id,
+            to_id,
+            None,
+            conflicts,
+        )
+        .await?;
+
+        result.push(MovedEntityRows::EntitySet { entity_set_ids });
+    }
+
+    let query = concat!(
+        "SELECT alias_directory.alias_table_name(at)::text ",
+        "FROM alias_directory.alias_type at ",
+        "JOIN directory.entity_type et ON et.id = at.entity_type_id ",
+        "WHERE et.name = $1 ORDER BY 1"
+    );
+
+    let alias_tables: Vec<String> = client
+        .query(query, &[&entity_type])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load alias types: {e}")))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    for alias_table in alias_tables {
+        let aliases = move_entity_rows(
+            client,
+            &EntityTable::new("alias", &alias_table, "entity_id", "alias").unique_entity(),
+            from_id,
+            to_id,
+            None,
+            conflicts,
+        )
+        .await?;
+
+        result.push(MovedEntityRows::Alias {
+            alias_table,
+            aliases,
+        });
+    }
+
+    Ok(result
+        .into_iter()
+        .filter(|moved| !moved.is_empty())
+        .collect())
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RenameEntity {
+    pub entity_type: String,
+    pub name: String,
+    pub new_name: String,
+}
+
+impl fmt::Display for RenameEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RenameEntity({}, '{}' -> '{}')",
+            self.entity_type, self.name, self.new_name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RenameEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let tx = client.transaction().await?;
+
+        let entity_id = get_entity_id(&tx, &self.entity_type, &self.name).await?;
+
+        if get_entity_id(&tx, &self.entity_type, &self.new_name)
+            .await
+            .is_ok()
+        {
+            return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                "Entity '{}' of type '{}' already exists, merge the entities instead",
+                self.new_name, self.entity_type
+            ))));
+        }
+
+        let query = format!(
+            "UPDATE entity.{} SET name = $2 WHERE id = $1::bigint",
+            escape_identifier(&self.entity_type)
+        );
+
+        tx.execute(&query, &[&entity_id, &self.new_name])
+            .await
+            .map_err(|e| DatabaseError::from_msg(format!("Could not rename entity: {e}")))?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RenamedEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+            new_name: self.new_name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RenamedEntity {
+    pub entity_type: String,
+    pub name: String,
+    pub new_name: String,
+}
+
+impl Display for RenamedEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Renamed entity '{}' of type '{}' to '{}'",
+            self.name, self.entity_type, self.new_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RenamedEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(RenameEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.new_name.clone(),
+            new_name: self.name.clone(),
+        }))
+    }
+}
+
+/// Merge the data of one entity into another entity, e.g. after a managed object was renamed and
+/// data was loaded for both names. The merged entity is retired afterwards.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MergeEntity {
+    pub entity_type: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl fmt::Display for MergeEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MergeEntity({}, '{}' -> '{}')",
+            self.entity_type, self.from, self.to
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for MergeEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let tx = client.transaction().await?;
+
+        let from_id = get_entity_id(&tx, &self.entity_type, &self.from).await?;
+        let to_id = get_entity_id(&tx, &self.entity_type, &self.to).await?;
+
+        if from_id == to_id {
+            return Err(Error::Runtime(RuntimeError::from_msg(
+                "Can not merge an entity into itself".to_string(),
+            )));
+        }
+
+        let mut conflicts = Vec::new();
+
+        let moved = move_all_rows(&tx, &self.entity_type, from_id, to_id, &mut conflicts).await?;
+
+        set_retired(&tx, &self.entity_type, from_id, true).await?;
+
+        let query = concat!(
+            "INSERT INTO directory.entity_merge(entity_type_id, from_entity_id, to_entity_id) ",
+            "SELECT id, $2, $3 FROM directory.entity_type WHERE name = $1"
+        );
+
+        tx.execute(query, &[&self.entity_type, &from_id, &to_id])
+            .await
+            .map_err(|e| DatabaseError::from_msg(format!("Could not log entity merge: {e}")))?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(MergedEntity {
+            entity_type: self.entity_type.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            moved,
+            conflicts,
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MergedEntity {
+    pub entity_type: String,
+    pub from: String,
+    pub to: String,
+    pub moved: Vec<MovedEntityRows>,
+    /// Rows that were left on the merged entity, because the entity it was merged into already
+    /// has rows with the same key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<EntityRowConflicts>,
+}
+
+impl Display for MergedEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Merged entity '{}' of type '{}' into '{}'",
+            self.from, self.entity_type, self.to
+        )?;
+
+        for moved in &self.moved {
+            writeln!(f, " - {moved}")?;
+        }
+
+        write_conflicts(f, &self.from, &self.conflicts)
+    }
+}
+
+fn write_conflicts(
+    f: &mut fmt::Formatter<'_>,
+    entity: &str,
+    conflicts: &[EntityRowConflicts],
+) -> fmt::Result {
+    if !conflicts.is_empty() {
+        writeln!(
+            f,
+            "Warning: rows left on '{entity}' because the other entity already has rows with the same key:"
+        )?;
+
+        for conflict in conflicts {
+            writeln!(f, " - {conflict}")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[typetag::serde]
+impl Changed for MergedEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(UnmergeEntity {
+            entity_type: self.entity_type.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            moved: self.moved.clone(),
+        }))
+    }
+}
+
+/// Move the rows that were moved by a merge back to the original entity and reactivate it
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UnmergeEntity {
+    pub entity_type: String,
+    pub from: String,
+    pub to: String,
+    pub moved: Vec<MovedEntityRows>,
+}
+
+impl fmt::Display for UnmergeEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UnmergeEntity({}, '{}' <- '{}')",
+            self.entity_type, self.from, self.to
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for UnmergeEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let tx = client.transaction().await?;
+
+        let from_id = get_entity_id(&tx, &self.entity_type, &self.from).await?;
+        let to_id = get_entity_id(&tx, &self.entity_type, &self.to).await?;
+
+        let mut conflicts = Vec::new();
+
+        move_recorded_rows(&tx, &self.moved, to_id, from_id, &mut conflicts).await?;
+
+        set_retired(&tx, &self.entity_type, from_id, false).await?;
+
+        let query = concat!(
+            "UPDATE directory.entity_merge em SET reverted = now() ",
+            "FROM directory.entity_type et ",
+            "WHERE et.id = em.entity_type_id AND et.name = $1 ",
+            "AND em.from_entity_id = $2 AND em.to_entity_id = $3 AND em.reverted IS NULL"
+        );
+
+        tx.execute(query, &[&self.entity_type, &from_id, &to_id])
+            .await
+            .map_err(|e| DatabaseError::from_msg(format!("Could not log entity unmerge: {e}")))?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(UnmergedEntity {
+            entity_type: self.entity_type.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            conflicts,
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UnmergedEntity {
+    pub entity_type: String,
+    pub from: String,
+    pub to: String,
+    /// Rows that were left on the entity that was merged into, because the original entity
+    /// already has rows with the same key again
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<EntityRowConflicts>,
+}
+
+impl Display for UnmergedEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Moved data of entity '{}' of type '{}' back from '{}'",
+            self.from, self.entity_type, self.to
+        )?;
+
+        write_conflicts(f, &self.to, &self.conflicts)
+    }
+}
+
+#[typetag::serde]
+impl Changed for UnmergedEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(MergeEntity {
+            entity_type: self.entity_type.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }))
+    }
+}
+
+/// Mark an entity as inactive, so that it can be excluded from entity sets, triggers and other
+/// processing using `directory.is_retired`. The data of the entity is kept.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RetireEntity {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl fmt::Display for RetireEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RetireEntity({}, '{}')", self.entity_type, self.name)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RetireEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let tx = client.transaction().await?;
+
+        let entity_id = get_entity_id(&tx, &self.entity_type, &self.name).await?;
+
+        set_retired(&tx, &self.entity_type, entity_id, true).await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RetiredEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RetiredEntity {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl Display for RetiredEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Retired entity '{}' of type '{}'",
+            self.name, self.entity_type
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RetiredEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(ReactivateEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ReactivateEntity {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl fmt::Display for ReactivateEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ReactivateEntity({}, '{}')", self.entity_type, self.name)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for ReactivateEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let tx = client.transaction().await?;
+
+        let entity_id = get_entity_id(&tx, &self.entity_type, &self.name).await?;
+
+        set_retired(&tx, &self.entity_type, entity_id, false).await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(ReactivatedEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ReactivatedEntity {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl Display for ReactivatedEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Reactivated entity '{}' of type '{}'",
+            self.name, self.entity_type
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for ReactivatedEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(RetireEntity {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_entity_rows_query() {
+        let columns = vec![
+            "entity_id".to_string(),
+            "timestamp".to_string(),
+            "created".to_string(),
+            "x".to_string(),
+        ];
+
+        let query = move_entity_rows_query(
+            &EntityTable::new("trend", "node_15m", "entity_id", "timestamp"),
+            &columns,
+            true,
+        );
+
+        assert!(query.contains("SELECT $2::bigint, \"timestamp\", \"created\", \"x\" FROM moved"));
+        assert!(query.contains("t.\"timestamp\" = ANY($3)"));
+        assert!(query.contains("WHERE t.\"entity_id\" = $1::bigint"));
+        assert!(
+            query.contains(
+                "WHERE o.\"entity_id\" = $2::bigint AND o.\"timestamp\" = t.\"timestamp\""
+            )
+        );
+        assert!(pg_query::parse(&query).is_ok());
+
+        let query = move_entity_rows_query(
+            &EntityTable::new("relation", "node->entity_set", "source_id", "target_id"),
+            &["source_id".to_string(), "target_id".to_string()],
+            false,
+        );
+
+        assert!(!query.contains("ANY($3)"));
+        assert!(pg_query::parse(&query).is_ok());
+
+        let query = move_entity_rows_query(
+            &EntityTable::new("alias", "node_code", "entity_id", "alias").unique_entity(),
+            &["entity_id".to_string(), "alias".to_string()],
+            false,
+        );
+
+        assert!(query.contains("WHERE o.\"entity_id\" = $2::bigint) RETURNING"));
+        assert!(pg_query::parse(&query).is_ok());
+
+        assert_eq!(
+            entity_rows_count_query(
+                &EntityTable::new("trend", "node_15m", "entity_id", "timestamp"),
+                true
+            ),
+            "SELECT count(*) FROM \"trend\".\"node_15m\" WHERE \"entity_id\" = $1::bigint AND \"timestamp\" = ANY($2)"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use comfy_table::presets::UTF8_FULL_CONDENSED;
 use comfy_table::*;
 use console::Style;
@@ -19,13 +19,19 @@ use crate::change::{Change, ChangeResult, Changed, InformationOption, MinervaObj
 use crate::error::{DatabaseError, RuntimeError};
 use crate::interval::parse_interval;
 use crate::meas_value::DataType;
+use crate::trend_export::{TrendExport, TrendExportFormat, TrendExportSource};
 use crate::trend_store::create::{
-    create_trend_store, create_trend_store_part, remove_trend_store_part,
+    create_trend_store, create_trend_store_part, define_generated_trend,
+    generated_trend_column_spec, remove_trend_store_part,
 };
+use crate::trend_store::generated::validate_generated_trend;
 use crate::trend_store::remove::remove_trend_store;
+use crate::trend_store::reorganize::{
+    TrendReference, copy_trend_data, replace_part_reference, trend_references, uses_trends,
+};
 use crate::trend_store::{
-    Trend, TrendStore, TrendStorePart, TrendStoreRef, get_trend_store_id, load_trend_store,
-    load_trend_store_part, load_trend_store_ref_for_part,
+    GeneratedTrend, Trend, TrendStore, TrendStorePart, TrendStoreRef, get_trend_store_id,
+    load_trend_store, load_trend_store_part, load_trend_store_ref_for_part,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -122,12 +128,14 @@ pub fn random_name(len: usize) -> String {
     Alphanumeric.sample_string(&mut rand::rng(), len)
 }
 
-#[async_trait]
-#[typetag::serde]
-impl Change for StageTrendsForDeletion {
-    async fn apply(&self, client: &mut Client) -> ChangeResult {
-        let mut tx = client.transaction().await?;
-
+impl StageTrendsForDeletion {
+    /// Rename the trend columns to random names and mark the trends as staged for deletion,
+    /// within the transaction of the caller
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a trend cannot be staged.
+    pub async fn stage<T: GenericClient>(&self, client: &mut T) -> Result<(), DatabaseError> {
         let renamings: Vec<TrendColumnRename> = self
             .trends
             .iter()
@@ -139,7 +147,7 @@ impl Change for StageTrendsForDeletion {
             .collect();
 
         for trend_column_rename in &renamings {
-            trend_column_rename.update(&mut tx).await.map_err(|e| {
+            trend_column_rename.update(client).await.map_err(|e| {
                 DatabaseError::from_msg(format!(
                     "Error staging trend '{}' for removal in trend store part '{}': {}",
                     trend_column_rename.trend_name, self.trend_store_part_name, e
@@ -147,6 +155,18 @@ impl Change for StageTrendsForDeletion {
             })?;
         }
 
+        Ok(())
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for StageTrendsForDeletion {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        self.stage(&mut tx).await?;
+
         tx.commit().await?;
 
         Ok(Box::new(TrendsStagedForDeletion {
@@ -197,12 +217,100 @@ struct RestoreTrendsStagedForDeletion {
     pub trends: Vec<String>,
 }
 
+impl RestoreTrendsStagedForDeletion {
+    /// Rename the staged columns of the trends back to the trend names and clear the staging
+    /// marks, within the transaction of the caller
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a trend is not staged for deletion or cannot be restored.
+    pub async fn restore<T: GenericClient>(&self, client: &mut T) -> Result<(), DatabaseError> {
+        let staged_trend_query = concat!(
+            "SELECT tt.id, tt.deletion_staging_column ",
+            "FROM trend_directory.table_trend tt ",
+            "JOIN trend_directory.trend_store_part tsp ON tsp.id = tt.trend_store_part_id ",
+            "WHERE tsp.name = $1 AND tt.name = $2 AND tt.deleted IS NULL ",
+            "AND tt.staged_for_deletion IS NOT NULL AND tt.deletion_staging_column IS NOT NULL ",
+            "ORDER BY tt.staged_for_deletion DESC LIMIT 1"
+        );
+
+        for trend_name in &self.trends {
+            let row = client
+                .query_opt(
+                    staged_trend_query,
+                    &[&self.trend_store_part_name, trend_name],
+                )
+                .await
+                .map_err(|e| {
+                    DatabaseError::from_msg(format!(
+                        "Error loading staged trend '{trend_name}' of trend store part '{}': {e}",
+                        self.trend_store_part_name
+                    ))
+                })?
+                .ok_or_else(|| {
+                    DatabaseError::from_msg(format!(
+                        "No trend '{trend_name}' staged for deletion in trend store part '{}'",
+                        self.trend_store_part_name
+                    ))
+                })?;
+
+            let table_trend_id: i32 = row.get(0);
+            let staging_name: String = row.get(1);
+
+            let restore_error = |e: tokio_postgres::Error| {
+                DatabaseError::from_msg(format!(
+                    "Error restoring trend '{trend_name}' staged for deletion in trend store part '{}': {e}",
+                    self.trend_store_part_name
+                ))
+            };
+
+            for table_name in [
+                self.trend_store_part_name.clone(),
+                format!("{}_staging", self.trend_store_part_name),
+            ] {
+                let alter_query = format!(
+                    "ALTER TABLE trend.{} RENAME COLUMN {} TO {}",
+                    escape_identifier(&table_name),
+                    escape_identifier(&staging_name),
+                    escape_identifier(trend_name)
+                );
+
+                client
+                    .execute(&alter_query, &[])
+                    .await
+                    .map_err(restore_error)?;
+            }
+
+            client
+                .execute(
+                    concat!(
+                        "UPDATE trend_directory.table_trend ",
+                        "SET staged_for_deletion = NULL, deletion_staging_column = NULL ",
+                        "WHERE id = $1"
+                    ),
+                    &[&table_trend_id],
+                )
+                .await
+                .map_err(restore_error)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[async_trait]
 #[typetag::serde]
 impl Change for RestoreTrendsStagedForDeletion {
-    async fn apply(&self, _client: &mut Client) -> ChangeResult {
-        Err(crate::error::Error::Runtime(RuntimeError {
-            msg: "Not implemented".to_string(),
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        self.restore(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(TrendsRestored {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trends: self.trends.clone(),
         }))
     }
 }
@@ -211,13 +319,41 @@ impl Display for RestoreTrendsStagedForDeletion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Staged {} trends for deletion in trend store part '{}'",
+            "RestoreTrendsStagedForDeletion(TrendStorePart({}), {})",
+            self.trend_store_part_name,
+            self.trends.len()
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+struct TrendsRestored {
+    pub trend_store_part_name: String,
+    pub trends: Vec<String>,
+}
+
+impl Display for TrendsRestored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Restored {} trends staged for deletion in trend store part '{}'",
             self.trends.len(),
             self.trend_store_part_name
         )
     }
 }
 
+#[typetag::serde]
+impl Changed for TrendsRestored {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(StageTrendsForDeletion {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trends: self.trends.clone(),
+        }))
+    }
+}
+
 /////////////// RemoveTrends ////////////
 
 async fn load_trend<T: GenericClient>(
@@ -1784,3 +1920,1326 @@ impl Changed for CreatedStatistics {
         None
     }
 }
+
+/// Remove a partition of a trend store part, including its data. The data cannot be restored by
+/// reverting, so the removal is recorded with the location of the archive if one was made.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemoveTrendStorePartition {
+    pub partition: String,
+    /// CSV file to archive the data of the partition to before it is removed
+    pub archive: Option<String>,
+}
+
+impl fmt::Display for RemoveTrendStorePartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RemoveTrendStorePartition({})", self.partition)?;
+
+        if let Some(archive) = &self.archive {
+            write!(f, " archiving to '{archive}'")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RemoveTrendStorePartition {
+    async fn write_archive(
+        &self,
+        client: &Client,
+        archive: &str,
+        trend_store_part: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<(), RuntimeError> {
+        let path = std::path::Path::new(archive);
+
+        if let Some(archive_dir) = path.parent() {
+            std::fs::create_dir_all(archive_dir).map_err(|e| {
+                RuntimeError::from_msg(format!(
+                    "Could not create archive directory '{}': {e}",
+                    archive_dir.to_string_lossy()
+                ))
+            })?;
+        }
+
+        // The export range is inclusive and the partition range is not, so the export ends one
+        // microsecond (the resolution of timestamps in the database) before the partition does.
+        let export = TrendExport::new(
+            TrendExportSource::TrendStorePart(trend_store_part.to_string()),
+            TrendExportFormat::Csv,
+        )
+        .with_time_range(Some(from), Some(to - chrono::Duration::microseconds(1)));
+
+        let mut file = tokio::fs::File::create(path).await.map_err(|e| {
+            RuntimeError::from_msg(format!("Could not create archive file '{archive}': {e}"))
+        })?;
+
+        export.write(client, &mut file).await.map_err(|e| {
+            RuntimeError::from_msg(format!("Could not archive '{}': {e}", self.partition))
+        })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RemoveTrendStorePartition {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let query = concat!(
+            "SELECT p.id, tsp.name, p.from, p.to ",
+            "FROM trend_directory.partition p ",
+            "JOIN trend_directory.trend_store_part tsp ON tsp.id = p.trend_store_part_id ",
+            "WHERE p.name = $1"
+        );
+
+        let row = client
+            .query_opt(query, &[&self.partition])
+            .await?
+            .ok_or_else(|| {
+                RuntimeError::from_msg(format!("No such partition '{}'", self.partition))
+            })?;
+
+        let partition_id: i32 = row.get(0);
+
+        if let Some(archive) = &self.archive {
+            self.write_archive(client, archive, row.get(1), row.get(2), row.get(3))
+                .await?;
+        }
+
+        let tx = client.transaction().await?;
+
+        let drop_query = format!(
+            "DROP TABLE trend_partition.{}",
+            escape_identifier(&self.partition)
+        );
+
+        tx.execute(&drop_query, &[]).await.map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error dropping partition '{}': {e}",
+                self.partition
+            ))
+        })?;
+
+        tx.execute(
+            "DELETE FROM trend_directory.partition WHERE id = $1",
+            &[&partition_id],
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RemovedTrendStorePartition {
+            trend_store_part: row.get(1),
+            partition: self.partition.clone(),
+            from: row.get(2),
+            to: row.get(3),
+            archive: self.archive.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemovedTrendStorePartition {
+    pub trend_store_part: String,
+    pub partition: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub archive: Option<String>,
+}
+
+impl Display for RemovedTrendStorePartition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Removed partition '{}' of '{}' ({} - {})",
+            self.partition, self.trend_store_part, self.from, self.to
+        )?;
+
+        if let Some(archive) = &self.archive {
+            write!(f, ", archived to '{archive}'")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl Changed for RemovedTrendStorePartition {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        None
+    }
+}
+
+/// Result of moving trends with their data from one trend store part to another
+struct MovedTrends {
+    target_created: bool,
+    row_count: u64,
+    /// Definitions of the views and functions that used the trends, before they were updated
+    references: Vec<TrendReference>,
+}
+
+/// What to do with the moved trends in the source trend store part
+#[derive(Clone, Copy, PartialEq)]
+enum SourceCleanup {
+    /// Remove the moved trends from the source part
+    RemoveTrends,
+    /// Stage the moved trends for deletion, so that their columns can still be inspected before
+    /// they are removed
+    StageTrends,
+    /// Remove the moved trends and then the emptied source part
+    RemovePart,
+}
+
+/// Update the views and functions, like materializations and trigger rules, that use the trends
+/// of the source trend store part to use the target part instead. Definitions in `restore` that
+/// were recorded by an earlier move are put back as they were instead. Returns the definitions
+/// as they were before the update.
+async fn update_trend_references<T: GenericClient>(
+    client: &T,
+    source_part: &TrendStorePart,
+    target_part_name: &str,
+    trend_names: &[String],
+    restore: &[TrendReference],
+) -> Result<Vec<TrendReference>, crate::error::Error> {
+    let references = trend_references(client, &source_part.name, trend_names).await?;
+
+    let remaining_trends: Vec<String> = source_part
+        .trends
+        .iter()
+        .filter(|trend| !trend_names.contains(&trend.name))
+        .map(|trend| trend.name.clone())
+        .collect();
+
+    for reference in &references {
+        let definition = match restore.iter().find(|r| r.name == reference.name) {
+            Some(recorded) => recorded.definition.clone(),
+            None => {
+                if uses_trends(&reference.definition, &remaining_trends) {
+                    return Err(RuntimeError::from_msg(format!(
+                        "{} uses trends of '{}' that are moved and trends that are not, update its definition first",
+                        reference.name, source_part.name
+                    ))
+                    .into());
+                }
+
+                replace_part_reference(&reference.definition, &source_part.name, target_part_name)
+            }
+        };
+
+        client.batch_execute(&definition).await.map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error updating {} to use '{target_part_name}': {e}",
+                reference.name
+            ))
+        })?;
+    }
+
+    Ok(references)
+}
+
+/// Copy the statistics and tags of trends to the trends with the same names in another trend
+/// store part
+async fn copy_trend_metadata<T: GenericClient>(
+    client: &T,
+    source_part_name: &str,
+    target_part_name: &str,
+    trend_names: &[String],
+) -> Result<(), crate::error::Error> {
+    let table_trends = concat!(
+        "WITH source AS (",
+        "SELECT tt.id, tt.name FROM trend_directory.table_trend tt ",
+        "JOIN trend_directory.trend_store_part tsp ON tsp.id = tt.trend_store_part_id ",
+        "WHERE tsp.name = $1 AND tt.name = ANY($3) AND tt.deleted IS NULL AND tt.staged_for_deletion IS NULL",
+        "), target AS (",
+        "SELECT tt.id, tt.name FROM trend_directory.table_trend tt ",
+        "JOIN trend_directory.trend_store_part tsp ON tsp.id = tt.trend_store_part_id ",
+        "WHERE tsp.name = $2 AND tt.name = ANY($3) AND tt.deleted IS NULL AND tt.staged_for_deletion IS NULL",
+        ") "
+    );
+
+    let copy_statistics_query = format!(
+        concat!(
+            "{}UPDATE trend_directory.table_trend_statistics s ",
+            "SET min = source_statistics.min, max = source_statistics.max ",
+            "FROM source ",
+            "JOIN trend_directory.table_trend_statistics source_statistics ON source_statistics.table_trend_id = source.id ",
+            "JOIN target ON target.name = source.name ",
+            "WHERE s.table_trend_id = target.id"
+        ),
+        table_trends
+    );
+
+    let copy_tags_query = format!(
+        concat!(
+            "{}INSERT INTO trend_directory.table_trend_tag_link(table_trend_id, tag_id) ",
+            "SELECT target.id, l.tag_id ",
+            "FROM source ",
+            "JOIN trend_directory.table_trend_tag_link l ON l.table_trend_id = source.id ",
+            "JOIN target ON target.name = source.name ",
+            "ON CONFLICT DO NOTHING"
+        ),
+        table_trends
+    );
+
+    for query in [copy_statistics_query, copy_tags_query] {
+        client
+            .execute(
+                &query,
+                &[&source_part_name, &target_part_name, &trend_names],
+            )
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error copying metadata of trends of '{source_part_name}': {e}"
+                ))
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Move trends with their data, statistics and tags from one trend store part to another part of
+/// the same trend store, creating the target part if it does not exist. The data is copied one
+/// partition at a time and the views and functions that use the trends are updated to use the
+/// target part. Everything is done in one transaction that locks the source part against writes,
+/// so a failed move leaves both parts as they were.
+async fn move_trends(
+    client: &mut Client,
+    source_part_name: &str,
+    target_part_name: &str,
+    trend_names: &[String],
+    cleanup: SourceCleanup,
+    restore: &[TrendReference],
+) -> Result<MovedTrends, crate::error::Error> {
+    let mut tx = client.transaction().await?;
+
+    tx.execute(
+        &format!(
+            "LOCK TABLE trend.{} IN EXCLUSIVE MODE",
+            escape_identifier(source_part_name)
+        ),
+        &[],
+    )
+    .await
+    .map_err(|e| {
+        DatabaseError::from_msg(format!(
+            "Could not lock trend store part '{source_part_name}': {e}"
+        ))
+    })?;
+
+    let source_part = load_trend_store_part(&tx, source_part_name)
+        .await
+        .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+    let trend_store = load_trend_store_ref_for_part(&tx, source_part_name)
+        .await
+        .map_err(RuntimeError::from_msg)?;
+
+    let trends = trend_names
+        .iter()
+        .map(|trend_name| {
+            source_part
+                .trends
+                .iter()
+                .find(|trend| &trend.name == trend_name)
+                .cloned()
+                .ok_or_else(|| {
+                    RuntimeError::from_msg(format!(
+                        "No trend '{trend_name}' in trend store part '{source_part_name}'"
+                    ))
+                })
+        })
+        .collect::<Result<Vec<Trend>, RuntimeError>>()?;
+
+    let target_exists = tx
+        .query_opt(
+            "SELECT 1 FROM trend_directory.trend_store_part WHERE name = $1",
+            &[&target_part_name],
+        )
+        .await?
+        .is_some();
+
+    if target_exists {
+        let target_trend_store = load_trend_store_ref_for_part(&tx, target_part_name)
+            .await
+            .map_err(RuntimeError::from_msg)?;
+
+        if target_trend_store != trend_store {
+            return Err(RuntimeError::from_msg(format!(
+                "Trend store part '{target_part_name}' is not part of trend store '{trend_store}'"
+            ))
+            .into());
+        }
+
+        let target_part = load_trend_store_part(&tx, target_part_name)
+            .await
+            .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+
+        if let Some(trend) = target_part
+            .trends
+            .iter()
+            .find(|trend| trend_names.contains(&trend.name))
+        {
+            return Err(RuntimeError::from_msg(format!(
+                "Trend store part '{target_part_name}' already has a trend '{}'",
+                trend.name
+            ))
+            .into());
+        }
+
+        create_table_trends(&mut tx, target_part_name, &trends)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error adding trends to trend store part '{target_part_name}': {e}"
+                ))
+            })?;
+    } else {
+        let trend_store_id = get_trend_store_id(
+            &tx,
+            &trend_store.data_source,
+            &trend_store.entity_type,
+            &trend_store.granularity,
+        )
+        .await?;
+
+        let target_part = TrendStorePart {
+            name: target_part_name.to_string(),
+            has_alias_column: false,
+            trends,
+            generated_trends: Vec::new(),
+            entity_id_type: source_part.entity_id_type.clone(),
+        };
+
+        create_trend_store_part(&mut tx, trend_store_id, &target_part)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error creating trend store part '{target_part_name}': {e}"
+                ))
+            })?;
+    }
+
+    copy_trend_metadata(&tx, source_part_name, target_part_name, trend_names).await?;
+
+    let row_count = copy_trend_data(&tx, source_part_name, target_part_name, trend_names).await?;
+
+    let references =
+        update_trend_references(&tx, &source_part, target_part_name, trend_names, restore).await?;
+
+    if cleanup == SourceCleanup::StageTrends {
+        StageTrendsForDeletion {
+            trend_store_part_name: source_part_name.to_string(),
+            trends: trend_names.to_vec(),
+        }
+        .stage(&mut tx)
+        .await?;
+    } else {
+        for trend_name in trend_names {
+            let remove = TrendRemove {
+                trend_store_part_name: source_part_name.to_string(),
+                trend_name: trend_name.clone(),
+            };
+
+            remove.remove(&mut tx).await.map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error removing trend '{trend_name}' from trend store part '{source_part_name}': {e}"
+                ))
+            })?;
+        }
+    }
+
+    if cleanup == SourceCleanup::RemovePart {
+        remove_trend_store_part(&mut tx, source_part_name)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error removing trend store part '{source_part_name}': {e}"
+                ))
+            })?;
+    }
+
+    tx.commit().await?;
+
+    Ok(MovedTrends {
+        target_created: !target_exists,
+        row_count,
+        references,
+    })
+}
+
+/// Move trends with their data from a trend store part into a new or existing part of the same
+/// trend store
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct SplitTrendStorePart {
+    pub trend_store_part_name: String,
+    pub trends: Vec<String>,
+    pub target_part_name: String,
+    /// Definitions of views and functions to put back instead of updating them, recorded by the
+    /// move that is reverted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<TrendReference>,
+}
+
+impl fmt::Display for SplitTrendStorePart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "SplitTrendStorePart({} -> {}, {}):",
+            self.trend_store_part_name,
+            self.target_part_name,
+            self.trends.len()
+        )?;
+
+        for trend in &self.trends {
+            writeln!(f, " - {trend}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for SplitTrendStorePart {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let moved = move_trends(
+            client,
+            &self.trend_store_part_name,
+            &self.target_part_name,
+            &self.trends,
+            SourceCleanup::RemoveTrends,
+            &self.references,
+        )
+        .await?;
+
+        Ok(Box::new(TrendStorePartSplit {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trends: self.trends.clone(),
+            target_part_name: self.target_part_name.clone(),
+            target_created: moved.target_created,
+            row_count: moved.row_count,
+            references: moved.references,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct TrendStorePartSplit {
+    pub trend_store_part_name: String,
+    pub trends: Vec<String>,
+    pub target_part_name: String,
+    pub target_created: bool,
+    pub row_count: u64,
+    /// Definitions of the views and functions that used the trends, before they were updated
+    #[serde(default)]
+    pub references: Vec<TrendReference>,
+}
+
+impl Display for TrendStorePartSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Moved {} trends with {} rows from '{}' to {} trend store part '{}'",
+            self.trends.len(),
+            self.row_count,
+            self.trend_store_part_name,
+            if self.target_created {
+                "new"
+            } else {
+                "existing"
+            },
+            self.target_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for TrendStorePartSplit {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        if self.target_created {
+            Some(Box::new(MergeTrendStoreParts {
+                trend_store_part_name: self.target_part_name.clone(),
+                target_part_name: self.trend_store_part_name.clone(),
+                references: self.references.clone(),
+            }))
+        } else {
+            Some(Box::new(SplitTrendStorePart {
+                trend_store_part_name: self.target_part_name.clone(),
+                trends: self.trends.clone(),
+                target_part_name: self.trend_store_part_name.clone(),
+                references: self.references.clone(),
+            }))
+        }
+    }
+}
+
+/// Move all trends with their data from a trend store part into another part of the same trend
+/// store and remove the emptied part. Parts with generated trends cannot be merged, because the
+/// generated trends would be lost.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MergeTrendStoreParts {
+    pub trend_store_part_name: String,
+    pub target_part_name: String,
+    /// Definitions of views and functions to put back instead of updating them, recorded by the
+    /// move that is reverted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<TrendReference>,
+}
+
+impl fmt::Display for MergeTrendStoreParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MergeTrendStoreParts({} -> {})",
+            self.trend_store_part_name, self.target_part_name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for MergeTrendStoreParts {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let trend_store_part = load_trend_store_part(client, &self.trend_store_part_name)
+            .await
+            .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+
+        let generated_trend_count: i64 = client
+            .query_one(
+                concat!(
+                    "SELECT count(*) FROM trend_directory.generated_table_trend gtt ",
+                    "JOIN trend_directory.trend_store_part tsp ON tsp.id = gtt.trend_store_part_id ",
+                    "WHERE tsp.name = $1"
+                ),
+                &[&self.trend_store_part_name],
+            )
+            .await?
+            .get(0);
+
+        if generated_trend_count > 0 {
+            return Err(RuntimeError::from_msg(format!(
+                "Trend store part '{}' has generated trends and cannot be merged",
+                self.trend_store_part_name
+            ))
+            .into());
+        }
+
+        let trends: Vec<String> = trend_store_part
+            .trends
+            .into_iter()
+            .map(|trend| trend.name)
+            .collect();
+
+        let moved = move_trends(
+            client,
+            &self.trend_store_part_name,
+            &self.target_part_name,
+            &trends,
+            SourceCleanup::RemovePart,
+            &self.references,
+        )
+        .await?;
+
+        Ok(Box::new(MergedTrendStoreParts {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            target_part_name: self.target_part_name.clone(),
+            trends,
+            row_count: moved.row_count,
+            references: moved.references,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MergedTrendStoreParts {
+    pub trend_store_part_name: String,
+    pub target_part_name: String,
+    pub trends: Vec<String>,
+    pub row_count: u64,
+    /// Definitions of the views and functions that used the trends, before they were updated
+    #[serde(default)]
+    pub references: Vec<TrendReference>,
+}
+
+impl Display for MergedTrendStoreParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Merged trend store part '{}' with {} trends and {} rows into '{}'",
+            self.trend_store_part_name,
+            self.trends.len(),
+            self.row_count,
+            self.target_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for MergedTrendStoreParts {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(SplitTrendStorePart {
+            trend_store_part_name: self.target_part_name.clone(),
+            trends: self.trends.clone(),
+            target_part_name: self.trend_store_part_name.clone(),
+            references: self.references.clone(),
+        }))
+    }
+}
+
+/// Move a trend with its data to another existing part of the same trend store. The trend
+/// definition, statistics and tags are copied to the target part and the column in the source
+/// part is staged for deletion, so that it can still be inspected before it is removed.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MoveTrend {
+    pub trend_store_part_name: String,
+    pub trend_name: String,
+    pub target_part_name: String,
+}
+
+impl fmt::Display for MoveTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MoveTrend({}, {} -> {})",
+            self.trend_name, self.trend_store_part_name, self.target_part_name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for MoveTrend {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let target_exists = client
+            .query_opt(
+                "SELECT 1 FROM trend_directory.trend_store_part WHERE name = $1",
+                &[&self.target_part_name],
+            )
+            .await?
+            .is_some();
+
+        if !target_exists {
+            return Err(RuntimeError::from_msg(format!(
+                "No trend store part '{}'",
+                self.target_part_name
+            ))
+            .into());
+        }
+
+        let moved = move_trends(
+            client,
+            &self.trend_store_part_name,
+            &self.target_part_name,
+            std::slice::from_ref(&self.trend_name),
+            SourceCleanup::StageTrends,
+            &[],
+        )
+        .await?;
+
+        Ok(Box::new(MovedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trend_name: self.trend_name.clone(),
+            target_part_name: self.target_part_name.clone(),
+            row_count: moved.row_count,
+            references: moved.references,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+
+    fn information_options(&self) -> Vec<Box<dyn InformationOption>> {
+        vec![Box::new(TrendRemoveValueInformation {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trend_names: vec![self.trend_name.clone()],
+        })]
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MovedTrend {
+    pub trend_store_part_name: String,
+    pub trend_name: String,
+    pub target_part_name: String,
+    pub row_count: u64,
+    /// Definitions of the views and functions that used the trends, before they were updated
+    #[serde(default)]
+    pub references: Vec<TrendReference>,
+}
+
+impl Display for MovedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Moved trend '{}' with {} rows from '{}' to '{}' and staged the old column for deletion",
+            self.trend_name, self.row_count, self.trend_store_part_name, self.target_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for MovedTrend {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(RestoreMovedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trend_name: self.trend_name.clone(),
+            target_part_name: self.target_part_name.clone(),
+            references: self.references.clone(),
+        }))
+    }
+}
+
+/// Undo a move of a trend by restoring the column that was staged for deletion in the original
+/// trend store part. Data that was stored in the target part since the move is copied back, the
+/// views and functions that use the trend get their recorded definitions back and the trend is
+/// removed from the target part.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RestoreMovedTrend {
+    pub trend_store_part_name: String,
+    pub trend_name: String,
+    pub target_part_name: String,
+    #[serde(default)]
+    pub references: Vec<TrendReference>,
+}
+
+impl fmt::Display for RestoreMovedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RestoreMovedTrend({}, {} <- {})",
+            self.trend_name, self.trend_store_part_name, self.target_part_name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RestoreMovedTrend {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        for part_name in [&self.trend_store_part_name, &self.target_part_name] {
+            tx.execute(
+                &format!(
+                    "LOCK TABLE trend.{} IN EXCLUSIVE MODE",
+                    escape_identifier(part_name)
+                ),
+                &[],
+            )
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Could not lock trend store part '{part_name}': {e}"
+                ))
+            })?;
+        }
+
+        let trend_names = std::slice::from_ref(&self.trend_name);
+
+        RestoreTrendsStagedForDeletion {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trends: trend_names.to_vec(),
+        }
+        .restore(&mut tx)
+        .await?;
+
+        let target_part = load_trend_store_part(&tx, &self.target_part_name)
+            .await
+            .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+
+        let row_count = copy_trend_data(
+            &tx,
+            &self.target_part_name,
+            &self.trend_store_part_name,
+            trend_names,
+        )
+        .await?;
+
+        update_trend_references(
+            &tx,
+            &target_part,
+            &self.trend_store_part_name,
+            trend_names,
+            &self.references,
+        )
+        .await?;
+
+        TrendRemove {
+            trend_store_part_name: self.target_part_name.clone(),
+            trend_name: self.trend_name.clone(),
+        }
+        .remove(&mut tx)
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error removing trend '{}' from trend store part '{}': {e}",
+                self.trend_name, self.target_part_name
+            ))
+        })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(MovedTrendRestored {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trend_name: self.trend_name.clone(),
+            target_part_name: self.target_part_name.clone(),
+            row_count,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct MovedTrendRestored {
+    pub trend_store_part_name: String,
+    pub trend_name: String,
+    pub target_part_name: String,
+    pub row_count: u64,
+}
+
+impl Display for MovedTrendRestored {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Restored trend '{}' in '{}' with {} rows copied back from '{}'",
+            self.trend_name, self.trend_store_part_name, self.row_count, self.target_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for MovedTrendRestored {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(MoveTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            trend_name: self.trend_name.clone(),
+            target_part_name: self.target_part_name.clone(),
+        }))
+    }
+}
+
+async fn check_generated_trend<T: GenericClient>(
+    client: &T,
+    trend_store_part_name: &str,
+    generated_trend: &GeneratedTrend,
+) -> Result<(), crate::error::Error> {
+    let trend_store_part = load_trend_store_part(client, trend_store_part_name)
+        .await
+        .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+
+    validate_generated_trend(&trend_store_part, generated_trend)
+        .map_err(|e| RuntimeError::from_msg(e.to_string()))?;
+
+    Ok(())
+}
+
+async fn add_generated_trend_column<T: GenericClient>(
+    client: &mut T,
+    trend_store_part_name: &str,
+    generated_trend: &GeneratedTrend,
+) -> Result<(), tokio_postgres::Error> {
+    let query = format!(
+        "ALTER TABLE {}.{} ADD COLUMN {}",
+        BASE_TABLE_SCHEMA,
+        escape_identifier(trend_store_part_name),
+        generated_trend_column_spec(generated_trend)
+    );
+
+    client.execute(&query, &[]).await?;
+
+    Ok(())
+}
+
+async fn drop_generated_trend_column<T: GenericClient>(
+    client: &mut T,
+    trend_store_part_name: &str,
+    generated_trend_name: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let query = format!(
+        "ALTER TABLE {}.{} DROP COLUMN {}",
+        BASE_TABLE_SCHEMA,
+        escape_identifier(trend_store_part_name),
+        escape_identifier(generated_trend_name)
+    );
+
+    client.execute(&query, &[]).await?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub generated_trend: GeneratedTrend,
+}
+
+impl fmt::Display for AddGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AddGeneratedTrend(TrendStorePart({}), {}: {} = {})",
+            self.trend_store_part_name,
+            self.generated_trend.name,
+            self.generated_trend.data_type,
+            self.generated_trend.expression
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for AddGeneratedTrend {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        check_generated_trend(&tx, &self.trend_store_part_name, &self.generated_trend).await?;
+
+        let trend_store_part_id: i32 = tx
+            .query_one(
+                "SELECT id FROM trend_directory.trend_store_part WHERE name = $1",
+                &[&self.trend_store_part_name],
+            )
+            .await?
+            .get(0);
+
+        define_generated_trend(&mut tx, trend_store_part_id, &self.generated_trend)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error defining generated trend '{}': {e}",
+                    self.generated_trend.name
+                ))
+            })?;
+
+        add_generated_trend_column(&mut tx, &self.trend_store_part_name, &self.generated_trend)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Error adding generated trend '{}' to trend store part '{}': {e}",
+                    self.generated_trend.name, self.trend_store_part_name
+                ))
+            })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(AddedGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            generated_trend_name: self.generated_trend.name.clone(),
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddedGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub generated_trend_name: String,
+}
+
+impl Display for AddedGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Added generated trend '{}' to trend store part '{}'",
+            self.generated_trend_name, self.trend_store_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for AddedGeneratedTrend {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(RemoveGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            generated_trend_name: self.generated_trend_name.clone(),
+        }))
+    }
+}
+
+/// Change the definition of a generated trend. The column is recreated, so that all values are
+/// generated again with the new expression.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ModifyGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub from: GeneratedTrend,
+    pub to: GeneratedTrend,
+}
+
+impl fmt::Display for ModifyGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "ModifyGeneratedTrend(TrendStorePart({}), {}):",
+            self.trend_store_part_name, self.from.name
+        )?;
+
+        if self.from.data_type != self.to.data_type {
+            writeln!(
+                f,
+                " - data type: {} -> {}",
+                self.from.data_type, self.to.data_type
+            )?;
+        }
+
+        if self.from.expression != self.to.expression {
+            writeln!(
+                f,
+                " - expression: {} -> {}",
+                self.from.expression, self.to.expression
+            )?;
+        }
+
+        if self.from.description != self.to.description {
+            writeln!(f, " - description")?;
+        }
+
+        if self.from.extra_data != self.to.extra_data {
+            writeln!(f, " - extra data")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for ModifyGeneratedTrend {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        check_generated_trend(&tx, &self.trend_store_part_name, &self.to).await?;
+
+        if self.from.data_type != self.to.data_type || self.from.expression != self.to.expression {
+            drop_generated_trend_column(&mut tx, &self.trend_store_part_name, &self.from.name)
+                .await
+                .map_err(|e| {
+                    DatabaseError::from_msg(format!(
+                        "Error removing generated trend '{}' from trend store part '{}': {e}",
+                        self.from.name, self.trend_store_part_name
+                    ))
+                })?;
+
+            add_generated_trend_column(&mut tx, &self.trend_store_part_name, &self.to)
+                .await
+                .map_err(|e| {
+                    DatabaseError::from_msg(format!(
+                        "Error adding generated trend '{}' to trend store part '{}': {e}",
+                        self.to.name, self.trend_store_part_name
+                    ))
+                })?;
+        }
+
+        let update_query = concat!(
+            "UPDATE trend_directory.generated_table_trend gtt ",
+            "SET data_type = $3, expression = $4, description = $5, extra_data = $6 ",
+            "FROM trend_directory.trend_store_part tsp ",
+            "WHERE tsp.id = gtt.trend_store_part_id AND tsp.name = $1 AND gtt.name = $2"
+        );
+
+        tx.execute(
+            update_query,
+            &[
+                &self.trend_store_part_name,
+                &self.from.name,
+                &self.to.data_type,
+                &self.to.expression,
+                &self.to.description,
+                &self.to.extra_data,
+            ],
+        )
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error updating definition of generated trend '{}': {e}",
+                self.from.name
+            ))
+        })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(ModifiedGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ModifiedGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub from: GeneratedTrend,
+    pub to: GeneratedTrend,
+}
+
+impl Display for ModifiedGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Modified generated trend '{}' of trend store part '{}'",
+            self.from.name, self.trend_store_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for ModifiedGeneratedTrend {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(ModifyGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            from: self.to.clone(),
+            to: self.from.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemoveGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub generated_trend_name: String,
+}
+
+impl fmt::Display for RemoveGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RemoveGeneratedTrend(TrendStorePart({}), {})",
+            self.trend_store_part_name, self.generated_trend_name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RemoveGeneratedTrend {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let trend_store_part = load_trend_store_part(&tx, &self.trend_store_part_name)
+            .await
+            .map_err(|e| RuntimeError::from_msg(format!("{e}")))?;
+
+        let generated_trend = trend_store_part
+            .generated_trends
+            .into_iter()
+            .find(|generated_trend| generated_trend.name == self.generated_trend_name)
+            .ok_or_else(|| {
+                RuntimeError::from_msg(format!(
+                    "No generated trend '{}' in trend store part '{}'",
+                    self.generated_trend_name, self.trend_store_part_name
+                ))
+            })?;
+
+        drop_generated_trend_column(
+            &mut tx,
+            &self.trend_store_part_name,
+            &self.generated_trend_name,
+        )
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error removing generated trend '{}' from trend store part '{}': {e}",
+                self.generated_trend_name, self.trend_store_part_name
+            ))
+        })?;
+
+        let delete_query = concat!(
+            "DELETE FROM trend_directory.generated_table_trend gtt ",
+            "USING trend_directory.trend_store_part tsp ",
+            "WHERE tsp.id = gtt.trend_store_part_id AND tsp.name = $1 AND gtt.name = $2"
+        );
+
+        tx.execute(
+            delete_query,
+            &[&self.trend_store_part_name, &self.generated_trend_name],
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RemovedGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            generated_trend,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::TrendStorePart(
+            self.trend_store_part_name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemovedGeneratedTrend {
+    pub trend_store_part_name: String,
+    pub generated_trend: GeneratedTrend,
+}
+
+impl Display for RemovedGeneratedTrend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Removed generated trend '{}' from trend store part '{}'",
+            self.generated_trend.name, self.trend_store_part_name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RemovedGeneratedTrend {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(AddGeneratedTrend {
+            trend_store_part_name: self.trend_store_part_name.clone(),
+            generated_trend: self.generated_trend.clone(),
+        }))
+    }
+}
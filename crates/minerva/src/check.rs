@@ -0,0 +1,1394 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+use pg_query::NodeEnum;
+use pg_query::protobuf::{FunctionParameterMode, TypeName};
+use serde_json::Value;
+
+use crate::error::{Error, RuntimeError};
+use crate::instance::MinervaInstance;
+use crate::trend_materialization::{
+    TrendFunctionMaterialization, TrendMaterialization, TrendViewMaterialization,
+};
+use crate::trend_store::generated::validate_generated_trends;
+use crate::trend_store::{TREND_STORE_PART_COLUMNS, TrendStore, TrendStorePart};
+
+/// Suffixes of the tables and views in the `attribute_history` schema that are derived from an
+/// attribute store, e.g. `attribute_history."hub_node_curr_ptr"`
+const ATTRIBUTE_HISTORY_SUFFIXES: [&str; 5] = [
+    "_curr_ptr",
+    "_curr_selection",
+    "_changes",
+    "_run_length",
+    "_compacted",
+];
+
+/// Suffix of the staging table of a trend store part in the `trend` schema
+const TREND_STORE_PART_STAGING_SUFFIX: &str = "_staging";
+
+/// Columns that every materialization has to produce next to the trends
+const MATERIALIZATION_KEY_COLUMNS: [&str; 2] = ["entity_id", "timestamp"];
+
+const TARGET_FIELD: &[&str] = &["target_trend_store_part"];
+const SOURCE_TREND_STORE_PARTS_FIELD: &[&str] = &["source_trend_store_parts"];
+const VIEW_FIELD: &[&str] = &["view"];
+const FINGERPRINT_FUNCTION_FIELD: &[&str] = &["fingerprint_function"];
+const FUNCTION_SRC_FIELD: &[&str] = &["function", "src"];
+const FUNCTION_RETURN_TYPE_FIELD: &[&str] = &["function", "return_type"];
+const FUNCTION_LANGUAGE_FIELD: &[&str] = &["function", "language"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckSeverity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for CheckSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckSeverity::Error => write!(f, "error"),
+            CheckSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Position in the value of a field of a definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// Path of the field in the definition, e.g. `["function", "src"]`
+    pub field: &'static [&'static str],
+    /// 0-based line in the value of the field
+    pub line: usize,
+}
+
+impl SourceLocation {
+    fn field(field: &'static [&'static str]) -> SourceLocation {
+        SourceLocation { field, line: 0 }
+    }
+}
+
+/// Problem found in a definition, independent of the file it was loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefinitionIssue {
+    pub severity: CheckSeverity,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+}
+
+impl DefinitionIssue {
+    fn error(message: String, location: Option<SourceLocation>) -> DefinitionIssue {
+        DefinitionIssue {
+            severity: CheckSeverity::Error,
+            message,
+            location,
+        }
+    }
+
+    fn warning(message: String, location: Option<SourceLocation>) -> DefinitionIssue {
+        DefinitionIssue {
+            severity: CheckSeverity::Warning,
+            message,
+            location,
+        }
+    }
+}
+
+/// Query in the value of a field of a definition
+struct FieldQuery<'a> {
+    field: &'static [&'static str],
+    value: &'a str,
+    query: String,
+    /// Byte offset of the query in the value of the field
+    offset: usize,
+}
+
+impl<'a> FieldQuery<'a> {
+    /// Query that is the complete value of a field
+    fn field(field: &'static [&'static str], value: &'a str) -> FieldQuery<'a> {
+        FieldQuery {
+            field,
+            value,
+            query: value.to_string(),
+            offset: 0,
+        }
+    }
+
+    /// Location of a byte offset in the query as reported by the parser, which uses -1 for an
+    /// unknown offset
+    fn location(&self, query_offset: i32) -> SourceLocation {
+        let offset = self.offset + usize::try_from(query_offset).unwrap_or(0);
+
+        SourceLocation {
+            field: self.field,
+            line: line_index(self.value, offset),
+        }
+    }
+}
+
+/// 0-based number of the line in `text` that contains byte `offset`
+fn line_index(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset.min(text.len())]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+}
+
+/// Problem found in a definition file of an instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckIssue {
+    pub severity: CheckSeverity,
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for CheckIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(
+                f,
+                "{}:{}: {}: {}",
+                self.path.display(),
+                line,
+                self.severity,
+                self.message
+            ),
+            None => write!(
+                f,
+                "{}: {}: {}",
+                self.path.display(),
+                self.severity,
+                self.message
+            ),
+        }
+    }
+}
+
+/// Check all definitions of a Minerva instance directory without a database
+///
+/// The SQL of view and function materializations is parsed with the same parser as used by
+/// PostgreSQL and the referenced objects are checked against the instance definition.
+pub fn check_instance_dir(minerva_instance_root: &Path) -> Result<Vec<CheckIssue>, Error> {
+    let instance = MinervaInstance::load_from(minerva_instance_root).map_err(|e| {
+        Error::Runtime(RuntimeError::from_msg(format!(
+            "Could not load instance definition: {e}"
+        )))
+    })?;
+
+    let glob_path = format!(
+        "{}/materialization/*.yaml",
+        minerva_instance_root.to_string_lossy()
+    );
+
+    let mut issues: Vec<CheckIssue> = Vec::new();
+
+    let paths = glob(&glob_path).map_err(|e| {
+        Error::Runtime(RuntimeError::from_msg(format!(
+            "Could not read glob pattern '{glob_path}': {e}"
+        )))
+    })?;
+
+    for path in paths.flatten() {
+        let relative_path = path
+            .strip_prefix(minerva_instance_root)
+            .unwrap_or(&path)
+            .to_path_buf();
+
+        let definition = std::fs::read_to_string(&path).map_err(|e| {
+            Error::Runtime(RuntimeError::from_msg(format!(
+                "Could not read definition file '{}': {e}",
+                path.display()
+            )))
+        })?;
+
+        let materialization: TrendMaterialization = match serde_yaml::from_str(&definition) {
+            Ok(materialization) => materialization,
+            Err(e) => {
+                issues.push(CheckIssue {
+                    severity: CheckSeverity::Error,
+                    path: relative_path,
+                    line: e.location().map(|location| location.line()),
+                    message: format!("Could not deserialize materialization: {e}"),
+                });
+                continue;
+            }
+        };
+
+        for issue in check_trend_materialization_definition(&instance, &materialization) {
+            issues.push(CheckIssue {
+                severity: issue.severity,
+                path: relative_path.clone(),
+                line: issue.location.and_then(|location| {
+                    let path: Vec<YamlPathElement> = location
+                        .field
+                        .iter()
+                        .map(|key| YamlPathElement::Key(key))
+                        .collect();
+
+                    yaml_value_line(&definition, &path).map(|line| line + location.line)
+                }),
+                message: issue.message,
+            });
+        }
+    }
+
+    issues.extend(check_trend_store_dir(minerva_instance_root)?);
+
+    Ok(issues)
+}
+
+/// Check the expressions of the generated trends in the trend store definitions of an instance
+fn check_trend_store_dir(minerva_instance_root: &Path) -> Result<Vec<CheckIssue>, Error> {
+    let glob_path = format!("{}/trend/*.yaml", minerva_instance_root.to_string_lossy());
+
+    let mut issues: Vec<CheckIssue> = Vec::new();
+
+    let paths = glob(&glob_path).map_err(|e| {
+        Error::Runtime(RuntimeError::from_msg(format!(
+            "Could not read glob pattern '{glob_path}': {e}"
+        )))
+    })?;
+
+    for path in paths.flatten() {
+        let relative_path = path
+            .strip_prefix(minerva_instance_root)
+            .unwrap_or(&path)
+            .to_path_buf();
+
+        let definition = std::fs::read_to_string(&path).map_err(|e| {
+            Error::Runtime(RuntimeError::from_msg(format!(
+                "Could not read definition file '{}': {e}",
+                path.display()
+            )))
+        })?;
+
+        let trend_store: TrendStore = match serde_yaml::from_str(&definition) {
+            Ok(trend_store) => trend_store,
+            Err(e) => {
+                issues.push(CheckIssue {
+                    severity: CheckSeverity::Error,
+                    path: relative_path,
+                    line: e.location().map(|location| location.line()),
+                    message: format!("Could not deserialize trend store: {e}"),
+                });
+                continue;
+            }
+        };
+
+        for part in &trend_store.parts {
+            for (generated_trend, e) in validate_generated_trends(part) {
+                issues.push(CheckIssue {
+                    severity: CheckSeverity::Error,
+                    path: relative_path.clone(),
+                    line: yaml_value_line(
+                        &definition,
+                        &[
+                            YamlPathElement::Key("parts"),
+                            YamlPathElement::Item("name", &part.name),
+                            YamlPathElement::Key("generated_trends"),
+                            YamlPathElement::Item("name", &generated_trend.name),
+                            YamlPathElement::Key("expression"),
+                        ],
+                    ),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Element of the path to a value in a YAML document
+enum YamlPathElement<'a> {
+    /// Value of a key in a mapping
+    Key(&'a str),
+    /// Item of a sequence of mappings that has the key with the value
+    Item(&'a str, &'a str),
+}
+
+/// Return the 1-based number of the line on which the value at `path` starts in a YAML document
+///
+/// The definitions are deserialized without positions, so the value is located by following the
+/// indentation of the document. The value of a block scalar starts on the line after its key.
+fn yaml_value_line(text: &str, path: &[YamlPathElement]) -> Option<usize> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    // Lines of the node in which the next element of the path is looked up
+    let mut range = 0..lines.len();
+    let mut value_line = None;
+
+    for element in path {
+        let indent = range
+            .clone()
+            .filter_map(|index| yaml_line_content(lines[index]))
+            .map(|(indent, _)| indent)
+            .min()?;
+
+        let is_entry = |index: &usize, key: &str, value: Option<&str>| {
+            yaml_line_content(lines[*index]).is_some_and(|(content_indent, content)| {
+                content_indent == indent
+                    && yaml_entry_value(content, key).is_some_and(|entry_value| match value {
+                        Some(value) => entry_value.trim_matches(['"', '\'']) == value,
+                        None => true,
+                    })
+            })
+        };
+
+        match element {
+            YamlPathElement::Key(key) => {
+                let key_line = range.clone().find(|index| is_entry(index, key, None))?;
+                let (_, content) = yaml_line_content(lines[key_line])?;
+                let entry_value = yaml_entry_value(content, key)?;
+
+                value_line = Some(
+                    if entry_value.is_empty() || entry_value.starts_with(['|', '>']) {
+                        key_line + 1
+                    } else {
+                        key_line
+                    },
+                );
+                range = key_line + 1..yaml_node_end(&lines, key_line, indent, range.end);
+            }
+            YamlPathElement::Item(key, value) => {
+                let entry_line = range
+                    .clone()
+                    .find(|index| is_entry(index, key, Some(value)))?;
+
+                // The item starts at the line with the dash that introduces its entries
+                let item_line = (range.start..=entry_line).rev().find(|index| {
+                    indent >= 2 && lines[*index].get(indent - 2..indent) == Some("- ")
+                })?;
+
+                value_line = Some(item_line);
+                range = item_line..yaml_item_end(&lines, item_line, indent - 2, range.end);
+            }
+        }
+    }
+
+    value_line.map(|line| line + 1)
+}
+
+/// Indentation and content of a line, where the dashes of sequence items count as indentation,
+/// or None for blank and comment lines
+fn yaml_line_content(line: &str) -> Option<(usize, &str)> {
+    let mut content = line.trim_start_matches(' ');
+
+    while let Some(rest) = content.strip_prefix("- ") {
+        content = rest.trim_start_matches(' ');
+    }
+
+    if content.is_empty() || content.starts_with('#') {
+        None
+    } else {
+        Some((line.len() - content.len(), content))
+    }
+}
+
+/// Value following `key:` in the content of a line, or None if the line has no entry for the key
+fn yaml_entry_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    let rest = content.strip_prefix(key)?.strip_prefix(':')?;
+
+    if rest.is_empty() || rest.starts_with(' ') {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+/// Return the index of the first line after the entry on `line` with indentation `indent` that is
+/// not part of its value. A sequence can be indented as much as the key it is the value of.
+fn yaml_node_end(lines: &[&str], line: usize, indent: usize, end: usize) -> usize {
+    (line + 1..end)
+        .find(|index| {
+            let text = lines[*index];
+            let line_indent = text.len() - text.trim_start_matches(' ').len();
+
+            yaml_line_content(text).is_some()
+                && (line_indent < indent
+                    || (line_indent == indent && !text[line_indent..].starts_with("- ")))
+        })
+        .unwrap_or(end)
+}
+
+/// Return the index of the first line after the sequence item with its dash on `line` at
+/// indentation `indent` that is not part of the item
+fn yaml_item_end(lines: &[&str], line: usize, indent: usize, end: usize) -> usize {
+    (line + 1..end)
+        .find(|index| {
+            let text = lines[*index];
+
+            yaml_line_content(text).is_some()
+                && text.len() - text.trim_start_matches(' ').len() <= indent
+        })
+        .unwrap_or(end)
+}
+
+/// Check the SQL of a trend materialization against the definition of an instance
+pub fn check_trend_materialization_definition(
+    instance: &MinervaInstance,
+    materialization: &TrendMaterialization,
+) -> Vec<DefinitionIssue> {
+    let checker = DefinitionChecker::new(instance);
+
+    match materialization {
+        TrendMaterialization::View(m) => checker.check_view_materialization(m),
+        TrendMaterialization::Function(m) => checker.check_function_materialization(m),
+        TrendMaterialization::Declarative(m) => match m.validate() {
+            Ok(()) => checker.check_function_materialization(&m.to_function_materialization()),
+            Err(e) => vec![DefinitionIssue::error(
+                e.msg,
+                Some(SourceLocation::field(SOURCE_TREND_STORE_PARTS_FIELD)),
+            )],
+        },
+    }
+}
+
+struct DefinitionChecker<'a> {
+    trend_store_parts: HashMap<&'a str, &'a TrendStorePart>,
+    relations: HashSet<&'a str>,
+    history_relations: HashSet<&'a str>,
+    attribute_stores: HashSet<String>,
+}
+
+impl<'a> DefinitionChecker<'a> {
+    fn new(instance: &'a MinervaInstance) -> DefinitionChecker<'a> {
+        let trend_store_parts = instance
+            .trend_stores
+            .iter()
+            .flat_map(|trend_store| trend_store.parts.iter())
+            .map(|part| (part.name.as_str(), part))
+            .collect();
+
+        let relations = instance
+            .relations
+            .iter()
+            .map(|relation| relation.name.as_str())
+            .collect();
+
+        let history_relations = instance
+            .relations
+            .iter()
+            .filter(|relation| relation.history)
+            .map(|relation| relation.name.as_str())
+            .collect();
+
+        let attribute_stores = instance
+            .attribute_stores
+            .iter()
+            .map(|attribute_store| {
+                format!(
+                    "{}_{}",
+                    attribute_store.data_source, attribute_store.entity_type
+                )
+            })
+            .collect();
+
+        DefinitionChecker {
+            trend_store_parts,
+            relations,
+            history_relations,
+            attribute_stores,
+        }
+    }
+
+    fn check_view_materialization(
+        &self,
+        materialization: &TrendViewMaterialization,
+    ) -> Vec<DefinitionIssue> {
+        let mut issues = Vec::new();
+
+        let target_part = self.check_target(&materialization.target_trend_store_part, &mut issues);
+
+        let view = FieldQuery::field(VIEW_FIELD, &materialization.view);
+
+        match pg_query::parse(&view.query) {
+            Err(e) => issues.push(parse_error_issue("view", &e, &view)),
+            Ok(parse_result) => {
+                self.check_references(&view, &parse_result, &mut issues);
+
+                if let Some(target_part) = target_part
+                    && let Some(columns) = select_output_columns(&parse_result)
+                {
+                    check_output_columns(target_part, &columns, &view, &mut issues);
+                }
+            }
+        }
+
+        self.check_fingerprint_function(&materialization.fingerprint_function, &mut issues);
+
+        issues
+    }
+
+    fn check_function_materialization(
+        &self,
+        materialization: &TrendFunctionMaterialization,
+    ) -> Vec<DefinitionIssue> {
+        let mut issues = Vec::new();
+
+        let target_part = self.check_target(&materialization.target_trend_store_part, &mut issues);
+
+        let function_definition = materialization
+            .function
+            .function_definition(&materialization.target_trend_store_part);
+
+        let src = &materialization.function.src;
+
+        let queries = match materialization.function.language.to_lowercase().as_str() {
+            "plpgsql" => match pg_query::parse_plpgsql(&function_definition) {
+                Err(e) => {
+                    issues.push(parse_error_issue(
+                        "function",
+                        &e,
+                        &FieldQuery::field(FUNCTION_SRC_FIELD, src),
+                    ));
+                    vec![]
+                }
+                Ok(json) => {
+                    let mut queries = Vec::new();
+                    collect_return_queries(&json, src, &mut queries, &mut issues);
+                    queries
+                }
+            },
+            "sql" => vec![FieldQuery::field(FUNCTION_SRC_FIELD, src)],
+            language => {
+                issues.push(DefinitionIssue::warning(
+                    format!("Function language '{language}' can not be checked"),
+                    Some(SourceLocation::field(FUNCTION_LANGUAGE_FIELD)),
+                ));
+                vec![]
+            }
+        };
+
+        for query in queries {
+            match pg_query::parse(&query.query) {
+                Err(e) => issues.push(parse_error_issue("function", &e, &query)),
+                Ok(parse_result) => self.check_references(&query, &parse_result, &mut issues),
+            }
+        }
+
+        if let Some(target_part) = target_part {
+            let return_type = FieldQuery::field(
+                FUNCTION_RETURN_TYPE_FIELD,
+                &materialization.function.return_type,
+            );
+
+            match return_table_columns(&return_type.query) {
+                Err(e) => issues.push(parse_error_issue("function return type", &e, &return_type)),
+                Ok(columns) => {
+                    check_output_columns(target_part, &columns, &return_type, &mut issues)
+                }
+            }
+        }
+
+        self.check_fingerprint_function(&materialization.fingerprint_function, &mut issues);
+
+        issues
+    }
+
+    fn check_target(
+        &self,
+        target_trend_store_part: &str,
+        issues: &mut Vec<DefinitionIssue>,
+    ) -> Option<&'a TrendStorePart> {
+        let target_part = self.trend_store_parts.get(target_trend_store_part).copied();
+
+        if target_part.is_none() {
+            issues.push(DefinitionIssue::error(
+                format!("Target trend store part '{target_trend_store_part}' is not defined"),
+                Some(SourceLocation::field(TARGET_FIELD)),
+            ));
+        }
+
+        target_part
+    }
+
+    fn check_fingerprint_function(&self, src: &str, issues: &mut Vec<DefinitionIssue>) {
+        let query = FieldQuery::field(FINGERPRINT_FUNCTION_FIELD, src);
+
+        match pg_query::parse(&query.query) {
+            Err(e) => issues.push(parse_error_issue("fingerprint function", &e, &query)),
+            Ok(parse_result) => self.check_references(&query, &parse_result, issues),
+        }
+    }
+
+    /// Check that all referenced tables in known schemas are defined and that qualified
+    /// references to trend store part columns refer to existing trends.
+    fn check_references(
+        &self,
+        query: &FieldQuery,
+        parse_result: &pg_query::ParseResult,
+        issues: &mut Vec<DefinitionIssue>,
+    ) {
+        let mut reported_tables: HashSet<(&str, &str)> = HashSet::new();
+
+        for (node, _depth, _context, _has_filter_columns) in parse_result.protobuf.nodes() {
+            let pg_query::NodeRef::RangeVar(range_var) = node else {
+                continue;
+            };
+
+            let (schema, name) = (range_var.schemaname.as_str(), range_var.relname.as_str());
+
+            if schema.is_empty() {
+                continue;
+            }
+
+            let defined = match schema {
+                "trend" => {
+                    self.trend_store_parts.contains_key(name)
+                        || name
+                            .strip_suffix(TREND_STORE_PART_STAGING_SUFFIX)
+                            .is_some_and(|part| self.trend_store_parts.contains_key(part))
+                }
+                "relation" => self.relations.contains(name) || name.ends_with("->entity_set"),
+                "relation_history" => self.history_relations.contains(name),
+                "attribute" => self.attribute_stores.contains(name),
+                "attribute_history" => {
+                    self.attribute_stores.contains(name)
+                        || ATTRIBUTE_HISTORY_SUFFIXES.iter().any(|suffix| {
+                            name.strip_suffix(suffix)
+                                .is_some_and(|store| self.attribute_stores.contains(store))
+                        })
+                }
+                _ => true,
+            };
+
+            if !defined && reported_tables.insert((schema, name)) {
+                issues.push(DefinitionIssue::error(
+                    format!("Referenced table '{schema}.\"{name}\"' is not defined"),
+                    Some(query.location(range_var.location)),
+                ));
+            }
+        }
+
+        for (node, _depth, _context, _has_filter_columns) in parse_result.protobuf.nodes() {
+            let pg_query::NodeRef::ColumnRef(column_ref) = node else {
+                continue;
+            };
+
+            let fields: Vec<&str> = column_ref
+                .fields
+                .iter()
+                .filter_map(|field| match &field.node {
+                    Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+                    _ => None,
+                })
+                .collect();
+
+            let (table, column) = match fields.as_slice() {
+                [qualifier, column] => match parse_result.aliases.get(*qualifier) {
+                    Some(table) => (table.clone(), *column),
+                    None => continue,
+                },
+                [schema, table, column] => (format!("{schema}.{table}"), *column),
+                _ => continue,
+            };
+
+            let Some(part_name) = table.strip_prefix("trend.") else {
+                continue;
+            };
+
+            let Some(part) = self.trend_store_parts.get(part_name) else {
+                continue;
+            };
+
+            if !trend_store_part_has_column(part, column) {
+                issues.push(DefinitionIssue::error(
+                    format!("Trend store part '{part_name}' has no trend '{column}'"),
+                    Some(query.location(column_ref.location)),
+                ));
+            }
+        }
+    }
+}
+
+fn trend_store_part_has_column(part: &TrendStorePart, column: &str) -> bool {
+    TREND_STORE_PART_COLUMNS.contains(&column)
+        || (part.has_alias_column && column == "name")
+        || part.trends.iter().any(|trend| trend.name == column)
+        || part
+            .generated_trends
+            .iter()
+            .any(|generated_trend| generated_trend.name == column)
+}
+
+fn parse_error_issue(what: &str, error: &pg_query::Error, query: &FieldQuery) -> DefinitionIssue {
+    // The parser reports errors like 'syntax error at or near "FROM"' without a position, so the
+    // first occurrence of the quoted token in the query is the best available hint.
+    let offset = match error {
+        pg_query::Error::Parse(msg) => msg
+            .split('"')
+            .nth(1)
+            .and_then(|token| query.query.find(token))
+            .and_then(|offset| i32::try_from(offset).ok()),
+        _ => None,
+    };
+
+    DefinitionIssue::error(
+        format!("Invalid SQL in {what}: {error}"),
+        Some(query.location(offset.unwrap_or(-1))),
+    )
+}
+
+/// Collect the queries of all `RETURN QUERY` statements in a parsed plpgsql function with source
+/// `src`
+fn collect_return_queries<'a>(
+    json: &Value,
+    src: &'a str,
+    queries: &mut Vec<FieldQuery<'a>>,
+    issues: &mut Vec<DefinitionIssue>,
+) {
+    match json {
+        Value::Object(map) => {
+            if let Some(return_query) = map.get("PLpgSQL_stmt_return_query") {
+                let statement_offset = return_query["lineno"]
+                    .as_u64()
+                    .map_or(0, |lineno| plpgsql_line_offset(src, lineno));
+
+                // The parser reports only the line of the statement, so the query is looked up in
+                // the source from there
+                let field_query = |query: String| {
+                    let offset = src[statement_offset..]
+                        .find(&query)
+                        .map_or(statement_offset, |offset| statement_offset + offset);
+
+                    FieldQuery {
+                        field: FUNCTION_SRC_FIELD,
+                        value: src,
+                        query,
+                        offset,
+                    }
+                };
+
+                if let Some(query) = return_query["query"]["PLpgSQL_expr"]["query"].as_str() {
+                    queries.push(field_query(query.to_string()));
+                }
+
+                if let Some(dynquery) = return_query["dynquery"]["PLpgSQL_expr"]["query"].as_str() {
+                    match unquote_string_literal(dynquery) {
+                        Some(query) => queries.push(field_query(query)),
+                        None => issues.push(DefinitionIssue::warning(
+                            "Dynamically constructed query can not be checked".to_string(),
+                            Some(SourceLocation {
+                                field: FUNCTION_SRC_FIELD,
+                                line: line_index(src, statement_offset),
+                            }),
+                        )),
+                    }
+                }
+            }
+
+            for value in map.values() {
+                collect_return_queries(value, src, queries, issues);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                collect_return_queries(value, src, queries, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Return the byte offset in the source of a plpgsql function of the line that the parser reports
+/// as `lineno`. The function definition that is parsed starts with a line with the header, up to
+/// the dollar quote, followed by the trimmed source.
+fn plpgsql_line_offset(src: &str, lineno: u64) -> usize {
+    let leading_lines = src[..src.len() - src.trim_start().len()]
+        .matches('\n')
+        .count();
+    let line = leading_lines + usize::try_from(lineno.saturating_sub(2)).unwrap_or(0);
+
+    src.split_inclusive('\n').take(line).map(str::len).sum()
+}
+
+/// Return the contents of a single dollar-quoted or single-quoted string literal
+fn unquote_string_literal(literal: &str) -> Option<String> {
+    let literal = literal.trim();
+
+    if let Some(rest) = literal.strip_prefix('$') {
+        let tag_end = rest.find('$')? + 2;
+        let tag = &literal[..tag_end];
+
+        if literal.len() >= 2 * tag_end && literal.ends_with(tag) {
+            let inner = &literal[tag_end..literal.len() - tag_end];
+
+            if !inner.contains(tag) {
+                return Some(inner.to_string());
+            }
+        }
+
+        None
+    } else if literal.len() >= 2 && literal.starts_with('\'') && literal.ends_with('\'') {
+        let inner = &literal[1..literal.len() - 1];
+
+        if inner.replace("''", "").contains('\'') {
+            None
+        } else {
+            Some(inner.replace("''", "'"))
+        }
+    } else {
+        None
+    }
+}
+
+/// Output column of a query or function with the data type if it is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputColumn {
+    name: String,
+    data_type: Option<String>,
+    /// Byte offset of the column in the query or return type, or -1 if unknown
+    location: i32,
+}
+
+/// Determine the output columns of a select statement
+///
+/// Returns None if the columns can not be determined statically, e.g. because of a `*`.
+fn select_output_columns(parse_result: &pg_query::ParseResult) -> Option<Vec<OutputColumn>> {
+    let stmt = parse_result.protobuf.stmts.first()?.stmt.as_ref()?;
+
+    let Some(NodeEnum::SelectStmt(select_stmt)) = &stmt.node else {
+        return None;
+    };
+
+    // For UNION, INTERSECT and EXCEPT, the first query determines the column names
+    let mut select_stmt = select_stmt.as_ref();
+
+    while let Some(larg) = &select_stmt.larg {
+        select_stmt = larg.as_ref();
+    }
+
+    let mut columns = Vec::new();
+
+    for target in &select_stmt.target_list {
+        let Some(NodeEnum::ResTarget(res_target)) = &target.node else {
+            return None;
+        };
+
+        let value = res_target.val.as_ref().and_then(|val| val.node.as_ref());
+
+        let name = if res_target.name.is_empty() {
+            match value {
+                Some(NodeEnum::ColumnRef(column_ref)) => match &column_ref.fields.last()?.node {
+                    Some(NodeEnum::String(s)) => s.sval.clone(),
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        } else {
+            res_target.name.clone()
+        };
+
+        let data_type = match value {
+            Some(NodeEnum::TypeCast(type_cast)) => {
+                type_cast.type_name.as_ref().map(type_name_to_string)
+            }
+            _ => None,
+        };
+
+        columns.push(OutputColumn {
+            name,
+            data_type,
+            location: res_target.location,
+        });
+    }
+
+    Some(columns)
+}
+
+/// Determine the columns of a `TABLE (...)` function return type
+fn return_table_columns(return_type: &str) -> Result<Vec<OutputColumn>, pg_query::Error> {
+    const PREFIX: &str = "CREATE FUNCTION check_return_type() RETURNS ";
+
+    let sql = format!("{PREFIX}{return_type} AS $$ $$ LANGUAGE sql");
+
+    let parse_result = pg_query::parse(&sql)?;
+
+    let mut columns = Vec::new();
+
+    for stmt in &parse_result.protobuf.stmts {
+        let Some(NodeEnum::CreateFunctionStmt(create_function)) =
+            stmt.stmt.as_ref().and_then(|s| s.node.as_ref())
+        else {
+            continue;
+        };
+
+        for parameter in &create_function.parameters {
+            if let Some(NodeEnum::FunctionParameter(parameter)) = &parameter.node
+                && parameter.mode == FunctionParameterMode::FuncParamTable as i32
+            {
+                // Parameters have no location, so the location of the type is used
+                let location = parameter.arg_type.as_ref().map_or(-1, |arg_type| {
+                    arg_type.location - i32::try_from(PREFIX.len()).unwrap_or(0)
+                });
+
+                columns.push(OutputColumn {
+                    name: parameter.name.clone(),
+                    data_type: parameter.arg_type.as_ref().map(type_name_to_string),
+                    location,
+                });
+            }
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Render a parsed type name in the form used by trend definitions, e.g. 'bigint' for 'int8'
+fn type_name_to_string(type_name: &TypeName) -> String {
+    let name = type_name
+        .names
+        .last()
+        .and_then(|name| match &name.node {
+            Some(NodeEnum::String(s)) => Some(s.sval.as_str()),
+            _ => None,
+        })
+        .unwrap_or("");
+
+    let name = match name {
+        "int2" => "smallint",
+        "int4" => "integer",
+        "int8" => "bigint",
+        "float4" => "real",
+        "float8" => "double precision",
+        "bool" => "boolean",
+        "timestamptz" => "timestamp with time zone",
+        "varchar" => "character varying",
+        name => name,
+    };
+
+    if type_name.array_bounds.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}[]")
+    }
+}
+
+/// Normalize a data type as written in a definition, so that e.g. 'int8' and 'bigint' are equal
+fn normalize_data_type(data_type: &str) -> String {
+    let sql = format!("SELECT NULL::{data_type}");
+
+    pg_query::parse(&sql)
+        .ok()
+        .and_then(|parse_result| {
+            parse_result
+                .protobuf
+                .nodes()
+                .into_iter()
+                .find_map(|(node, _, _, _)| match node {
+                    pg_query::NodeRef::TypeCast(type_cast) => {
+                        type_cast.type_name.as_ref().map(type_name_to_string)
+                    }
+                    _ => None,
+                })
+        })
+        .unwrap_or_else(|| data_type.to_string())
+}
+
+/// Return true if every value of data type `from` can be stored without loss as data type `to`
+fn is_widening_conversion(from: &str, to: &str) -> bool {
+    const NUMERIC_TYPES: [&str; 4] = ["smallint", "integer", "bigint", "numeric"];
+    const FLOAT_TYPES: [&str; 2] = ["real", "double precision"];
+
+    let widens = |types: &[&str]| match (
+        types.iter().position(|t| *t == from),
+        types.iter().position(|t| *t == to),
+    ) {
+        (Some(from_index), Some(to_index)) => from_index < to_index,
+        _ => false,
+    };
+
+    widens(&NUMERIC_TYPES) || widens(&FLOAT_TYPES)
+}
+
+fn check_output_columns(
+    target_part: &TrendStorePart,
+    columns: &[OutputColumn],
+    query: &FieldQuery,
+    issues: &mut Vec<DefinitionIssue>,
+) {
+    for key_column in MATERIALIZATION_KEY_COLUMNS {
+        if !columns.iter().any(|column| column.name == key_column) {
+            issues.push(DefinitionIssue::error(
+                format!("Output has no '{key_column}' column"),
+                Some(query.location(-1)),
+            ));
+        }
+    }
+
+    for column in columns {
+        if MATERIALIZATION_KEY_COLUMNS.contains(&column.name.as_str()) {
+            continue;
+        }
+
+        match target_part
+            .trends
+            .iter()
+            .find(|trend| trend.name == column.name)
+        {
+            None => issues.push(DefinitionIssue::error(
+                format!(
+                    "Output column '{}' is not a trend of target trend store part '{}'",
+                    column.name, target_part.name
+                ),
+                Some(query.location(column.location)),
+            )),
+            Some(trend) => {
+                if let Some(data_type) = &column.data_type {
+                    let trend_data_type = normalize_data_type(&trend.data_type.to_string());
+
+                    let output_data_type = normalize_data_type(data_type);
+
+                    if output_data_type != trend_data_type {
+                        let message = format!(
+                            "Output column '{}' has type '{}', but trend has type '{}'",
+                            column.name, output_data_type, trend_data_type
+                        );
+
+                        // Widening conversions are applied implicitly when storing the data
+                        let location = Some(query.location(column.location));
+
+                        if is_widening_conversion(&output_data_type, &trend_data_type) {
+                            issues.push(DefinitionIssue::warning(message, location));
+                        } else {
+                            issues.push(DefinitionIssue::error(message, location));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for trend in &target_part.trends {
+        if !columns.iter().any(|column| column.name == trend.name) {
+            issues.push(DefinitionIssue::warning(
+                format!(
+                    "Trend '{}' of the target trend store part is not produced",
+                    trend.name
+                ),
+                Some(query.location(-1)),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREND_STORE_DEFINITION: &str = r#"
+data_source: hub
+entity_type: node
+granularity: 15m
+partition_size: 1d
+parts:
+- name: hub_node_main_15m
+  trends:
+  - name: power_kwh
+    data_type: numeric
+- name: hub-kpi_node_main_15m
+  trends:
+  - name: power_mwh
+    data_type: numeric
+"#;
+
+    const ATTRIBUTE_STORE_DEFINITION: &str = r#"
+data_source: hub
+entity_type: node
+attributes:
+- name: equipment_type
+  data_type: text
+  unit: null
+  description: null
+  extra_data: null
+"#;
+
+    /// Run `check_instance_dir` on an instance directory with a trend store, an attribute store
+    /// and the materialization definition
+    fn check_materialization(name: &str, materialization: &str) -> Vec<CheckIssue> {
+        let root =
+            std::env::temp_dir().join(format!("minerva-check-{name}-{}", std::process::id()));
+
+        for (path, definition) in [
+            ("trend/hub_node_15m.yaml", TREND_STORE_DEFINITION),
+            ("attribute/hub_node.yaml", ATTRIBUTE_STORE_DEFINITION),
+            (
+                "materialization/hub-kpi_node_main_15m.yaml",
+                materialization,
+            ),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, definition).unwrap();
+        }
+
+        let issues = check_instance_dir(&root).unwrap();
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        issues
+    }
+
+    #[test]
+    fn test_check_instance_dir_missing_part_and_trend() {
+        let issues = check_materialization(
+            "missing",
+            r#"
+target_trend_store_part: hub-kpi_node_main_15m
+enabled: true
+processing_delay: 30m
+stability_delay: 5m
+reprocessing_period: 3 days
+sources:
+- trend_store_part: hub_node_main_15m
+  mapping_function: trend.mapping_id
+view: |-
+  SELECT t.timestamp, t.entity_id, t.power_w / 1000 AS power_mwh
+  FROM trend."hub_node_main_15m" t
+  JOIN trend."hub_node_extra_15m" x ON x.entity_id = t.entity_id
+fingerprint_function: |
+  SELECT modified.last, format('{"hub_node_main_15m": "%s"}', modified.last)::jsonb
+  FROM trend_directory.modified
+  JOIN trend_directory.trend_store_part ttsp ON ttsp.id = modified.trend_store_part_id
+  WHERE ttsp::name = 'hub_node_main_15m' AND modified.timestamp = $1;
+"#,
+        );
+
+        let messages: Vec<&str> = issues
+            .iter()
+            .filter(|issue| issue.severity == CheckSeverity::Error)
+            .map(|issue| issue.message.as_str())
+            .collect();
+
+        assert!(
+            messages.contains(&"Referenced table 'trend.\"hub_node_extra_15m\"' is not defined")
+        );
+        assert!(messages.contains(&"Trend store part 'hub_node_main_15m' has no trend 'power_w'"));
+
+        let line = |message: &str| {
+            issues
+                .iter()
+                .find(|issue| issue.message == message)
+                .and_then(|issue| issue.line)
+        };
+
+        assert_eq!(
+            line("Referenced table 'trend.\"hub_node_extra_15m\"' is not defined"),
+            Some(13)
+        );
+        assert_eq!(
+            line("Trend store part 'hub_node_main_15m' has no trend 'power_w'"),
+            Some(11)
+        );
+    }
+
+    #[test]
+    fn test_check_instance_dir_function_lines() {
+        let issues = check_materialization(
+            "function-lines",
+            r#"
+target_trend_store_part: hub-kpi_node_main_15m
+enabled: true
+processing_delay: 30m
+stability_delay: 5m
+reprocessing_period: 3 days
+sources:
+- trend_store_part: hub_node_main_15m
+  mapping_function: trend.mapping_id
+function:
+  return_type: |
+    TABLE (
+      "entity_id" integer,
+      "timestamp" timestamp with time zone,
+      "power_mwh" numeric,
+      "power_w" numeric
+    )
+  src: |
+    BEGIN
+    RETURN QUERY EXECUTE $query$
+      SELECT t.entity_id, t.timestamp,
+        t.power_kwh / 1000 AS power_mwh,
+        t.power_w
+      FROM trend."hub_node_main_15m" t
+    $query$;
+    END;
+  language: plpgsql
+fingerprint_function: |
+  SELECT modified.last, format('{"hub_node_main_15m": "%s"}', modified.last)::jsonb
+  FROM trend_directory.modified
+  JOIN trend_directory.trend_store_part ttsp ON ttsp.id = modified.trend_store_part_id
+  WHERE ttsp::name = 'hub_node_main_15m' AND modified.timestamp = $1;
+"#,
+        );
+
+        let lines: Vec<(&str, Option<usize>)> = issues
+            .iter()
+            .map(|issue| (issue.message.as_str(), issue.line))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                (
+                    "Trend store part 'hub_node_main_15m' has no trend 'power_w'",
+                    Some(23)
+                ),
+                (
+                    "Output column 'power_w' is not a trend of target trend store part 'hub-kpi_node_main_15m'",
+                    Some(16)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_yaml_value_line() {
+        let definition = concat!(
+            "data_source: hub\n",
+            "parts:\n",
+            "- name: hub_node_main_15m\n",
+            "  generated_trends:\n",
+            "  - name: ratio\n",
+            "    expression: a / b\n",
+            "- name: hub_node_kpi_15m\n",
+            "  generated_trends:\n",
+            "  - name: total\n",
+            "    data_type: numeric\n",
+            "    expression: |\n",
+            "      a + b\n",
+        );
+
+        let expression_line = |part: &str, trend: &str| {
+            yaml_value_line(
+                definition,
+                &[
+                    YamlPathElement::Key("parts"),
+                    YamlPathElement::Item("name", part),
+                    YamlPathElement::Key("generated_trends"),
+                    YamlPathElement::Item("name", trend),
+                    YamlPathElement::Key("expression"),
+                ],
+            )
+        };
+
+        assert_eq!(expression_line("hub_node_main_15m", "ratio"), Some(6));
+        assert_eq!(expression_line("hub_node_kpi_15m", "total"), Some(12));
+        assert_eq!(expression_line("hub_node_kpi_15m", "ratio"), None);
+        assert_eq!(
+            yaml_value_line(definition, &[YamlPathElement::Key("data_source")]),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_check_instance_dir_curr_ptr_reference() {
+        let issues = check_materialization(
+            "curr-ptr",
+            r#"
+target_trend_store_part: hub-kpi_node_main_15m
+enabled: true
+processing_delay: 30m
+stability_delay: 5m
+reprocessing_period: 3 days
+sources:
+- trend_store_part: hub_node_main_15m
+  mapping_function: trend.mapping_id
+view: |-
+  SELECT t.timestamp, t.entity_id, t.power_kwh / 1000 AS power_mwh
+  FROM trend."hub_node_main_15m" t
+  JOIN attribute_history."hub_node_curr_ptr" p ON p.entity_id = t.entity_id
+  JOIN attribute_history."hub_node" a ON a.id = p.id
+  WHERE a.equipment_type = 'meter'
+fingerprint_function: |
+  SELECT modified.last, format('{"hub_node_main_15m": "%s"}', modified.last)::jsonb
+  FROM trend_directory.modified
+  JOIN trend_directory.trend_store_part ttsp ON ttsp.id = modified.trend_store_part_id
+  WHERE ttsp::name = 'hub_node_main_15m' AND modified.timestamp = $1;
+"#,
+        );
+
+        assert_eq!(issues, vec![]);
+    }
+
+    #[test]
+    fn test_unquote_string_literal() {
+        assert_eq!(
+            unquote_string_literal("$query$\nSELECT 1\n$query$"),
+            Some("\nSELECT 1\n".to_string())
+        );
+        assert_eq!(
+            unquote_string_literal("'SELECT ''a'''"),
+            Some("SELECT 'a'".to_string())
+        );
+        assert_eq!(unquote_string_literal("'SELECT ' || x"), None);
+    }
+
+    #[test]
+    fn test_is_widening_conversion() {
+        assert!(is_widening_conversion("smallint", "integer"));
+        assert!(is_widening_conversion("integer", "numeric"));
+        assert!(!is_widening_conversion("bigint", "integer"));
+        assert!(!is_widening_conversion("numeric", "double precision"));
+    }
+
+    #[test]
+    fn test_return_table_columns() {
+        let columns = return_table_columns(
+            "TABLE (\"entity_id\" integer, \"timestamp\" timestamp with time zone, \"bytes\" int8)",
+        )
+        .unwrap();
+
+        assert_eq!(
+            columns,
+            vec![
+                OutputColumn {
+                    name: "entity_id".to_string(),
+                    data_type: Some("integer".to_string()),
+                    location: 19,
+                },
+                OutputColumn {
+                    name: "timestamp".to_string(),
+                    data_type: Some("timestamp with time zone".to_string()),
+                    location: 40,
+                },
+                OutputColumn {
+                    name: "bytes".to_string(),
+                    data_type: Some("bigint".to_string()),
+                    location: 74,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_output_columns() {
+        let parse_result = pg_query::parse(
+            "SELECT t.entity_id, t.timestamp, sum(t.x)::numeric AS y FROM trend.\"a\" t",
+        )
+        .unwrap();
+
+        assert_eq!(
+            select_output_columns(&parse_result),
+            Some(vec![
+                OutputColumn {
+                    name: "entity_id".to_string(),
+                    data_type: None,
+                    location: 7,
+                },
+                OutputColumn {
+                    name: "timestamp".to_string(),
+                    data_type: None,
+                    location: 20,
+                },
+                OutputColumn {
+                    name: "y".to_string(),
+                    data_type: Some("numeric".to_string()),
+                    location: 33,
+                },
+            ])
+        );
+    }
+}
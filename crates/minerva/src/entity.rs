@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::future::Future;
@@ -9,6 +10,10 @@ use thiserror::Error;
 use tokio_postgres::GenericClient;
 
 use super::entity_type::EntityTypeName;
+use super::error::{ConfigurationError, DatabaseError, Error, RuntimeError};
+use super::relation::{
+    RelationFilter, load_related_entities, load_relation_names, relation_entity_types,
+};
 
 type EntityName = String;
 
@@ -46,6 +51,10 @@ pub enum EntityMappingError {
     EntityInsertError,
     #[error("Could not map entity")]
     UnmappedEntityError,
+    #[error("Unknown entity '{0}'")]
+    UnknownEntityError(String),
+    #[error("Unknown alias type '{0}'")]
+    UnknownAliasTypeError(String),
     #[error("Value unexpectedly not found in cache")]
     CacheError,
 }
@@ -87,9 +96,71 @@ pub trait EntityMapping {
         entity_type: &EntityTypeName,
         names: &[EntityName],
     ) -> impl Future<Output = Result<Vec<LargeEntity>, EntityMappingError>> + Send;
+
+    /// Map aliases of an alias type to the names of the entities, e.g. for data that only
+    /// identifies entities by their inventory key. Entities are never created from an alias, so
+    /// unknown aliases are rejected.
+    fn aliases_to_names<T: GenericClient + Sync>(
+        &self,
+        client: &T,
+        entity_type: &EntityTypeName,
+        alias_type: &str,
+        aliases: &[String],
+    ) -> impl Future<Output = Result<Vec<EntityName>, EntityMappingError>> + Send;
+}
+
+/// Name of the table in the `alias` schema for an alias type, preferring an alias type of the
+/// entity type over a shared alias type with the same name.
+pub(crate) async fn alias_table_name<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    alias_type: &str,
+) -> Result<String, EntityMappingError> {
+    let query = concat!(
+        "SELECT alias_directory.alias_table_name(at)::text FROM alias_directory.alias_type at ",
+        "LEFT JOIN directory.entity_type et ON et.id = at.entity_type_id ",
+        "WHERE at.name = $2 AND (et.name = $1 OR at.entity_type_id IS NULL) ",
+        "ORDER BY at.entity_type_id NULLS LAST LIMIT 1"
+    );
+
+    client
+        .query_opt(query, &[&entity_type, &alias_type])
+        .await
+        .map_err(EntityMappingError::DatabaseError)?
+        .map(|row| row.get(0))
+        .ok_or_else(|| EntityMappingError::UnknownAliasTypeError(alias_type.to_string()))
 }
 
-pub struct DbEntityMapping {}
+/// Look up the entity names for aliases, returning only the aliases that are found. When
+/// multiple entities share an alias, the one with the lowest Id is used.
+async fn lookup_alias_names<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    alias_table: &str,
+    aliases: &[&str],
+) -> Result<HashMap<String, EntityName>, EntityMappingError> {
+    let query = format!(
+        "SELECT DISTINCT ON (a.alias) a.alias, e.name FROM alias.{} a \
+        JOIN entity.{} e ON e.id = a.entity_id \
+        WHERE a.alias = ANY($1) ORDER BY a.alias, e.id",
+        escape_identifier(alias_table),
+        escape_identifier(entity_type)
+    );
+
+    let rows = client
+        .query(&query, &[&aliases])
+        .await
+        .map_err(EntityMappingError::DatabaseError)?;
+
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Entity mapping that looks up every name in the database. In strict mode, unknown entities are
+/// rejected instead of created.
+#[derive(Default)]
+pub struct DbEntityMapping {
+    pub strict: bool,
+}
 
 impl EntityMapping for DbEntityMapping {
     async fn uses_alias_column<T: GenericClient + Sync>(
@@ -132,6 +203,7 @@ impl EntityMapping for DbEntityMapping {
                 row.try_get(1).map_err(EntityMappingError::DatabaseError)?;
             let entity_id: i64 = match entity_id_value {
                 Some(entity_id) => entity_id,
+                None if self.strict => return Err(EntityMappingError::UnknownEntityError(name)),
                 None => create_entity(client, entity_type, &name).await?,
             };
 
@@ -217,6 +289,7 @@ impl EntityMapping for DbEntityMapping {
                         alias,
                     }
                 }
+                None if self.strict => return Err(EntityMappingError::UnknownEntityError(name)),
                 None => {
                     if has_primary_alias {
                         create_entity_with_alias(client, entity_type, &name).await?
@@ -247,12 +320,38 @@ impl EntityMapping for DbEntityMapping {
 
         Ok(result)
     }
+
+    async fn aliases_to_names<T: GenericClient + Sync>(
+        &self,
+        client: &T,
+        entity_type: &EntityTypeName,
+        alias_type: &str,
+        aliases: &[String],
+    ) -> Result<Vec<EntityName>, EntityMappingError> {
+        let alias_table = alias_table_name(client, entity_type, alias_type).await?;
+
+        let lookup_list: Vec<&str> = aliases.iter().map(String::as_str).collect();
+
+        let names = lookup_alias_names(client, entity_type, &alias_table, &lookup_list).await?;
+
+        aliases
+            .iter()
+            .map(|alias| {
+                names
+                    .get(alias)
+                    .cloned()
+                    .ok_or_else(|| EntityMappingError::UnknownEntityError(alias.clone()))
+            })
+            .collect()
+    }
 }
 
 pub struct CachingEntityMapping {
     id_cache: Cache<(EntityTypeName, EntityName), i64>,
     alias_cache: Cache<(EntityTypeName, EntityName), Option<String>>,
     primary_alias_cache: Cache<EntityTypeName, bool>,
+    alias_name_cache: Cache<(EntityTypeName, String, String), EntityName>,
+    strict: bool,
 }
 
 impl CachingEntityMapping {
@@ -262,8 +361,18 @@ impl CachingEntityMapping {
             id_cache: Cache::new(size),
             alias_cache: Cache::new(size),
             primary_alias_cache: Cache::new(size),
+            alias_name_cache: Cache::new(size),
+            strict: false,
         }
     }
+
+    /// Reject unknown entities instead of creating them, e.g. when all entities are registered
+    /// up-front from an inventory.
+    #[must_use]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 }
 
 impl EntityMapping for CachingEntityMapping {
@@ -330,6 +439,9 @@ impl EntityMapping for CachingEntityMapping {
                     row.try_get(1).map_err(EntityMappingError::DatabaseError)?;
                 let entity_id: i64 = match entity_id_value {
                     Some(entity_id) => entity_id,
+                    None if self.strict => {
+                        return Err(EntityMappingError::UnknownEntityError(name));
+                    }
                     None => create_entity(client, entity_type, &name).await?,
                 };
 
@@ -477,6 +589,12 @@ impl EntityMapping for CachingEntityMapping {
                 .map(String::as_str)
                 .collect();
 
+            if self.strict
+                && let Some(name) = missing_entities.first()
+            {
+                return Err(EntityMappingError::UnknownEntityError(name.to_string()));
+            }
+
             if !missing_entities.is_empty() {
                 let query = "WITH lookup_list AS (SELECT unnest($1::text[]) AS name), \
                     data AS (SELECT entity.get_entity(et, l.name) AS entity FROM lookup_list l, directory.entity_type et WHERE et.name = $2)
@@ -506,6 +624,61 @@ impl EntityMapping for CachingEntityMapping {
             })
             .collect()
     }
+    async fn aliases_to_names<T: GenericClient + Sync>(
+        &self,
+        client: &T,
+        entity_type: &EntityTypeName,
+        alias_type: &str,
+        aliases: &[String],
+    ) -> Result<Vec<EntityName>, EntityMappingError> {
+        let mut names: HashMap<String, EntityName> = HashMap::new();
+        let mut lookup_list: Vec<&str> = Vec::new();
+
+        for alias in aliases {
+            let key = (
+                entity_type.to_string(),
+                alias_type.to_string(),
+                alias.clone(),
+            );
+
+            match self.alias_name_cache.get(&key) {
+                Some(name) => {
+                    names.insert(alias.clone(), name);
+                }
+                None => lookup_list.push(alias.as_str()),
+            }
+        }
+
+        // Only lookup in the database if there is anything left to lookup
+        if !lookup_list.is_empty() {
+            let alias_table = alias_table_name(client, entity_type, alias_type).await?;
+
+            let found = lookup_alias_names(client, entity_type, &alias_table, &lookup_list).await?;
+
+            for (alias, name) in found {
+                self.alias_name_cache.insert(
+                    (
+                        entity_type.to_string(),
+                        alias_type.to_string(),
+                        alias.clone(),
+                    ),
+                    name.clone(),
+                );
+
+                names.insert(alias, name);
+            }
+        }
+
+        aliases
+            .iter()
+            .map(|alias| {
+                names
+                    .get(alias)
+                    .cloned()
+                    .ok_or_else(|| EntityMappingError::UnknownEntityError(alias.clone()))
+            })
+            .collect()
+    }
 }
 
 async fn create_entity<T: GenericClient>(
@@ -563,3 +736,542 @@ async fn create_entity_with_alias<T: GenericClient>(
         None => Err(EntityMappingError::EntityInsertError),
     }
 }
+
+#[derive(Error, Debug)]
+pub enum EntityQueryError {
+    #[error("No entity '{1}' of type '{0}'")]
+    NotFound(String, String),
+    #[error("{0}")]
+    Database(String),
+}
+
+/// Translate a search pattern with '*' and '?' wildcards to an SQL LIKE pattern. A pattern
+/// without wildcards matches any name containing it.
+#[must_use]
+pub fn search_pattern_to_like(pattern: &str) -> String {
+    let mut result = String::new();
+
+    for c in pattern.chars() {
+        match c {
+            '*' => result.push('%'),
+            '?' => result.push('_'),
+            '%' | '_' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+
+    if pattern.contains(['*', '?']) {
+        result
+    } else {
+        format!("%{result}%")
+    }
+}
+
+async fn has_primary_alias<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+) -> Result<bool, EntityQueryError> {
+    let row = client
+        .query_opt(
+            "SELECT primary_alias IS NOT NULL FROM directory.entity_type WHERE name = $1",
+            &[&entity_type],
+        )
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not load entity type: {e}")))?
+        .ok_or_else(|| {
+            EntityQueryError::Database(format!("No entity type with name '{entity_type}'"))
+        })?;
+
+    Ok(row.get(0))
+}
+
+/// Find entities of a type with a name or primary alias matching the pattern, case-insensitive
+pub async fn find_entities<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    pattern: &str,
+    limit: i64,
+) -> Result<Vec<LargeEntity>, EntityQueryError> {
+    let query = if has_primary_alias(client, entity_type).await? {
+        format!(
+            "SELECT id::bigint, name, primary_alias FROM entity.{} \
+            WHERE name ILIKE $1 OR primary_alias ILIKE $1 ORDER BY name LIMIT $2",
+            escape_identifier(entity_type)
+        )
+    } else {
+        format!(
+            "SELECT id::bigint, name, NULL::text FROM entity.{} \
+            WHERE name ILIKE $1 ORDER BY name LIMIT $2",
+            escape_identifier(entity_type)
+        )
+    };
+
+    let rows = client
+        .query(&query, &[&search_pattern_to_like(pattern), &limit])
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not search entities: {e}")))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| LargeEntity {
+            id: row.get(0),
+            name: row.get(1),
+            alias: row.get(2),
+        })
+        .collect())
+}
+
+/// Last timestamp with data for an entity in a trend store part, within the retention period of
+/// the trend store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityTrendData {
+    pub trend_store_part: String,
+    pub last_timestamp: DateTime<Utc>,
+}
+
+/// Current attributes of an entity in an attribute store
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityAttributes {
+    pub attribute_store: String,
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Entities related to an entity by a relation in which it is the source or the target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityRelations {
+    pub relation: String,
+    pub entity_is_source: bool,
+    pub related: Vec<String>,
+}
+
+/// Everything that is known about an entity in the different parts of Minerva
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntityDetails {
+    pub id: i64,
+    pub name: String,
+    pub primary_alias: Option<String>,
+    pub retired: bool,
+    pub trend_data: Vec<EntityTrendData>,
+    pub attributes: Vec<EntityAttributes>,
+    pub relations: Vec<EntityRelations>,
+    pub entity_sets: Vec<String>,
+}
+
+pub async fn load_entity_details<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    name: &str,
+) -> Result<EntityDetails, EntityQueryError> {
+    let query = if has_primary_alias(client, entity_type).await? {
+        format!(
+            "SELECT id::bigint, primary_alias, directory.is_retired($2, id) FROM entity.{} WHERE name = $1",
+            escape_identifier(entity_type)
+        )
+    } else {
+        format!(
+            "SELECT id::bigint, NULL::text, directory.is_retired($2, id) FROM entity.{} WHERE name = $1",
+            escape_identifier(entity_type)
+        )
+    };
+
+    let row = client
+        .query_opt(&query, &[&name, &entity_type])
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not load entity: {e}")))?
+        .ok_or_else(|| EntityQueryError::NotFound(entity_type.to_string(), name.to_string()))?;
+
+    let id: i64 = row.get(0);
+
+    Ok(EntityDetails {
+        id,
+        name: name.to_string(),
+        primary_alias: row.get(1),
+        retired: row.get(2),
+        trend_data: load_entity_trend_data(client, entity_type, id).await?,
+        attributes: load_entity_attributes(client, entity_type, id).await?,
+        relations: load_entity_relations(client, entity_type, name).await?,
+        entity_sets: load_entity_set_names(client, entity_type, id).await?,
+    })
+}
+
+async fn load_entity_trend_data<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    entity_id: i64,
+) -> Result<Vec<EntityTrendData>, EntityQueryError> {
+    let query = concat!(
+        "SELECT tsp.name, now() - ts.retention_period FROM trend_directory.trend_store_part tsp ",
+        "JOIN trend_directory.trend_store ts ON ts.id = tsp.trend_store_id ",
+        "JOIN directory.entity_type et ON et.id = ts.entity_type_id ",
+        "WHERE et.name = $1 ORDER BY tsp.name"
+    );
+
+    let rows = client.query(query, &[&entity_type]).await.map_err(|e| {
+        EntityQueryError::Database(format!("Could not load trend store parts: {e}"))
+    })?;
+
+    let mut result = Vec::new();
+
+    for row in rows {
+        let trend_store_part: String = row.get(0);
+        let retention_start: DateTime<Utc> = row.get(1);
+
+        // Only look at the partitions within the retention period, so that not every partition
+        // of the part is scanned.
+        let query = format!(
+            "SELECT max(timestamp) FROM trend.{} WHERE entity_id = $1::bigint AND timestamp >= $2",
+            escape_identifier(&trend_store_part)
+        );
+
+        let last_timestamp: Option<DateTime<Utc>> = client
+            .query_one(&query, &[&entity_id, &retention_start])
+            .await
+            .map_err(|e| {
+                EntityQueryError::Database(format!(
+                    "Could not load data of trend store part '{trend_store_part}': {e}"
+                ))
+            })?
+            .get(0);
+
+        if let Some(last_timestamp) = last_timestamp {
+            result.push(EntityTrendData {
+                trend_store_part,
+                last_timestamp,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+async fn load_entity_attributes<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    entity_id: i64,
+) -> Result<Vec<EntityAttributes>, EntityQueryError> {
+    let query = concat!(
+        "SELECT ast::text FROM attribute_directory.attribute_store ast ",
+        "JOIN directory.entity_type et ON et.id = ast.entity_type_id ",
+        "WHERE et.name = $1 ORDER BY ast::text"
+    );
+
+    let rows = client
+        .query(query, &[&entity_type])
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not load attribute stores: {e}")))?;
+
+    let mut result = Vec::new();
+
+    for row in rows {
+        let attribute_store: String = row.get(0);
+
+        let query = format!(
+            "SELECT to_jsonb(a) - 'id' - 'entity_id' FROM attribute.{} a WHERE entity_id = $1::bigint",
+            escape_identifier(&attribute_store)
+        );
+
+        let attributes = client.query_opt(&query, &[&entity_id]).await.map_err(|e| {
+            EntityQueryError::Database(format!(
+                "Could not load attributes from '{attribute_store}': {e}"
+            ))
+        })?;
+
+        if let Some(row) = attributes
+            && let serde_json::Value::Object(attributes) = row.get(0)
+        {
+            result.push(EntityAttributes {
+                attribute_store,
+                attributes,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+async fn load_entity_relations<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    name: &str,
+) -> Result<Vec<EntityRelations>, EntityQueryError> {
+    let relation_names = load_relation_names(client)
+        .await
+        .map_err(|e| EntityQueryError::Database(e.to_string()))?;
+
+    let mut result = Vec::new();
+
+    for relation in relation_names {
+        let Some((source_type, target_type)) = relation_entity_types(&relation) else {
+            continue;
+        };
+
+        // Entity set membership is reported separately
+        if target_type == "entity_set" {
+            continue;
+        }
+
+        for (entity_is_source, filter) in [
+            (true, RelationFilter::Source(name.to_string())),
+            (false, RelationFilter::Target(name.to_string())),
+        ] {
+            let matches_type = if entity_is_source {
+                source_type == entity_type
+            } else {
+                target_type == entity_type
+            };
+
+            if !matches_type {
+                continue;
+            }
+
+            let related: Vec<String> =
+                load_related_entities(client, std::slice::from_ref(&relation), &filter)
+                    .await
+                    .map_err(|e| EntityQueryError::Database(e.to_string()))?
+                    .into_iter()
+                    .map(|pair| {
+                        if entity_is_source {
+                            pair.target_name
+                        } else {
+                            pair.source_name
+                        }
+                    })
+                    .collect();
+
+            if !related.is_empty() {
+                result.push(EntityRelations {
+                    relation: relation.clone(),
+                    entity_is_source,
+                    related,
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+async fn load_entity_set_names<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    entity_id: i64,
+) -> Result<Vec<String>, EntityQueryError> {
+    let relation = format!("{entity_type}->entity_set");
+
+    let exists: bool = client
+        .query_one(
+            "SELECT exists(SELECT 1 FROM relation_directory.type WHERE name = $1)",
+            &[&relation],
+        )
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not check relation: {e}")))?
+        .get(0);
+
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let query = format!(
+        "SELECT es.owner || '/' || es.name FROM relation.{} r \
+        JOIN attribute.minerva_entity_set es ON es.entity_id = r.target_id \
+        WHERE r.source_id = $1::bigint ORDER BY es.owner, es.name",
+        escape_identifier(&relation)
+    );
+
+    let rows = client
+        .query(&query, &[&entity_id])
+        .await
+        .map_err(|e| EntityQueryError::Database(format!("Could not load entity sets: {e}")))?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Entities and their aliases from an inventory CSV file, with a 'name' column and a column per
+/// alias type. The aliases are stored per alias type in the order of the names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityInventory {
+    pub names: Vec<String>,
+    pub alias_types: Vec<String>,
+    pub aliases: Vec<Vec<Option<String>>>,
+}
+
+pub fn read_entity_inventory<R: std::io::Read>(reader: R) -> Result<EntityInventory, Error> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+
+    let headers = csv_reader
+        .headers()
+        .map_err(|e| RuntimeError::from_msg(format!("Could not read inventory header: {e}")))?
+        .clone();
+
+    let name_index = headers
+        .iter()
+        .position(|h| h == "name")
+        .ok_or_else(|| RuntimeError::from_msg("No 'name' column in inventory".to_string()))?;
+
+    let alias_columns: Vec<(usize, String)> = headers
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index != name_index)
+        .map(|(index, header)| (index, header.to_string()))
+        .collect();
+
+    let mut inventory = EntityInventory {
+        names: Vec::new(),
+        alias_types: alias_columns.iter().map(|(_, name)| name.clone()).collect(),
+        aliases: vec![Vec::new(); alias_columns.len()],
+    };
+
+    for (line, record) in (2..).zip(csv_reader.records()) {
+        let record = record.map_err(|e| {
+            RuntimeError::from_msg(format!("Could not read inventory line {line}: {e}"))
+        })?;
+
+        let name = record.get(name_index).unwrap_or_default().trim();
+
+        if name.is_empty() {
+            return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                "Empty entity name on inventory line {line}"
+            ))));
+        }
+
+        inventory.names.push(name.to_string());
+
+        for ((index, _), aliases) in alias_columns.iter().zip(inventory.aliases.iter_mut()) {
+            aliases.push(
+                record
+                    .get(*index)
+                    .map(str::trim)
+                    .filter(|alias| !alias.is_empty())
+                    .map(String::from),
+            );
+        }
+    }
+
+    Ok(inventory)
+}
+
+pub fn load_entity_inventory_from_file(path: &std::path::Path) -> Result<EntityInventory, Error> {
+    let f = std::fs::File::open(path).map_err(|e| {
+        ConfigurationError::from_msg(format!(
+            "Could not open inventory file '{}': {e}",
+            path.display()
+        ))
+    })?;
+
+    read_entity_inventory(std::io::BufReader::new(f))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EntityImportResult {
+    pub created: u64,
+    pub existing: u64,
+    pub aliases: u64,
+}
+
+/// Register the entities and aliases of an inventory in one statement per table. When an
+/// inventory has multiple aliases of a type for an entity, the last one in the inventory is
+/// used. Pass a transaction as client to register the inventory all-or-nothing.
+pub async fn register_entities<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    inventory: &EntityInventory,
+) -> Result<EntityImportResult, Error> {
+    let mut alias_tables: Vec<String> = Vec::new();
+
+    for alias_type in &inventory.alias_types {
+        let alias_table = match alias_table_name(client, entity_type, alias_type).await {
+            Ok(alias_table) => alias_table,
+            Err(EntityMappingError::UnknownAliasTypeError(_)) => {
+                return Err(Error::Runtime(RuntimeError::from_msg(format!(
+                    "Inventory column '{alias_type}' is not 'name' or an alias type"
+                ))));
+            }
+            Err(e) => {
+                return Err(
+                    DatabaseError::from_msg(format!("Could not load alias types: {e}")).into(),
+                );
+            }
+        };
+
+        alias_tables.push(alias_table);
+    }
+
+    let query = format!(
+        "INSERT INTO entity.{}(name) SELECT DISTINCT unnest($1::text[]) ON CONFLICT DO NOTHING",
+        escape_identifier(entity_type)
+    );
+
+    let created = client
+        .execute(&query, &[&inventory.names])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not register entities: {e}")))?;
+
+    let unique_count = inventory
+        .names
+        .iter()
+        .collect::<std::collections::HashSet<_>>()
+        .len() as u64;
+
+    let mut result = EntityImportResult {
+        created,
+        existing: unique_count - created,
+        aliases: 0,
+    };
+
+    for ((alias_type, alias_table), aliases) in inventory
+        .alias_types
+        .iter()
+        .zip(&alias_tables)
+        .zip(&inventory.aliases)
+    {
+        let query = format!(
+            "INSERT INTO alias.{}(entity_id, alias) \
+            SELECT DISTINCT ON (e.id) e.id, d.alias \
+            FROM unnest($1::text[], $2::text[]) WITH ORDINALITY d(name, alias, row_number) \
+            JOIN entity.{} e ON e.name = d.name WHERE d.alias IS NOT NULL \
+            ORDER BY e.id, d.row_number DESC \
+            ON CONFLICT (entity_id) DO UPDATE SET alias = EXCLUDED.alias",
+            escape_identifier(alias_table),
+            escape_identifier(entity_type)
+        );
+
+        result.aliases += client
+            .execute(&query, &[&inventory.names, aliases])
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!("Could not register '{alias_type}' aliases: {e}"))
+            })?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_pattern_to_like() {
+        assert_eq!(search_pattern_to_like("cell1"), "%cell1%");
+        assert_eq!(search_pattern_to_like("cell*"), "cell%");
+        assert_eq!(search_pattern_to_like("cell?_1"), "cell_\\_1");
+        assert_eq!(search_pattern_to_like("100%"), "%100\\%%");
+    }
+
+    #[test]
+    fn test_read_entity_inventory() {
+        let data = "vendor_id,name\nV1, cell1\n,cell2\n";
+
+        let inventory = read_entity_inventory(data.as_bytes()).unwrap();
+
+        assert_eq!(inventory.names, vec!["cell1", "cell2"]);
+        assert_eq!(inventory.alias_types, vec!["vendor_id"]);
+        assert_eq!(inventory.aliases, vec![vec![Some("V1".to_string()), None]]);
+
+        assert!(read_entity_inventory("vendor_id\nV1\n".as_bytes()).is_err());
+        assert!(read_entity_inventory("name\n\"\"\n".as_bytes()).is_err());
+    }
+}
@@ -1,16 +1,19 @@
 use std::fmt::{self, Display};
+use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
 use chrono::{DateTime, Utc};
-use tokio_postgres::{Client, Transaction};
+use postgres_protocol::escape::{escape_identifier, escape_literal};
+use tokio_postgres::{Client, GenericClient, Transaction};
 
 use async_trait::async_trait;
 
 use crate::change::Changed;
 
 use super::change::{Change, ChangeResult};
-use super::error::{DatabaseError, Error, RuntimeError};
+use super::error::{ConfigurationError, DatabaseError, Error, RuntimeError};
+use super::relation::relation_entity_types;
 
 type PostgresName = String;
 
@@ -23,10 +26,15 @@ pub struct EntitySet {
     pub owner: String,
     pub description: String,
     pub entities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<EntitySetRule>,
     pub created: DateTime<Utc>,
     pub modified: DateTime<Utc>,
 }
 
+/// A new entity set, either with a static list of entities or with rules that define the
+/// members. The members of an entity set with rules are derived from the rules and the
+/// `entities` are ignored.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct NewEntitySet {
     pub name: PostgresName,
@@ -34,9 +42,206 @@ pub struct NewEntitySet {
     pub entity_type: String,
     pub owner: String,
     pub description: String,
+    #[serde(default)]
     pub entities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<EntitySetRule>,
 }
 
+/// Comparison of an attribute with the value of an attribute rule
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeOperator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+    /// The value must be a list
+    In,
+}
+
+impl AttributeOperator {
+    fn sql(self) -> &'static str {
+        match self {
+            AttributeOperator::Eq => "=",
+            AttributeOperator::Ne => "<>",
+            AttributeOperator::Lt => "<",
+            AttributeOperator::Le => "<=",
+            AttributeOperator::Gt => ">",
+            AttributeOperator::Ge => ">=",
+            AttributeOperator::Like => "LIKE",
+            AttributeOperator::In => "IN",
+        }
+    }
+}
+
+/// Quoted SQL literal for a scalar rule value. The literal is left untyped, so that Postgres
+/// converts it to the type of the attribute it is compared with.
+fn rule_value_literal(value: &serde_json::Value) -> Result<String, EntitySetError> {
+    match value {
+        serde_json::Value::String(s) => Ok(escape_literal(s)),
+        serde_json::Value::Number(n) => Ok(escape_literal(&n.to_string())),
+        serde_json::Value::Bool(b) => Ok(escape_literal(&b.to_string())),
+        _ => Err(EntitySetError::InvalidRule(format!(
+            "Unsupported attribute rule value: {value}"
+        ))),
+    }
+}
+
+/// Rule for the members of a dynamic entity set. An entity is a member when it matches all rules
+/// of the entity set and is not retired.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntitySetRule {
+    /// Comparison of a current attribute in `attribute.<attribute_store>` with a value, e.g.
+    /// `technology` `eq` `5G`. A `null` value with `eq` or `ne` checks for (non) NULL values.
+    Attribute {
+        attribute_store: String,
+        attribute: String,
+        operator: AttributeOperator,
+        value: serde_json::Value,
+    },
+    /// Regular expression that the entity name must match
+    NameRegex { pattern: String },
+    /// The entity must be the source of the relation, with the named entity as target
+    Relation { relation: String, target: String },
+}
+
+impl EntitySetRule {
+    fn condition(&self) -> Result<String, EntitySetError> {
+        match self {
+            EntitySetRule::Attribute {
+                attribute_store,
+                attribute,
+                operator,
+                value,
+            } => {
+                let column = format!("a.{}", escape_identifier(attribute));
+
+                let comparison = match (operator, value) {
+                    (AttributeOperator::Eq, serde_json::Value::Null) => {
+                        format!("{column} IS NULL")
+                    }
+                    (AttributeOperator::Ne, serde_json::Value::Null) => {
+                        format!("{column} IS NOT NULL")
+                    }
+                    (AttributeOperator::In, serde_json::Value::Array(values)) => format!(
+                        "{column} IN ({})",
+                        values
+                            .iter()
+                            .map(rule_value_literal)
+                            .collect::<Result<Vec<String>, EntitySetError>>()?
+                            .join(", ")
+                    ),
+                    (AttributeOperator::In, _) => {
+                        return Err(EntitySetError::InvalidRule(format!(
+                            "The value for 'in' on attribute '{attribute}' must be a list"
+                        )));
+                    }
+                    (operator, value) => {
+                        format!("{column} {} {}", operator.sql(), rule_value_literal(value)?)
+                    }
+                };
+
+                Ok(format!(
+                    "EXISTS (SELECT 1 FROM attribute.{} a WHERE a.entity_id = e.id AND {comparison})",
+                    escape_identifier(attribute_store)
+                ))
+            }
+            EntitySetRule::NameRegex { pattern } => {
+                Ok(format!("e.name ~ {}", escape_literal(pattern)))
+            }
+            EntitySetRule::Relation { relation, target } => {
+                let (_, target_type) = relation_entity_types(relation).ok_or_else(|| {
+                    EntitySetError::InvalidRule(format!(
+                        "Relation name '{relation}' does not follow the '<source>-><target>' convention"
+                    ))
+                })?;
+
+                Ok(format!(
+                    concat!(
+                        "EXISTS (SELECT 1 FROM relation.{} r JOIN entity.{} t ON t.id = r.target_id ",
+                        "WHERE r.source_id = e.id AND t.name = {})"
+                    ),
+                    escape_identifier(relation),
+                    escape_identifier(target_type),
+                    escape_literal(target)
+                ))
+            }
+        }
+    }
+}
+
+/// Query for the names of the entities that match all rules
+pub fn entity_set_rules_query(
+    entity_type: &str,
+    rules: &[EntitySetRule],
+) -> Result<String, EntitySetError> {
+    let mut conditions = vec![format!(
+        "NOT directory.is_retired({}, e.id)",
+        escape_literal(entity_type)
+    )];
+
+    for rule in rules {
+        conditions.push(rule.condition()?);
+    }
+
+    Ok(format!(
+        "SELECT e.name FROM entity.{} e WHERE {} ORDER BY e.name",
+        escape_identifier(entity_type),
+        conditions.join(" AND ")
+    ))
+}
+
+pub async fn evaluate_entity_set_rules<T: GenericClient + Sync>(
+    conn: &T,
+    entity_type: &str,
+    rules: &[EntitySetRule],
+) -> Result<Vec<String>, EntitySetError> {
+    let query = entity_set_rules_query(entity_type, rules)?;
+
+    let rows = conn
+        .query(&query, &[])
+        .await
+        .map_err(|e| EntitySetError::InvalidRule(format!("Could not evaluate rules: {e}")))?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+fn rules_from_json(value: Option<serde_json::Value>) -> Result<Vec<EntitySetRule>, String> {
+    match value {
+        Some(value) => {
+            serde_json::from_value(value).map_err(|e| format!("Invalid entity set rules: {e}"))
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+async fn store_entity_set_rules<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+    rules: &[EntitySetRule],
+) -> Result<(), EntitySetError> {
+    let rules_json = if rules.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_value(rules).map_err(|e| EntitySetError::InvalidRule(e.to_string()))?)
+    };
+
+    conn.execute(
+        "UPDATE directory.entity_set SET rules = $2, evaluated = now() WHERE entity_id = $1",
+        &[&id, &rules_json],
+    )
+    .await
+    .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
 pub enum EntitySetError {
     DatabaseError(DatabaseError),
     NotFound(DatabaseError),
@@ -45,6 +250,7 @@ pub enum EntitySetError {
     MissingEntities(Vec<String>),
     UnchangeableFields(Vec<String>),
     IncorrectEntityType(String),
+    InvalidRule(String),
 }
 
 impl From<DatabaseError> for EntitySetError {
@@ -59,6 +265,37 @@ impl From<String> for EntitySetError {
     }
 }
 
+impl From<EntitySetError> for Error {
+    fn from(e: EntitySetError) -> Error {
+        match e {
+            EntitySetError::DatabaseError(err) => Error::Database(err),
+            EntitySetError::NotFound(err) => Error::Database(err),
+            EntitySetError::ExistingEntitySet(name, owner) => {
+                Error::Database(DatabaseError::UniqueViolation(format!(
+                    "An entity set with name {} and owner {} already exists.",
+                    name, owner,
+                )))
+            }
+            EntitySetError::EmptyEntitySet => Error::Runtime(RuntimeError::from_msg(
+                "Entity sets cannot be empty".to_string(),
+            )),
+            EntitySetError::MissingEntities(missing_entities) => {
+                Error::Runtime(RuntimeError::from_msg(format!(
+                    "The following entities do not exist: {}",
+                    missing_entities.join(", ")
+                )))
+            }
+            EntitySetError::UnchangeableFields(fields) => Error::Runtime(RuntimeError::from_msg(
+                format!("Fields can not be changed: {}", fields.join(", ")),
+            )),
+            EntitySetError::IncorrectEntityType(entity_type) => Error::Runtime(
+                RuntimeError::from_msg(format!("Entity type '{entity_type}' does not exist")),
+            ),
+            EntitySetError::InvalidRule(msg) => Error::Runtime(RuntimeError::from_msg(msg)),
+        }
+    }
+}
+
 impl fmt::Display for EntitySet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "EntitySet({}:{})", self.owner, self.name,)
@@ -71,6 +308,99 @@ impl fmt::Display for NewEntitySet {
     }
 }
 
+impl From<EntitySet> for NewEntitySet {
+    fn from(entity_set: EntitySet) -> Self {
+        NewEntitySet {
+            name: entity_set.name,
+            group: entity_set.group,
+            entity_type: entity_set.entity_type,
+            owner: entity_set.owner,
+            description: entity_set.description,
+            entities: entity_set.entities,
+            rules: entity_set.rules,
+        }
+    }
+}
+
+impl NewEntitySet {
+    #[must_use]
+    pub fn diff(&self, other: &NewEntitySet) -> Vec<Box<dyn Change + Send>> {
+        let mut changes: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        // The order of the members is not relevant
+        let mut my_entities = self.entities.clone();
+        my_entities.sort();
+
+        let mut other_entities = other.entities.clone();
+        other_entities.sort();
+
+        // The members of dynamic entity sets follow from the rules
+        let members_changed = if other.rules.is_empty() {
+            my_entities != other_entities
+        } else {
+            self.rules != other.rules
+        };
+
+        // The entity type of an existing entity set cannot be changed, so it is replaced
+        if self.entity_type != other.entity_type {
+            changes.push(Box::new(RemoveEntitySet {
+                owner: self.owner.clone(),
+                name: self.name.clone(),
+            }));
+            changes.push(Box::new(CreateEntitySet {
+                entity_set: other.clone(),
+            }));
+        } else if self.group != other.group
+            || self.description != other.description
+            || members_changed
+        {
+            changes.push(Box::new(UpdateEntitySet {
+                entity_set: other.clone(),
+            }));
+        }
+
+        changes
+    }
+}
+
+pub fn load_entity_set_from_file(path: &PathBuf) -> Result<NewEntitySet, Error> {
+    let f = std::fs::File::open(path).map_err(|e| {
+        ConfigurationError::from_msg(format!(
+            "Could not open entity set definition file '{}': {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    if path.extension() == Some(std::ffi::OsStr::new("yaml")) {
+        let entity_set: NewEntitySet = serde_yaml::from_reader(f).map_err(|e| {
+            RuntimeError::from_msg(format!(
+                "Could not read entity set definition from file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        Ok(entity_set)
+    } else if path.extension() == Some(std::ffi::OsStr::new("json")) {
+        let entity_set: NewEntitySet = serde_json::from_reader(f).map_err(|e| {
+            RuntimeError::from_msg(format!(
+                "Could not read entity set definition from file '{}': {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        Ok(entity_set)
+    } else {
+        Err(ConfigurationError::from_msg(format!(
+            "Unsupported entity set definition format '{}'",
+            path.extension().unwrap_or_default().to_string_lossy()
+        ))
+        .into())
+    }
+}
+
 async fn get_entity_set_members(conn: &mut Client, id: i32) -> Result<Vec<String>, String> {
     let query = "SELECT relation_directory.get_entity_set_members($1)";
     let row = conn
@@ -89,9 +419,10 @@ async fn get_entity_set_members(conn: &mut Client, id: i32) -> Result<Vec<String
 
 pub async fn load_entity_sets(conn: &mut Client) -> Result<Vec<EntitySet>, String> {
     let query = concat!(
-        "SELECT name, \"group\", source_entity_type, owner, description, ",
-        "entity_id, first_appearance, modified ",
-        "FROM attribute.minerva_entity_set es"
+        "SELECT es.name, es.\"group\", es.source_entity_type, es.owner, es.description, ",
+        "es.entity_id, es.first_appearance, es.modified, d.rules ",
+        "FROM attribute.minerva_entity_set es ",
+        "LEFT JOIN directory.entity_set d ON d.entity_id = es.entity_id"
     );
 
     let rows = conn
@@ -114,6 +445,7 @@ pub async fn load_entity_sets(conn: &mut Client) -> Result<Vec<EntitySet>, Strin
             owner: row.get(3),
             description: row.try_get(4).unwrap_or(String::new()),
             entities,
+            rules: rules_from_json(row.get(8))?,
             created: row.get(6),
             modified: row.get(7),
         });
@@ -124,9 +456,10 @@ pub async fn load_entity_sets(conn: &mut Client) -> Result<Vec<EntitySet>, Strin
 
 pub async fn load_entity_set(conn: &mut Client, id: &i32) -> Result<EntitySet, String> {
     let query = concat!(
-        "SELECT name, \"group\", source_entity_type, owner, description, ",
-        "first_appearance, modified, entity_id ",
+        "SELECT es.name, es.\"group\", es.source_entity_type, es.owner, es.description, ",
+        "es.first_appearance, es.modified, es.entity_id, d.rules ",
         "FROM attribute.minerva_entity_set es ",
+        "LEFT JOIN directory.entity_set d ON d.entity_id = es.entity_id ",
         "WHERE es.entity_id = $1"
     );
 
@@ -150,6 +483,7 @@ pub async fn load_entity_set(conn: &mut Client, id: &i32) -> Result<EntitySet, S
         owner: row.get(3),
         description: row.try_get(4).unwrap_or(String::new()),
         entities: entitydata.get(0),
+        rules: rules_from_json(row.get(8))?,
         created: row.get(5),
         modified: row.get(6),
     };
@@ -157,7 +491,337 @@ pub async fn load_entity_set(conn: &mut Client, id: &i32) -> Result<EntitySet, S
     Ok(entity_set)
 }
 
+pub async fn load_entity_set_by_name<T: GenericClient + Send + Sync>(
+    conn: &T,
+    owner: &str,
+    name: &str,
+) -> Result<EntitySet, EntitySetError> {
+    let query = concat!(
+        "SELECT es.name, es.\"group\", es.source_entity_type, es.owner, es.description, ",
+        "es.first_appearance, es.modified, es.entity_id, d.rules ",
+        "FROM attribute.minerva_entity_set es ",
+        "LEFT JOIN directory.entity_set d ON d.entity_id = es.entity_id ",
+        "WHERE es.owner = $1 AND es.name = $2"
+    );
+
+    let row = conn
+        .query_opt(query, &[&owner, &name])
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?
+        .ok_or_else(|| {
+            EntitySetError::NotFound(DatabaseError::from_msg(format!(
+                "No entity set with name '{name}' and owner '{owner}'"
+            )))
+        })?;
+
+    let id: i32 = row.get(7);
+
+    let members = conn
+        .query_one(
+            "SELECT relation_directory.get_entity_set_members($1)",
+            &[&id],
+        )
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+    let entities: Option<Vec<String>> = members.get(0);
+
+    Ok(EntitySet {
+        id,
+        name: row.get(0),
+        group: row.get(1),
+        entity_type: row.get(2),
+        owner: row.get(3),
+        description: row.try_get(4).unwrap_or(String::new()),
+        entities: entities.unwrap_or_default(),
+        rules: rules_from_json(row.get(8)).map_err(EntitySetError::InvalidRule)?,
+        created: row.get(5),
+        modified: row.get(6),
+    })
+}
+
+/// A revision of an entity set with the period in which it was the current revision. The end of
+/// the period is empty for the current revision.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EntitySetRevision {
+    pub id: i64,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct EntitySetRevisionDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Compare the members of two revisions, both as sorted lists in the result
+#[must_use]
+pub fn diff_entity_set_members(from: &[String], to: &[String]) -> EntitySetRevisionDiff {
+    let from_members: std::collections::BTreeSet<&String> = from.iter().collect();
+    let to_members: std::collections::BTreeSet<&String> = to.iter().collect();
+
+    EntitySetRevisionDiff {
+        added: to_members
+            .difference(&from_members)
+            .map(|name| (*name).clone())
+            .collect(),
+        removed: from_members
+            .difference(&to_members)
+            .map(|name| (*name).clone())
+            .collect(),
+    }
+}
+
+/// Entity type of an entity set that has revisions, by the Id of the entity set
+async fn entity_set_revision_entity_type<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+) -> Result<String, EntitySetError> {
+    let query = concat!(
+        "SELECT et.name FROM directory.entity_set es ",
+        "JOIN directory.entity_type et ON et.id = es.entity_type_id ",
+        "WHERE es.entity_id = $1"
+    );
+
+    let row = conn
+        .query_opt(query, &[&id])
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?
+        .ok_or_else(|| {
+            EntitySetError::NotFound(DatabaseError::from_msg(format!(
+                "No revisions for entity set {id}"
+            )))
+        })?;
+
+    Ok(row.get(0))
+}
+
+pub async fn load_entity_set_revisions<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+) -> Result<Vec<EntitySetRevision>, EntitySetError> {
+    let entity_type = entity_set_revision_entity_type(conn, id).await?;
+
+    let query = format!(
+        concat!(
+            "SELECT r.id, lower(r.validity_period), upper(r.validity_period) ",
+            "FROM entity_set.{} r JOIN directory.entity_set es ON es.id = r.entity_set_id ",
+            "WHERE es.entity_id = $1 AND NOT isempty(r.validity_period) ",
+            "ORDER BY lower(r.validity_period)"
+        ),
+        postgres_protocol::escape::escape_identifier(&format!("{entity_type}_revision"))
+    );
+
+    let rows = conn
+        .query(&query, &[&id])
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| EntitySetRevision {
+            id: row.get(0),
+            valid_from: row.get(1),
+            valid_to: row.get(2),
+        })
+        .collect())
+}
+
+pub async fn load_entity_set_revision_members<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+    revision_id: i64,
+) -> Result<Vec<String>, EntitySetError> {
+    let entity_type = entity_set_revision_entity_type(conn, id).await?;
+
+    let query = format!(
+        concat!(
+            "SELECT e.name FROM entity_set.{} m ",
+            "JOIN directory.entity_set es ON es.id = m.entity_set_id ",
+            "JOIN entity.{} e ON e.id = m.entity_id ",
+            "WHERE es.entity_id = $1 AND m.revision_id = $2 ORDER BY e.name"
+        ),
+        postgres_protocol::escape::escape_identifier(&entity_type),
+        postgres_protocol::escape::escape_identifier(&entity_type),
+    );
+
+    let rows = conn
+        .query(&query, &[&id, &revision_id])
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Load the members of an entity set as they were at the timestamp
+pub async fn load_entity_set_members_at<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+    timestamp: &DateTime<Utc>,
+) -> Result<Vec<String>, EntitySetError> {
+    let entity_type = entity_set_revision_entity_type(conn, id).await?;
+
+    let query = format!(
+        concat!(
+            "SELECT e.name FROM entity_set.get_members_at($1, $2) m(entity_id) ",
+            "JOIN entity.{} e ON e.id = m.entity_id ORDER BY e.name"
+        ),
+        postgres_protocol::escape::escape_identifier(&entity_type),
+    );
+
+    let rows = conn
+        .query(&query, &[&id, timestamp])
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+pub async fn diff_entity_set_revisions<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+    from_revision_id: i64,
+    to_revision_id: i64,
+) -> Result<EntitySetRevisionDiff, EntitySetError> {
+    let from = load_entity_set_revision_members(conn, id, from_revision_id).await?;
+    let to = load_entity_set_revision_members(conn, id, to_revision_id).await?;
+
+    Ok(diff_entity_set_members(&from, &to))
+}
+
+/// Result of the re-evaluation of the rules of a dynamic entity set
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntitySetEvaluation {
+    pub owner: String,
+    pub name: String,
+    pub diff: EntitySetRevisionDiff,
+}
+
+async fn load_current_entity_set_members<T: GenericClient + Sync>(
+    conn: &T,
+    id: i32,
+    entity_type: &str,
+) -> Result<Vec<String>, Error> {
+    let query = format!(
+        "SELECT e.name FROM relation.{} r JOIN entity.{} e ON e.id = r.source_id WHERE r.target_id = $1",
+        escape_identifier(&format!("{entity_type}->entity_set")),
+        escape_identifier(entity_type),
+    );
+
+    let rows = conn.query(&query, &[&id]).await.map_err(|e| {
+        DatabaseError::from_msg(format!("Could not load members of entity set {id}: {e}"))
+    })?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Re-evaluate the rules of the dynamic entity sets and update the members of the sets that
+/// changed, which records a new revision for each of them. With `changed_only`, only sets that
+/// refer to attribute stores that were materialized after the last evaluation are evaluated.
+pub async fn evaluate_dynamic_entity_sets(
+    client: &mut Client,
+    changed_only: bool,
+) -> Result<Vec<EntitySetEvaluation>, Error> {
+    let query = concat!(
+        "SELECT es.entity_id, es.owner, es.name, et.name, es.rules ",
+        "FROM directory.entity_set es ",
+        "JOIN directory.entity_type et ON et.id = es.entity_type_id ",
+        "WHERE es.rules IS NOT NULL AND (NOT $1 OR es.evaluated IS NULL OR EXISTS (",
+        "SELECT 1 FROM jsonb_array_elements(es.rules) rule ",
+        "JOIN attribute_directory.attribute_store ast ON ast::text = rule->>'attribute_store' ",
+        "JOIN attribute_directory.attribute_store_curr_materialized cm ON cm.attribute_store_id = ast.id ",
+        "WHERE cm.materialized > es.evaluated)) ",
+        "ORDER BY es.owner, es.name"
+    );
+
+    let rows = client
+        .query(query, &[&changed_only])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load dynamic entity sets: {e}")))?;
+
+    let mut evaluations: Vec<EntitySetEvaluation> = Vec::new();
+
+    for row in rows {
+        let id: i32 = row.get(0);
+        let owner: String = row.get(1);
+        let name: String = row.get(2);
+        let entity_type: String = row.get(3);
+        let rules = rules_from_json(row.get(4)).map_err(RuntimeError::from_msg)?;
+
+        let tx = client.transaction().await?;
+
+        let members = evaluate_entity_set_rules(&tx, &entity_type, &rules).await?;
+        let current_members = load_current_entity_set_members(&tx, id, &entity_type).await?;
+
+        let diff = diff_entity_set_members(&current_members, &members);
+
+        if !(diff.added.is_empty() && diff.removed.is_empty()) {
+            tx.execute(
+                "SELECT relation_directory.change_set_entities($1, $2::text[])",
+                &[&id, &members],
+            )
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Could not update members of entity set '{owner}:{name}': {e}"
+                ))
+            })?;
+        }
+
+        tx.execute(
+            "UPDATE directory.entity_set SET evaluated = now() WHERE entity_id = $1",
+            &[&id],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(e.to_string()))?;
+
+        tx.commit().await?;
+
+        evaluations.push(EntitySetEvaluation { owner, name, diff });
+    }
+
+    Ok(evaluations)
+}
+
 impl EntitySet {
+    /// Remove the entity set, including its membership relation records and attribute history.
+    pub async fn delete(&self, conn: &mut Transaction<'_>) -> Result<(), EntitySetError> {
+        conn.execute("SELECT entity_set.close_revision($1)", &[&self.id])
+            .await
+            .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+        let query = format!(
+            "DELETE FROM relation.{} WHERE target_id = $1",
+            postgres_protocol::escape::escape_identifier(&format!(
+                "{}->entity_set",
+                self.entity_type
+            ))
+        );
+
+        conn.execute(&query, &[&self.id])
+            .await
+            .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+        conn.execute(
+            "DELETE FROM attribute_history.minerva_entity_set WHERE entity_id = $1",
+            &[&self.id],
+        )
+        .await
+        .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+        let query = "SELECT attribute_directory.materialize_curr_ptr(at) FROM attribute_directory.attribute_store at WHERE at::text = 'minerva_entity_set'";
+        conn.execute(query, &[])
+            .await
+            .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+        conn.execute("DELETE FROM entity.entity_set WHERE id = $1", &[&self.id])
+            .await
+            .map_err(|e| EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string())))?;
+
+        Ok(())
+    }
+
     pub async fn update(&self, conn: &mut Transaction<'_>) -> Result<EntitySet, EntitySetError> {
         let row = conn
             .query_one(
@@ -174,21 +838,42 @@ impl EntitySet {
         if self.entity_type != foundentitytype {
             incorrect_fields.push("entity_type".to_string());
             Err(EntitySetError::UnchangeableFields(incorrect_fields))
-        } else if self.entities.is_empty() {
+        } else if self.rules.is_empty() && self.entities.is_empty() {
             Err(EntitySetError::EmptyEntitySet)
         } else {
-            let entitieslist = self.entities.join("', '");
-
-            let query = format!(
-                "SELECT relation_directory.change_set_entities_guarded({}, ARRAY['{}'])",
-                self.id, entitieslist
-            );
-            println!("{query}");
-            let row = conn.query_one(&query, &[]).await.map_err(|e| {
-                EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string()))
-            })?;
+            let entities = if self.rules.is_empty() {
+                self.entities.clone()
+            } else {
+                evaluate_entity_set_rules(&*conn, &self.entity_type, &self.rules).await?
+            };
+
+            let missing_entities: Vec<String> = if self.rules.is_empty() {
+                let entitieslist = entities.join("', '");
+
+                let query = format!(
+                    "SELECT relation_directory.change_set_entities_guarded({}, ARRAY['{}'])",
+                    self.id, entitieslist
+                );
+                println!("{query}");
+                let row = conn.query_one(&query, &[]).await.map_err(|e| {
+                    EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string()))
+                })?;
+
+                row.get(0)
+            } else {
+                // The members of a dynamic entity set are existing entities by definition
+                conn.execute(
+                    "SELECT relation_directory.change_set_entities($1, $2::text[])",
+                    &[&self.id, &entities],
+                )
+                .await
+                .map_err(|e| {
+                    EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string()))
+                })?;
+
+                Vec::new()
+            };
 
-            let missing_entities: Vec<String> = row.get(0);
             if missing_entities.is_empty() {
                 let query = "SELECT attribute_directory.transfer_staged(at) FROM attribute_directory.attribute_store at WHERE at::text = 'minerva_entity_set'";
                 println!("{query}");
@@ -226,6 +911,17 @@ impl EntitySet {
                     EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string()))
                 })?;
 
+                conn.execute(
+                    "UPDATE directory.entity_set SET name = $2, \"group\" = $3, owner = $4 WHERE entity_id = $1",
+                    &[&self.id, &self.name, &self.group, &owner],
+                )
+                .await
+                .map_err(|e| {
+                    EntitySetError::DatabaseError(DatabaseError::from_msg(e.to_string()))
+                })?;
+
+                store_entity_set_rules(&*conn, self.id, &self.rules).await?;
+
                 let query = "SELECT attribute_directory.materialize_curr_ptr(at) FROM attribute_directory.attribute_store at WHERE at::text = 'minerva_entity_set'";
                 println!("{query}");
                 conn.execute(query, &[]).await.map_err(|e| {
@@ -249,7 +945,8 @@ impl EntitySet {
                     entity_type: newdata.get(2),
                     owner: newdata.get(3),
                     description: newdata.get(4),
-                    entities: self.entities.clone(),
+                    entities,
+                    rules: self.rules.clone(),
                     created: newdata.get(5),
                     modified: newdata.get(5),
                 };
@@ -357,9 +1054,15 @@ impl NewEntitySet {
             ));
         }
 
-        if self.entities.is_empty() {
-            return Err(EntitySetError::EmptyEntitySet);
-        }
+        let entities = if self.rules.is_empty() {
+            if self.entities.is_empty() {
+                return Err(EntitySetError::EmptyEntitySet);
+            }
+
+            self.entities.clone()
+        } else {
+            evaluate_entity_set_rules(&*conn, &self.entity_type, &self.rules).await?
+        };
 
         let query = concat!(
             "SELECT relation_directory.create_entity_set_guarded(",
@@ -375,7 +1078,7 @@ impl NewEntitySet {
                     &self.entity_type,
                     &self.owner,
                     &self.description,
-                    &self.entities,
+                    &entities,
                 ],
             )
             .await
@@ -389,14 +1092,19 @@ impl NewEntitySet {
                     &[&self.name, &self.owner,])
                 .await
                 .map_err(|e| DatabaseError::Default(e.to_string()))?;
+            let id: i32 = iddata.get(0);
+
+            store_entity_set_rules(&*conn, id, &self.rules).await?;
+
             let created_entity_set = EntitySet {
-                id: iddata.get(0),
+                id,
                 name: self.name.clone(),
                 group: self.group.clone(),
                 entity_type: self.entity_type.clone(),
                 owner: self.owner.clone(),
                 description: self.description.clone(),
-                entities: self.entities.clone(),
+                entities,
+                rules: self.rules.clone(),
                 created: iddata.get(1),
                 modified: iddata.get(2),
             };
@@ -481,6 +1189,300 @@ impl Display for CreatedEntitySet {
 #[typetag::serde]
 impl Changed for CreatedEntitySet {
     fn revert(&self) -> Option<Box<dyn Change>> {
-        None
+        Some(Box::new(RemoveEntitySet {
+            owner: self.owner.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+/// Update an existing entity set to match the definition. The entity set is looked up by owner
+/// and name, so no database id is required.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdateEntitySet {
+    pub entity_set: NewEntitySet,
+}
+
+impl fmt::Display for UpdateEntitySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "UpdateEntitySet({}:{})",
+            self.entity_set.owner, self.entity_set.name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for UpdateEntitySet {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let original =
+            load_entity_set_by_name(&tx, &self.entity_set.owner, &self.entity_set.name).await?;
+
+        let entity_set = EntitySet {
+            id: original.id,
+            name: self.entity_set.name.clone(),
+            group: self.entity_set.group.clone(),
+            entity_type: self.entity_set.entity_type.clone(),
+            owner: self.entity_set.owner.clone(),
+            description: self.entity_set.description.clone(),
+            entities: self.entity_set.entities.clone(),
+            rules: self.entity_set.rules.clone(),
+            created: original.created,
+            modified: original.modified,
+        };
+
+        entity_set.update(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(UpdatedEntitySet {
+            owner: self.entity_set.owner.clone(),
+            name: self.entity_set.name.clone(),
+            original: original.into(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdatedEntitySet {
+    pub owner: String,
+    pub name: String,
+    pub original: NewEntitySet,
+}
+
+impl Display for UpdatedEntitySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity set '{}'.'{}' updated", self.owner, self.name)
+    }
+}
+
+#[typetag::serde]
+impl Changed for UpdatedEntitySet {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(UpdateEntitySet {
+            entity_set: self.original.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemoveEntitySet {
+    pub owner: String,
+    pub name: String,
+}
+
+impl fmt::Display for RemoveEntitySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RemoveEntitySet({}:{})", self.owner, self.name)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RemoveEntitySet {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let entity_set = load_entity_set_by_name(&tx, &self.owner, &self.name).await?;
+
+        entity_set.delete(&mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(RemovedEntitySet {
+            entity_set: entity_set.into(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemovedEntitySet {
+    pub entity_set: NewEntitySet,
+}
+
+impl Display for RemovedEntitySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Entity set '{}'.'{}' removed",
+            self.entity_set.owner, self.entity_set.name
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RemovedEntitySet {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(CreateEntitySet {
+            entity_set: self.entity_set.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity_set(entities: &[&str]) -> NewEntitySet {
+        NewEntitySet {
+            name: "core".to_string(),
+            group: "network".to_string(),
+            entity_type: "node".to_string(),
+            owner: "ops".to_string(),
+            description: "Core nodes".to_string(),
+            entities: entities.iter().map(|e| e.to_string()).collect(),
+            rules: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn entity_set_diff_ignores_member_order() {
+        let my_entity_set = entity_set(&["node_1", "node_2"]);
+        let other_entity_set = entity_set(&["node_2", "node_1"]);
+
+        assert!(my_entity_set.diff(&other_entity_set).is_empty());
+    }
+
+    #[test]
+    fn entity_set_diff_changed_members() {
+        let my_entity_set = entity_set(&["node_1", "node_2"]);
+        let other_entity_set = entity_set(&["node_1", "node_3"]);
+
+        let changes = my_entity_set.diff(&other_entity_set);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "UpdateEntitySet(ops:core)");
+    }
+
+    #[test]
+    fn entity_set_diff_changed_entity_type() {
+        let my_entity_set = entity_set(&["node_1"]);
+        let mut other_entity_set = entity_set(&["node_1"]);
+        other_entity_set.entity_type = "cell".to_string();
+
+        let changes = my_entity_set.diff(&other_entity_set);
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].to_string(), "RemoveEntitySet(ops:core)");
+        assert_eq!(changes[1].to_string(), "CreateEntitySet(ops:core)");
+    }
+
+    #[test]
+    fn entity_set_revision_diff() {
+        let from = vec!["c1".to_string(), "c2".to_string(), "c3".to_string()];
+        let to = vec!["c4".to_string(), "c2".to_string(), "c1".to_string()];
+
+        let diff = diff_entity_set_members(&from, &to);
+
+        assert_eq!(diff.added, vec!["c4".to_string()]);
+        assert_eq!(diff.removed, vec!["c3".to_string()]);
+    }
+
+    #[test]
+    fn entity_set_rules_deserialization() {
+        let definition = r#"name: 5g-cells
+group: network
+entity_type: cell
+owner: ops
+description: All 5G cells of the north region
+rules:
+- type: attribute
+  attribute_store: hub_cell
+  attribute: technology
+  operator: eq
+  value: 5G
+- type: name_regex
+  pattern: ^north_
+- type: relation
+  relation: cell->region
+  target: north
+"#;
+
+        let entity_set: NewEntitySet = serde_yaml::from_str(definition).unwrap();
+
+        assert!(entity_set.entities.is_empty());
+        assert_eq!(entity_set.rules.len(), 3);
+        assert_eq!(
+            entity_set.rules[0],
+            EntitySetRule::Attribute {
+                attribute_store: "hub_cell".to_string(),
+                attribute: "technology".to_string(),
+                operator: AttributeOperator::Eq,
+                value: serde_json::Value::String("5G".to_string()),
+            }
+        );
+        assert_eq!(
+            entity_set.rules[1],
+            EntitySetRule::NameRegex {
+                pattern: "^north_".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn entity_set_rules_query_conditions() {
+        let rules = vec![
+            EntitySetRule::Attribute {
+                attribute_store: "hub_cell".to_string(),
+                attribute: "technology".to_string(),
+                operator: AttributeOperator::Eq,
+                value: serde_json::json!("5G') OR (SELECT pg_sleep(100)) IS NULL OR ('"),
+            },
+            EntitySetRule::Attribute {
+                attribute_store: "hub_cell".to_string(),
+                attribute: "band".to_string(),
+                operator: AttributeOperator::In,
+                value: serde_json::json!([700, 3500]),
+            },
+            EntitySetRule::Relation {
+                relation: "cell->region".to_string(),
+                target: "north".to_string(),
+            },
+        ];
+
+        let query = entity_set_rules_query("cell", &rules).unwrap();
+
+        assert!(query.starts_with(
+            "SELECT e.name FROM entity.\"cell\" e WHERE NOT directory.is_retired('cell', e.id) AND "
+        ));
+        assert!(query.contains(
+            "attribute.\"hub_cell\" a WHERE a.entity_id = e.id AND a.\"technology\" = '5G'') OR (SELECT pg_sleep(100)) IS NULL OR (''')"
+        ));
+        assert!(query.contains("a.\"band\" IN ('700', '3500')"));
+        assert!(query.contains("JOIN entity.\"region\" t ON t.id = r.target_id"));
+        assert!(pg_query::parse(&query).is_ok());
+    }
+
+    #[test]
+    fn entity_set_rules_query_invalid_relation() {
+        let rules = vec![EntitySetRule::Relation {
+            relation: "cell_region".to_string(),
+            target: "north".to_string(),
+        }];
+
+        assert!(matches!(
+            entity_set_rules_query("cell", &rules),
+            Err(EntitySetError::InvalidRule(_))
+        ));
+
+        let rules = vec![EntitySetRule::Attribute {
+            attribute_store: "hub_cell".to_string(),
+            attribute: "band".to_string(),
+            operator: AttributeOperator::In,
+            value: serde_json::json!(700),
+        }];
+
+        assert!(matches!(
+            entity_set_rules_query("cell", &rules),
+            Err(EntitySetError::InvalidRule(_))
+        ));
     }
 }
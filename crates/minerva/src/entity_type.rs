@@ -1,24 +1,182 @@
+use std::collections::HashMap;
 use std::fmt::{self, Display};
 use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 use glob::glob;
+use postgres_protocol::escape::{escape_identifier, escape_literal};
+use postgres_types::{ToSql, Type, to_sql_checked};
 use serde::{Deserialize, Serialize};
-use tokio_postgres::types::ToSql;
 use tokio_postgres::{Client, GenericClient};
 
 use crate::change::{ChangeResult, Changed};
 
 use super::change::Change;
-use super::error::{ConfigurationError, Error, RuntimeError};
+use super::error::{ConfigurationError, DatabaseError, Error, RuntimeError};
+use super::relation::Relation;
 
 pub type EntityTypeName = String;
 
-#[derive(Debug, Serialize, Deserialize, Clone, ToSql)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EntityType {
     pub name: EntityTypeName,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub primary_alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<EntityTypeAlias>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<EntityTypeParent>,
+}
+
+/// Composite value with the name and primary alias of an entity type, so that an `EntityType`
+/// can be passed as query parameter
+#[derive(Debug, ToSql)]
+#[postgres(name = "EntityType")]
+struct EntityTypeValue<'a> {
+    name: &'a str,
+    primary_alias: Option<&'a str>,
+}
+
+impl ToSql for EntityType {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut bytes::BytesMut,
+    ) -> Result<postgres_types::IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        EntityTypeValue {
+            name: &self.name,
+            primary_alias: self.primary_alias.as_deref(),
+        }
+        .to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        EntityTypeValue::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+/// Named alias type of an entity type, e.g. a vendor Id or an inventory key. The aliases are
+/// materialized from the `source` query, which must return the columns `entity_id` and `alias`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EntityTypeAlias {
+    pub name: String,
+    pub source: String,
+}
+
+/// Parent of an entity type in a hierarchy, e.g. site for cell, with the rule that derives the
+/// name of the parent entity from the name of the child entity. The relations to the parent and
+/// further ancestors are generated from this, see [`hierarchy_relations`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EntityTypeParent {
+    pub entity_type: EntityTypeName,
+    #[serde(flatten)]
+    pub name_rule: ParentNameRule,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum ParentNameRule {
+    /// The parent name is the distinguished name of the child up to and including the component
+    /// with the specified key, e.g. `Network=1,Site=2` for `Network=1,Site=2,Cell=3` with
+    /// component `Site`. Without a component, only the last component is removed.
+    DnPrefix {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        component: Option<String>,
+    },
+    /// The parent name is the first capture group of the regular expression
+    Regex { pattern: String },
+}
+
+/// Escape the characters that have a special meaning in a regular expression
+fn escape_regex(value: &str) -> String {
+    let mut result = String::new();
+
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            result.push('\\');
+        }
+
+        result.push(c);
+    }
+
+    result
+}
+
+impl ParentNameRule {
+    /// SQL expression for the parent name based on the child name in `name_column`, which
+    /// results in NULL when the name does not match.
+    #[must_use]
+    pub fn name_expression(&self, name_column: &str) -> String {
+        let pattern = match self {
+            ParentNameRule::DnPrefix { component: None } => "^(.*),[^,]*$".to_string(),
+            ParentNameRule::DnPrefix {
+                component: Some(component),
+            } => format!("^(.*(?:^|,){}=[^,]*)", escape_regex(component)),
+            ParentNameRule::Regex { pattern } => pattern.clone(),
+        };
+
+        format!(
+            "substring({name_column} from {})",
+            escape_literal(&pattern).trim_start()
+        )
+    }
+}
+
+/// Generate the relations from the entity types with a parent to their parent and all further
+/// ancestors, e.g. 'cell->site' and 'cell->region' for the hierarchy cell -> site -> region.
+/// The view of a relation joins the entity tables on the derived names of each level.
+#[must_use]
+pub fn hierarchy_relations(entity_types: &[EntityType]) -> Vec<Relation> {
+    let mut relations: Vec<Relation> = Vec::new();
+
+    for entity_type in entity_types {
+        let mut joins: Vec<String> = Vec::new();
+        let mut visited: Vec<&str> = vec![&entity_type.name];
+        let mut name_column = "e.name".to_string();
+        let mut current = entity_type;
+
+        while let Some(parent) = &current.parent {
+            // Stop at cycles in the hierarchy
+            if visited.contains(&parent.entity_type.as_str()) {
+                break;
+            }
+
+            visited.push(&parent.entity_type);
+
+            let alias = format!("p{}", joins.len() + 1);
+
+            joins.push(format!(
+                "JOIN entity.{} {alias} ON {alias}.name = {}",
+                escape_identifier(&parent.entity_type),
+                parent.name_rule.name_expression(&name_column)
+            ));
+
+            relations.push(Relation {
+                name: format!("{}->{}", entity_type.name, parent.entity_type),
+                query: Some(format!(
+                    "SELECT e.id AS source_id, {alias}.id AS target_id FROM entity.{} e {}",
+                    escape_identifier(&entity_type.name),
+                    joins.join(" ")
+                )),
+                procedure: None,
+                history: false,
+            });
+
+            name_column = format!("{alias}.name");
+
+            match entity_types
+                .iter()
+                .find(|candidate| candidate.name == parent.entity_type)
+            {
+                Some(parent_type) => current = parent_type,
+                None => break,
+            }
+        }
+    }
+
+    relations
 }
 
 impl fmt::Display for EntityType {
@@ -29,6 +187,46 @@ impl fmt::Display for EntityType {
 
 impl EntityType {
     pub fn diff(&self, other: &EntityType) -> Vec<Box<dyn Change + Send>> {
+        let mut changes = self.diff_primary_alias(other);
+
+        changes.extend(self.diff_aliases(other));
+
+        changes
+    }
+
+    fn diff_aliases(&self, other: &EntityType) -> Vec<Box<dyn Change + Send>> {
+        let mut changes: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        for other_alias in &other.aliases {
+            match self.aliases.iter().find(|a| a.name == other_alias.name) {
+                None => changes.push(Box::new(AddEntityTypeAlias {
+                    entity_type: self.name.clone(),
+                    alias: other_alias.clone(),
+                })),
+                Some(my_alias) => {
+                    if my_alias.source != other_alias.source {
+                        changes.push(Box::new(ChangeEntityTypeAlias {
+                            entity_type: self.name.clone(),
+                            alias: other_alias.clone(),
+                        }));
+                    }
+                }
+            }
+        }
+
+        for my_alias in &self.aliases {
+            if !other.aliases.iter().any(|a| a.name == my_alias.name) {
+                changes.push(Box::new(RemoveEntityTypeAlias {
+                    entity_type: self.name.clone(),
+                    name: my_alias.name.clone(),
+                }));
+            }
+        }
+
+        changes
+    }
+
+    fn diff_primary_alias(&self, other: &EntityType) -> Vec<Box<dyn Change + Send>> {
         match &self.primary_alias {
             None => match &other.primary_alias {
                 None => Vec::new(),
@@ -279,6 +477,273 @@ impl Changed for ChangedPrimaryAlias {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddEntityTypeAlias {
+    pub entity_type: String,
+    pub alias: EntityTypeAlias,
+}
+
+impl fmt::Display for AddEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AddEntityTypeAlias({}, {})",
+            self.entity_type, self.alias.name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for AddEntityTypeAlias {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let transaction = client.transaction().await?;
+
+        create_entity_type_alias(&transaction, &self.entity_type, &self.alias).await?;
+
+        transaction.commit().await?;
+
+        Ok(Box::new(AddedEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            name: self.alias.name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddedEntityTypeAlias {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl Display for AddedEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Added alias type '{}' to entity type '{}'",
+            self.name, self.entity_type
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for AddedEntityTypeAlias {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(RemoveEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            name: self.name.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemoveEntityTypeAlias {
+    pub entity_type: String,
+    pub name: String,
+}
+
+impl fmt::Display for RemoveEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RemoveEntityTypeAlias({}, {})",
+            self.entity_type, self.name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for RemoveEntityTypeAlias {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let transaction = client.transaction().await?;
+
+        let row = transaction
+            .query_one(
+                "SELECT (alias_directory.get_entity_type_alias($1, $2)).source",
+                &[&self.entity_type, &self.name],
+            )
+            .await?;
+
+        let source: Option<String> = row.get(0);
+
+        transaction
+            .execute(
+                "SELECT alias_directory.delete_entity_type_alias($1, $2)",
+                &[&self.entity_type, &self.name],
+            )
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(Box::new(RemovedEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            alias: EntityTypeAlias {
+                name: self.name.clone(),
+                source: source.unwrap_or_default(),
+            },
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct RemovedEntityTypeAlias {
+    pub entity_type: String,
+    pub alias: EntityTypeAlias,
+}
+
+impl Display for RemovedEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Removed alias type '{}' from entity type '{}'",
+            self.alias.name, self.entity_type
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for RemovedEntityTypeAlias {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(AddEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            alias: self.alias.clone(),
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ChangeEntityTypeAlias {
+    pub entity_type: String,
+    pub alias: EntityTypeAlias,
+}
+
+impl fmt::Display for ChangeEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ChangeEntityTypeAlias({}, {})",
+            self.entity_type, self.alias.name
+        )
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for ChangeEntityTypeAlias {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let transaction = client.transaction().await?;
+
+        let row = transaction
+            .query_one(
+                "SELECT (alias_directory.get_entity_type_alias($1, $2)).source",
+                &[&self.entity_type, &self.alias.name],
+            )
+            .await?;
+
+        let original_source: Option<String> = row.get(0);
+
+        transaction
+            .execute(
+                "SELECT alias_directory.change_entity_type_alias_source($1, $2, $3)",
+                &[&self.entity_type, &self.alias.name, &self.alias.source],
+            )
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(Box::new(ChangedEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            original: EntityTypeAlias {
+                name: self.alias.name.clone(),
+                source: original_source.unwrap_or_default(),
+            },
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct ChangedEntityTypeAlias {
+    pub entity_type: String,
+    pub original: EntityTypeAlias,
+}
+
+impl Display for ChangedEntityTypeAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Changed source of alias type '{}' of entity type '{}'",
+            self.original.name, self.entity_type
+        )
+    }
+}
+
+#[typetag::serde]
+impl Changed for ChangedEntityTypeAlias {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(ChangeEntityTypeAlias {
+            entity_type: self.entity_type.clone(),
+            alias: self.original.clone(),
+        }))
+    }
+}
+
+async fn create_entity_type_alias<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    alias: &EntityTypeAlias,
+) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute(
+            "SELECT alias_directory.create_entity_type_alias($1, $2, $3)",
+            &[&entity_type, &alias.name, &alias.source],
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Materialize the aliases of the alias types of an entity type from their source queries, or
+/// only of the alias type with the specified name. Returns the number of aliases per alias type.
+pub async fn materialize_entity_type_aliases<T: GenericClient + Sync>(
+    client: &T,
+    entity_type: &str,
+    name: Option<&str>,
+) -> Result<Vec<(String, i64)>, Error> {
+    let query = concat!(
+        "SELECT at.name::text, alias_directory.materialize_entity_type_alias(et.name, at.name) ",
+        "FROM alias_directory.alias_type at ",
+        "JOIN directory.entity_type et ON et.id = at.entity_type_id ",
+        "WHERE et.name = $1 AND ($2::text IS NULL OR at.name = $2) ",
+        "ORDER BY at.name"
+    );
+
+    let rows = client
+        .query(query, &[&entity_type, &name])
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Could not materialize aliases of entity type '{entity_type}': {e}"
+            ))
+        })?;
+
+    if rows.is_empty()
+        && let Some(name) = name
+    {
+        return Err(RuntimeError::from_msg(format!(
+            "No alias type '{name}' for entity type '{entity_type}'"
+        ))
+        .into());
+    }
+
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
 pub fn load_entity_type_from_file(path: &PathBuf) -> Result<EntityType, Error> {
     let f = std::fs::File::open(path).map_err(|e| {
         ConfigurationError::from_msg(format!(
@@ -378,7 +843,7 @@ pub enum CreateEntityTypeError {
     Database(String),
 }
 
-pub async fn create_entity_type<T: GenericClient>(
+pub async fn create_entity_type<T: GenericClient + Sync>(
     client: &mut T,
     entity_type: &EntityType,
 ) -> Result<(), CreateEntityTypeError> {
@@ -388,8 +853,20 @@ pub async fn create_entity_type<T: GenericClient>(
             &[&entity_type.name, &entity_type.primary_alias],
         )
         .await
-        .map(|_| ())
-        .map_err(|e| CreateEntityTypeError::Database(format!("Error creating entity type: {e}")))
+        .map_err(|e| CreateEntityTypeError::Database(format!("Error creating entity type: {e}")))?;
+
+    for alias in &entity_type.aliases {
+        create_entity_type_alias(client, &entity_type.name, alias)
+            .await
+            .map_err(|e| {
+                CreateEntityTypeError::Database(format!(
+                    "Error creating alias type '{}': {e}",
+                    alias.name
+                ))
+            })?;
+    }
+
+    Ok(())
 }
 
 pub fn load_entity_types_from(minerva_instance_root: &Path) -> impl Iterator<Item = EntityType> {
@@ -422,17 +899,152 @@ pub fn load_entity_types_from(minerva_instance_root: &Path) -> impl Iterator<Ite
 pub async fn load_entity_types<T: GenericClient + Send + Sync>(
     client: &mut T,
 ) -> Result<Vec<EntityType>, Error> {
+    let query = concat!(
+        "SELECT et.name, at.name::text, at.source ",
+        "FROM alias_directory.alias_type at ",
+        "JOIN directory.entity_type et ON et.id = at.entity_type_id ",
+        "ORDER BY at.name"
+    );
+
+    let mut aliases: HashMap<String, Vec<EntityTypeAlias>> = HashMap::new();
+
+    for row in client.query(query, &[]).await? {
+        let entity_type: String = row.get(0);
+
+        aliases
+            .entry(entity_type)
+            .or_default()
+            .push(EntityTypeAlias {
+                name: row.get(1),
+                source: row.get::<_, Option<String>>(2).unwrap_or_default(),
+            });
+    }
+
     let query = "SELECT name, primary_alias FROM directory.entity_type";
 
     let rows = client.query(query, &[]).await?;
 
     let entity_types: Vec<EntityType> = rows
         .iter()
-        .map(|row| EntityType {
-            name: row.get(0),
-            primary_alias: row.get(1),
+        .map(|row| {
+            let name: String = row.get(0);
+
+            EntityType {
+                aliases: aliases.remove(&name).unwrap_or_default(),
+                name,
+                primary_alias: row.get(1),
+                parent: None,
+            }
         })
         .collect();
 
     Ok(entity_types)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFINITION: &str = r#"name: cell
+aliases:
+- name: vendor_id
+  source: SELECT entity_id, vendor_id AS alias FROM attribute.hub_cell
+- name: inventory_key
+  source: SELECT entity_id, key AS alias FROM attribute.inventory_cell
+"#;
+
+    #[test]
+    fn entity_type_alias_diff() {
+        let my_entity_type: EntityType = serde_yaml::from_str(DEFINITION).unwrap();
+        let mut other_entity_type = my_entity_type.clone();
+
+        other_entity_type.aliases[0].source =
+            "SELECT entity_id, oss_id AS alias FROM attribute.hub_cell".to_string();
+        other_entity_type.aliases.remove(1);
+        other_entity_type.aliases.push(EntityTypeAlias {
+            name: "oss_name".to_string(),
+            source: "SELECT entity_id, oss_name AS alias FROM attribute.hub_cell".to_string(),
+        });
+
+        let changes: Vec<String> = my_entity_type
+            .diff(&other_entity_type)
+            .iter()
+            .map(|change| change.to_string())
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                "ChangeEntityTypeAlias(cell, vendor_id)",
+                "AddEntityTypeAlias(cell, oss_name)",
+                "RemoveEntityTypeAlias(cell, inventory_key)",
+            ]
+        );
+        assert!(my_entity_type.diff(&my_entity_type.clone()).is_empty());
+    }
+
+    const HIERARCHY: &str = r#"- name: cell
+  parent:
+    entity_type: site
+    rule: dn_prefix
+    component: Site
+- name: site
+  parent:
+    entity_type: region
+    rule: regex
+    pattern: ^Network=(\w+),
+- name: region
+"#;
+
+    #[test]
+    fn parent_name_expression() {
+        let rule = ParentNameRule::DnPrefix { component: None };
+
+        assert_eq!(
+            rule.name_expression("e.name"),
+            "substring(e.name from '^(.*),[^,]*$')"
+        );
+
+        let rule = ParentNameRule::DnPrefix {
+            component: Some("Site".to_string()),
+        };
+
+        assert_eq!(
+            rule.name_expression("e.name"),
+            "substring(e.name from '^(.*(?:^|,)Site=[^,]*)')"
+        );
+    }
+
+    #[test]
+    fn entity_type_hierarchy_relations() {
+        let entity_types: Vec<EntityType> = serde_yaml::from_str(HIERARCHY).unwrap();
+
+        let relations = hierarchy_relations(&entity_types);
+
+        let names: Vec<&str> = relations.iter().map(|r| r.name.as_str()).collect();
+
+        assert_eq!(names, vec!["cell->site", "cell->region", "site->region"]);
+        assert_eq!(
+            relations[1].query.as_deref().unwrap(),
+            concat!(
+                "SELECT e.id AS source_id, p2.id AS target_id FROM entity.\"cell\" e ",
+                "JOIN entity.\"site\" p1 ON p1.name = substring(e.name from '^(.*(?:^|,)Site=[^,]*)') ",
+                "JOIN entity.\"region\" p2 ON p2.name = substring(p1.name from E'^Network=(\\\\w+),')"
+            )
+        );
+
+        for relation in &relations {
+            assert!(pg_query::parse(relation.query.as_deref().unwrap()).is_ok());
+        }
+    }
+
+    #[test]
+    fn entity_type_hierarchy_cycle() {
+        let entity_types: Vec<EntityType> = serde_yaml::from_str(
+            "- name: a\n  parent:\n    entity_type: b\n    rule: dn_prefix\n- name: b\n  parent:\n    entity_type: a\n    rule: dn_prefix\n",
+        )
+        .unwrap();
+
+        assert_eq!(hierarchy_relations(&entity_types).len(), 2);
+    }
+}
@@ -9,6 +9,7 @@ use crate::change::Change;
 pub enum GraphNode {
     Table(String),
     TrendStorePart(String),
+    GeneratedTrend(String),
     AttributeStore(String),
     AttributeMaterialization(String),
     TrendViewMaterialization(String),
@@ -26,6 +27,7 @@ impl GraphNode {
         match self {
             GraphNode::Table(name) => name,
             GraphNode::TrendStorePart(name) => name,
+            GraphNode::GeneratedTrend(name) => name,
             GraphNode::AttributeStore(name) => name,
             GraphNode::AttributeMaterialization(name) => name,
             GraphNode::TrendViewMaterialization(name) => name,
@@ -45,6 +47,9 @@ impl Display for GraphNode {
             GraphNode::TrendStorePart(name) => {
                 write!(f, "TrendStorePart({name})")
             }
+            GraphNode::GeneratedTrend(name) => {
+                write!(f, "GeneratedTrend({name})")
+            }
             GraphNode::AttributeStore(name) => {
                 write!(f, "AttributeStore({name})")
             }
@@ -140,6 +145,7 @@ pub fn render_graph(graph: &Graph<GraphNode, String>) -> String {
             GraphNode::VirtualEntity(_) => "shape=box,style=\"rounded\"".to_string(),
             GraphNode::Relation(_) => "shape=box,style=\"rounded\"".to_string(),
             GraphNode::TrendStorePart(_) => "shape=box".to_string(),
+            GraphNode::GeneratedTrend(_) => "shape=ellipse".to_string(),
             GraphNode::TrendFunctionMaterialization(_) => "shape=box,style=\"rounded\"".to_string(),
             GraphNode::TrendViewMaterialization(_) => "shape=box,style=\"rounded\"".to_string(),
             GraphNode::AttributeStore(_) => "shape=box".to_string(),
@@ -169,6 +175,7 @@ pub fn render_graph_with_changes(
                 GraphNode::VirtualEntity(_) => "shape=box,style=\"rounded\"".to_string(),
                 GraphNode::Relation(_) => "shape=box,style=\"rounded\"".to_string(),
                 GraphNode::TrendStorePart(_) => "shape=box".to_string(),
+                GraphNode::GeneratedTrend(_) => "shape=ellipse".to_string(),
                 GraphNode::TrendFunctionMaterialization(_) => {
                     "shape=box,style=\"rounded\"".to_string()
                 }
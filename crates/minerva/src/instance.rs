@@ -13,9 +13,13 @@ use serde_json::Value;
 use std::time::Duration;
 use tokio_postgres::Client;
 
-use crate::attribute_materialization::AddAttributeMaterialization;
+use crate::attribute_materialization::{
+    AddAttributeMaterialization, AttributeMaterializationRef, RemoveAttributeMaterialization,
+};
 use crate::changes::trend_store::{RemoveTrendStore, RemoveTrendStorePart};
-use crate::entity_type::{AddEntityType, EntityType, load_entity_types, load_entity_types_from};
+use crate::entity_type::{
+    AddEntityType, EntityType, hierarchy_relations, load_entity_types, load_entity_types_from,
+};
 use crate::error::RuntimeError;
 use crate::graph::GraphNode;
 use crate::meas_value::DataType;
@@ -31,7 +35,9 @@ use super::attribute_store::{
 };
 use super::change::Change;
 use super::changes::trend_store::AddTrendStore;
-use super::entity_set::{EntitySet, load_entity_sets};
+use super::entity_set::{
+    CreateEntitySet, NewEntitySet, RemoveEntitySet, load_entity_set_from_file, load_entity_sets,
+};
 use super::error::Error;
 use super::notification_store::{
     AddNotificationStore, NotificationStore, load_notification_stores,
@@ -41,6 +47,7 @@ use super::trend_materialization::{
     AddTrendMaterialization, TrendMaterialization, load_materializations,
     load_materializations_from,
 };
+use super::trend_store::generated::validate_generated_trend;
 use super::trend_store::{
     TrendStore, TrendStoreDiffOptions, load_trend_store_from_file, load_trend_stores,
 };
@@ -215,7 +222,7 @@ pub struct MinervaInstance {
     pub trend_materializations: Vec<TrendMaterialization>,
     pub attribute_materializations: Vec<AttributeMaterialization>,
     pub triggers: Vec<Trigger>,
-    pub entity_sets: Vec<EntitySet>,
+    pub entity_sets: Vec<NewEntitySet>,
 }
 
 pub async fn initialize_from<K, V>(
@@ -329,7 +336,11 @@ impl MinervaInstance {
             .await
             .map_err(super::trigger::TriggerError::to_database_error)?;
 
-        let entity_sets = load_entity_sets(client).await?;
+        let entity_sets = load_entity_sets(client)
+            .await?
+            .into_iter()
+            .map(NewEntitySet::from)
+            .collect();
 
         Ok(MinervaInstance {
             entity_types,
@@ -346,18 +357,27 @@ impl MinervaInstance {
     }
 
     pub fn load_from(minerva_instance_root: &Path) -> Result<MinervaInstance, String> {
-        let entity_types = load_entity_types_from(minerva_instance_root).collect();
+        let entity_types: Vec<EntityType> = load_entity_types_from(minerva_instance_root).collect();
         let trend_stores = load_trend_stores_from(minerva_instance_root).collect();
         let notification_stores = load_notification_stores_from(minerva_instance_root).collect();
         let attribute_stores = load_attribute_stores_from(minerva_instance_root)
             .collect::<Result<Vec<AttributeStore>, String>>()?;
         let virtual_entities = load_virtual_entities_from(minerva_instance_root).collect();
-        let relations = load_relations_from(minerva_instance_root).collect();
+        let mut relations: Vec<Relation> = load_relations_from(minerva_instance_root).collect();
+
+        // Relations defined in files take precedence over the relations generated from the
+        // entity type hierarchy
+        for relation in hierarchy_relations(&entity_types) {
+            if !relations.iter().any(|r| r.name == relation.name) {
+                relations.push(relation);
+            }
+        }
+
         let trend_materializations = load_materializations_from(minerva_instance_root).collect();
         let attribute_materializations =
             load_attribute_materializations_from(minerva_instance_root).collect();
         let triggers = load_triggers_from(minerva_instance_root).collect();
-        let entity_sets: Vec<EntitySet> = vec![];
+        let entity_sets = load_entity_sets_from(minerva_instance_root).collect();
 
         Ok(MinervaInstance {
             entity_types,
@@ -392,6 +412,25 @@ impl MinervaInstance {
                 let node_idx = graph.add_node(node);
 
                 table_node_map.insert(format!("trend.{}", trend_store_part.name), node_idx);
+
+                // A generated trend depends on the trends of its part that its expression uses,
+                // which are listed in the label of the edge
+                for generated_trend in &trend_store_part.generated_trends {
+                    match validate_generated_trend(trend_store_part, generated_trend) {
+                        Err(e) => {
+                            error!("{e}");
+                        }
+                        Ok(trends) => {
+                            let generated_trend_node_idx =
+                                graph.add_node(GraphNode::GeneratedTrend(format!(
+                                    "{}.{}",
+                                    trend_store_part.name, generated_trend.name
+                                )));
+
+                            graph.add_edge(generated_trend_node_idx, node_idx, trends.join(", "));
+                        }
+                    }
+                }
             }
         }
 
@@ -496,14 +535,16 @@ impl MinervaInstance {
                         }
                     }
                 }
-                TrendMaterialization::Function(m) => {
+                TrendMaterialization::Function(_) | TrendMaterialization::Declarative(_) => {
                     let materialization_node_idx =
-                        graph.add_node(GraphNode::TrendFunctionMaterialization(m.name()));
-                    let table_name = format!("trend.{}", m.name());
+                        graph.add_node(GraphNode::TrendFunctionMaterialization(
+                            trend_materialization.name().to_string(),
+                        ));
+                    let table_name = format!("trend.{}", trend_materialization.name());
                     let source_index = table_node_map.get(&table_name).unwrap();
                     graph.add_edge(*source_index, materialization_node_idx, "".to_string());
 
-                    for source in &m.sources {
+                    for source in &trend_materialization.sources() {
                         match source {
                             TrendMaterializationSource::Trend(trend_source) => {
                                 let table_name = format!("trend.{}", trend_source.trend_store_part);
@@ -813,8 +854,8 @@ impl MinervaInstance {
                 .iter()
                 .find(|my_virtual_entity| my_virtual_entity.name == other_virtual_entity.name)
             {
-                Some(_my_virtual_entity) => {
-                    // changes.append(my_virtual_entity.diff(&other_virtual_entity));
+                Some(my_virtual_entity) => {
+                    changes.append(&mut my_virtual_entity.diff(other_virtual_entity));
                 }
                 None => {
                     changes.push(Box::new(AddVirtualEntity {
@@ -845,7 +886,9 @@ impl MinervaInstance {
                 .iter()
                 .find(|my_relation| my_relation.name == other_relation.name)
             {
-                Some(_my_relation) => {}
+                Some(my_relation) => {
+                    changes.append(&mut my_relation.diff(other_relation));
+                }
                 None => {
                     changes.push(Box::new(AddRelation {
                         relation: other_relation.clone(),
@@ -870,6 +913,79 @@ impl MinervaInstance {
             }
         }
 
+        // Check for changes in attribute materializations
+        for other_attribute_materialization in &other.attribute_materializations {
+            match self
+                .attribute_materializations
+                .iter()
+                .find(|my_attribute_materialization| {
+                    my_attribute_materialization.attribute_store
+                        == other_attribute_materialization.attribute_store
+                }) {
+                Some(my_attribute_materialization) => {
+                    changes.append(
+                        &mut my_attribute_materialization.diff(other_attribute_materialization),
+                    );
+                }
+                None => changes.push(Box::new(AddAttributeMaterialization::from(
+                    other_attribute_materialization.clone(),
+                ))),
+            }
+        }
+
+        for my_attribute_materialization in &self.attribute_materializations {
+            if !options.ignore_deletions
+                && !other
+                    .attribute_materializations
+                    .iter()
+                    .any(|other_attribute_materialization| {
+                        other_attribute_materialization.attribute_store
+                            == my_attribute_materialization.attribute_store
+                    })
+            {
+                changes.push(Box::new(RemoveAttributeMaterialization {
+                    attribute_materialization: AttributeMaterializationRef::from(
+                        my_attribute_materialization,
+                    ),
+                }))
+            }
+        }
+
+        // Check for changes in entity sets
+        for other_entity_set in &other.entity_sets {
+            match self.entity_sets.iter().find(|my_entity_set| {
+                my_entity_set.owner == other_entity_set.owner
+                    && my_entity_set.name == other_entity_set.name
+            }) {
+                Some(my_entity_set) => {
+                    changes.append(&mut my_entity_set.diff(other_entity_set));
+                }
+                None => changes.push(Box::new(CreateEntitySet {
+                    entity_set: other_entity_set.clone(),
+                })),
+            }
+        }
+
+        // Entity sets are mostly created by users at runtime, so only remove entity sets of
+        // owners that are managed by the definition
+        for my_entity_set in &self.entity_sets {
+            if !options.ignore_deletions
+                && other
+                    .entity_sets
+                    .iter()
+                    .any(|other_entity_set| other_entity_set.owner == my_entity_set.owner)
+                && !other.entity_sets.iter().any(|other_entity_set| {
+                    other_entity_set.owner == my_entity_set.owner
+                        && other_entity_set.name == my_entity_set.name
+                })
+            {
+                changes.push(Box::new(RemoveEntitySet {
+                    owner: my_entity_set.owner.clone(),
+                    name: my_entity_set.name.clone(),
+                }))
+            }
+        }
+
         // Check for changes in triggers
         for other_trigger in &other.triggers {
             match self
@@ -1213,6 +1329,33 @@ fn load_virtual_entities_from(minerva_instance_root: &Path) -> impl Iterator<Ite
     from_sql_definitions.chain(from_yaml_definitions)
 }
 
+fn load_entity_sets_from(minerva_instance_root: &Path) -> impl Iterator<Item = NewEntitySet> {
+    let yaml_paths = glob(&format!(
+        "{}/entity-set/*.yaml",
+        minerva_instance_root.to_string_lossy()
+    ))
+    .expect("Failed to read glob pattern");
+
+    let json_paths = glob(&format!(
+        "{}/entity-set/*.json",
+        minerva_instance_root.to_string_lossy()
+    ))
+    .expect("Failed to read glob pattern");
+
+    yaml_paths
+        .chain(json_paths)
+        .filter_map(|entry| match entry {
+            Ok(path) => match load_entity_set_from_file(&path) {
+                Ok(entity_set) => Some(entity_set),
+                Err(e) => {
+                    println!("Error loading entity set definition: {e}");
+                    None
+                }
+            },
+            Err(_) => None,
+        })
+}
+
 fn load_relations_from(minerva_instance_root: &Path) -> impl Iterator<Item = Relation> {
     let yaml_paths = glob(&format!(
         "{}/relation/*.yaml",
@@ -5,6 +5,7 @@ pub mod attribute_store;
 pub mod audit_log;
 pub mod change;
 pub mod changes;
+pub mod check;
 pub mod cluster;
 pub mod database;
 pub mod entity;
@@ -16,11 +17,14 @@ pub mod instance;
 pub mod interval;
 pub mod job;
 pub mod loading;
+pub mod maintenance;
 pub mod meas_value;
 pub mod notification_store;
 pub mod relation;
 pub mod schema;
+pub mod trend_export;
 pub mod trend_materialization;
+pub mod trend_query;
 pub mod trend_store;
 pub mod trigger;
 pub mod trigger_template;
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use tokio_postgres::Client;
 
-use crate::entity::CachingEntityMapping;
+use crate::entity::{CachingEntityMapping, EntityMapping};
 use crate::error::{Error, RuntimeError};
 use crate::interval::parse_interval;
 use crate::job::{end_job, start_job};
@@ -37,6 +37,12 @@ pub struct ParserConfig {
     pub trends: TrendsFrom,
     pub extra: Option<Value>,
     pub null_value: String,
+    /// Reject data for entities that are not registered yet instead of creating them
+    #[serde(default)]
+    pub strict_entities: bool,
+    /// Alias type of the entity type that the entity column contains instead of entity names
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity_alias: Option<String>,
 }
 
 pub async fn load_data<P: AsRef<Path>>(
@@ -96,7 +102,7 @@ pub async fn load_data<P: AsRef<Path>>(
 
     debug!("Started job with Id {job_id}");
 
-    let raw_data_package: Vec<(String, DateTime<chrono::Utc>, Vec<String>)> = csv_reader
+    let mut raw_data_package: Vec<(String, DateTime<chrono::Utc>, Vec<String>)> = csv_reader
         .records()
         .map(|record| {
             let record = record.unwrap();
@@ -145,7 +151,25 @@ pub async fn load_data<P: AsRef<Path>>(
         }
     }
 
-    let entity_mapping = CachingEntityMapping::new(100);
+    let entity_mapping = CachingEntityMapping::new(100).with_strict(parser_config.strict_entities);
+
+    if let Some(alias_type) = &parser_config.entity_alias {
+        let aliases: Vec<String> = raw_data_package
+            .iter()
+            .map(|(alias, _, _)| alias.clone())
+            .collect();
+
+        let names = entity_mapping
+            .aliases_to_names(client, &parser_config.entity_type, alias_type, &aliases)
+            .await
+            .map_err(|e| {
+                RuntimeError::from_msg(format!("Could not map '{alias_type}' aliases: {e}"))
+            })?;
+
+        for (record, name) in raw_data_package.iter_mut().zip(names) {
+            record.0 = name;
+        }
+    }
 
     trend_store
         .store_raw(
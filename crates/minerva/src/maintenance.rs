@@ -0,0 +1,388 @@
+use std::fmt;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use tokio_postgres::{Client, GenericClient};
+
+use crate::attribute_store::compact::compact_attribute_store_by_id;
+use crate::attribute_store::materialize_curr_ptr::materialize_curr_ptr;
+use crate::change::Change;
+use crate::changes::trend_store::RemoveTrendStorePartition;
+use crate::error::{DatabaseError, Error, RuntimeError};
+use crate::interval::parse_interval;
+use crate::trend_store::retention::expired_partitions;
+use crate::trend_store::{columnarize_partitions, create_partitions_for_trend_store};
+
+/// Key of the session level advisory lock that is held by the maintenance service instance
+/// that runs the tasks, so that only one instance acts at a time.
+pub const MAINTENANCE_LOCK_KEY: i64 = 0x006d_696e_6572_7661;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceTask {
+    CreatePartitions,
+    ColumnarizePartitions,
+    RemoveExpiredPartitions,
+    CompactAttributeStores,
+    MaterializeCurrPtr,
+    RemoveExpiredChangeEvents,
+    RemoveExpiredEntitySetRevisions,
+}
+
+impl MaintenanceTask {
+    /// All tasks in the order in which they are run
+    pub const ALL: [MaintenanceTask; 7] = [
+        MaintenanceTask::CreatePartitions,
+        MaintenanceTask::ColumnarizePartitions,
+        MaintenanceTask::RemoveExpiredPartitions,
+        MaintenanceTask::CompactAttributeStores,
+        MaintenanceTask::MaterializeCurrPtr,
+        MaintenanceTask::RemoveExpiredChangeEvents,
+        MaintenanceTask::RemoveExpiredEntitySetRevisions,
+    ];
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaintenanceTask::CreatePartitions => "create-partitions",
+            MaintenanceTask::ColumnarizePartitions => "columnarize-partitions",
+            MaintenanceTask::RemoveExpiredPartitions => "remove-expired-partitions",
+            MaintenanceTask::CompactAttributeStores => "compact-attribute-stores",
+            MaintenanceTask::MaterializeCurrPtr => "materialize-curr-ptr",
+            MaintenanceTask::RemoveExpiredChangeEvents => "remove-expired-change-events",
+            MaintenanceTask::RemoveExpiredEntitySetRevisions => {
+                "remove-expired-entity-set-revisions"
+            }
+        }
+    }
+}
+
+impl fmt::Display for MaintenanceTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub struct MaintenanceConfig {
+    /// Number of partitions to create ahead of the current time for each trend store
+    pub lookahead_partitions: u32,
+    /// Minimum period to create partitions for ahead of the current time
+    pub min_lookahead: Duration,
+    /// Period to keep the events in `directory.change_event` for
+    pub change_event_retention: Duration,
+    pub tasks: Vec<MaintenanceTask>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        MaintenanceConfig {
+            lookahead_partitions: 2,
+            min_lookahead: Duration::from_secs(3 * 86400),
+            change_event_retention: Duration::from_secs(7 * 86400),
+            tasks: MaintenanceTask::ALL.to_vec(),
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    /// Period to create partitions for ahead of the current time for a trend store with the
+    /// specified partition size
+    #[must_use]
+    pub fn lookahead(&self, partition_size: Duration) -> Duration {
+        (partition_size * self.lookahead_partitions).max(self.min_lookahead)
+    }
+}
+
+/// Result of the last run of a maintenance task
+pub struct MaintenanceStatus {
+    pub task: String,
+    pub instance: String,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub success: bool,
+    pub message: Option<String>,
+}
+
+/// Try to acquire the maintenance lock for the session of the client. Returns false when
+/// another session holds the lock.
+///
+/// # Errors
+///
+/// Will return `Err` if the lock cannot be requested.
+pub async fn try_acquire_maintenance_lock<T: GenericClient>(client: &T) -> Result<bool, Error> {
+    let row = client
+        .query_one("SELECT pg_try_advisory_lock($1)", &[&MAINTENANCE_LOCK_KEY])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not acquire maintenance lock: {e}")))?;
+
+    Ok(row.get(0))
+}
+
+/// # Errors
+///
+/// Will return `Err` if the lock cannot be released.
+pub async fn release_maintenance_lock<T: GenericClient>(client: &T) -> Result<(), Error> {
+    client
+        .execute("SELECT pg_advisory_unlock($1)", &[&MAINTENANCE_LOCK_KEY])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not release maintenance lock: {e}")))?;
+
+    Ok(())
+}
+
+/// Run a maintenance task and return a summary of what was done
+///
+/// # Errors
+///
+/// Will return `Err` if the task fails.
+pub async fn run_maintenance_task(
+    client: &mut Client,
+    task: MaintenanceTask,
+    config: &MaintenanceConfig,
+) -> Result<String, Error> {
+    match task {
+        MaintenanceTask::CreatePartitions => create_partitions_ahead(client, config).await,
+        MaintenanceTask::ColumnarizePartitions => {
+            columnarize_partitions(client).await?;
+
+            Ok("Columnarized partitions".to_string())
+        }
+        MaintenanceTask::RemoveExpiredPartitions => remove_expired_partitions(client).await,
+        MaintenanceTask::CompactAttributeStores => compact_attribute_stores(client).await,
+        MaintenanceTask::MaterializeCurrPtr => materialize_modified_curr_ptr(client).await,
+        MaintenanceTask::RemoveExpiredChangeEvents => {
+            remove_expired_change_events(client, config).await
+        }
+        MaintenanceTask::RemoveExpiredEntitySetRevisions => {
+            remove_expired_entity_set_revisions(client).await
+        }
+    }
+}
+
+async fn create_partitions_ahead(
+    client: &mut Client,
+    config: &MaintenanceConfig,
+) -> Result<String, Error> {
+    let rows = client
+        .query(
+            "SELECT id, partition_size::text FROM trend_directory.trend_store",
+            &[],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Error loading trend stores: {e}")))?;
+
+    for row in &rows {
+        let trend_store_id: i32 = row.get(0);
+        let partition_size = parse_interval(row.get(1))?;
+        let lookahead = config.lookahead(partition_size);
+
+        debug!(
+            "Creating partitions for trend store {trend_store_id} with lookahead {}",
+            humantime::format_duration(lookahead)
+        );
+
+        create_partitions_for_trend_store(client, trend_store_id, lookahead).await?;
+    }
+
+    Ok(format!("Checked partitions of {} trend stores", rows.len()))
+}
+
+async fn remove_expired_partitions(client: &mut Client) -> Result<String, Error> {
+    let partitions = expired_partitions(client, None).await?;
+
+    for partition in &partitions {
+        let change = RemoveTrendStorePartition {
+            partition: partition.name.clone(),
+            archive: None,
+        };
+
+        let changed = change.apply(client).await?;
+
+        info!("{changed}");
+    }
+
+    Ok(format!("Removed {} expired partitions", partitions.len()))
+}
+
+async fn compact_attribute_stores(client: &mut Client) -> Result<String, Error> {
+    let query = concat!(
+        "SELECT ast.id FROM attribute_directory.attribute_store ast ",
+        "LEFT JOIN attribute_directory.attribute_store_compacted astc ON astc.attribute_store_id = ast.id ",
+        "JOIN attribute_directory.attribute_store_modified astm ON astm.attribute_store_id = ast.id ",
+        "WHERE astc.compacted IS NULL OR astm.modified <> astc.compacted"
+    );
+
+    let rows = client.query(query, &[]).await.map_err(|e| {
+        DatabaseError::from_msg(format!("Error loading modified attribute stores: {e}"))
+    })?;
+
+    let mut record_count: u64 = 0;
+
+    for row in &rows {
+        let id: i32 = row.get(0);
+
+        let transaction = client.transaction().await?;
+
+        let result = compact_attribute_store_by_id(&transaction, id, None).await?;
+
+        // When any attribute data is compacted, also update the curr-ptr data
+        if result.record_count > 0 {
+            materialize_curr_ptr(&transaction, id).await?;
+        }
+
+        transaction.commit().await?;
+
+        info!("{result}");
+
+        record_count += result.record_count;
+    }
+
+    Ok(format!(
+        "Compacted {record_count} records of {} attribute stores",
+        rows.len()
+    ))
+}
+
+async fn materialize_modified_curr_ptr(client: &mut Client) -> Result<String, Error> {
+    let query = concat!(
+        "SELECT ast.id FROM attribute_directory.attribute_store ast ",
+        "LEFT JOIN attribute_directory.attribute_store_curr_materialized ascm ON ascm.attribute_store_id = ast.id ",
+        "LEFT JOIN attribute_directory.attribute_store_modified asm ON asm.attribute_store_id = ast.id ",
+        "WHERE asm.modified <> ascm.materialized OR (ascm.materialized IS NULL AND asm.modified IS NOT NULL)"
+    );
+
+    let rows = client.query(query, &[]).await.map_err(|e| {
+        DatabaseError::from_msg(format!("Error loading modified attribute stores: {e}"))
+    })?;
+
+    for row in &rows {
+        let id: i32 = row.get(0);
+
+        let result = materialize_curr_ptr(client, id).await?;
+
+        info!("{result}");
+    }
+
+    Ok(format!(
+        "Materialized curr-ptr tables of {} attribute stores",
+        rows.len()
+    ))
+}
+
+async fn remove_expired_change_events(
+    client: &mut Client,
+    config: &MaintenanceConfig,
+) -> Result<String, Error> {
+    let retention = humantime::format_duration(config.change_event_retention).to_string();
+
+    let removed_count = client
+        .execute(
+            "DELETE FROM directory.change_event WHERE timestamp < now() - $1::text::interval",
+            &[&retention],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Error removing change events: {e}")))?;
+
+    Ok(format!("Removed {removed_count} expired change events"))
+}
+
+async fn remove_expired_entity_set_revisions(client: &mut Client) -> Result<String, Error> {
+    let row = client
+        .query_one("SELECT entity_set.remove_expired_revisions()", &[])
+        .await
+        .map_err(|e| {
+            DatabaseError::from_msg(format!("Error removing entity set revisions: {e}"))
+        })?;
+
+    let removed_count: i64 = row.get(0);
+
+    Ok(format!(
+        "Removed {removed_count} members of expired entity set revisions"
+    ))
+}
+
+/// Record the result of a run of a maintenance task, replacing the previous result
+///
+/// # Errors
+///
+/// Will return `Err` if the status cannot be stored.
+pub async fn record_maintenance_status<T: GenericClient>(
+    client: &T,
+    task: MaintenanceTask,
+    instance: &str,
+    started: DateTime<Utc>,
+    result: &Result<String, Error>,
+) -> Result<(), Error> {
+    let query = concat!(
+        "INSERT INTO system.maintenance_status(task, instance, started, finished, success, message) ",
+        "VALUES ($1, $2, $3, now(), $4, $5) ",
+        "ON CONFLICT (task) DO UPDATE SET ",
+        "instance = EXCLUDED.instance, started = EXCLUDED.started, finished = EXCLUDED.finished, ",
+        "success = EXCLUDED.success, message = EXCLUDED.message"
+    );
+
+    let message = match result {
+        Ok(summary) => summary.clone(),
+        Err(e) => e.to_string(),
+    };
+
+    client
+        .execute(
+            query,
+            &[&task.name(), &instance, &started, &result.is_ok(), &message],
+        )
+        .await
+        .map_err(|e| {
+            RuntimeError::from_msg(format!("Could not record status of task '{task}': {e}"))
+        })?;
+
+    Ok(())
+}
+
+/// # Errors
+///
+/// Will return `Err` if the status cannot be loaded.
+pub async fn load_maintenance_status<T: GenericClient>(
+    client: &T,
+) -> Result<Vec<MaintenanceStatus>, Error> {
+    let query = concat!(
+        "SELECT task, instance, started, finished, success, message ",
+        "FROM system.maintenance_status ORDER BY task"
+    );
+
+    let rows = client
+        .query(query, &[])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load maintenance status: {e}")))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| MaintenanceStatus {
+            task: row.get(0),
+            instance: row.get(1),
+            started: row.get(2),
+            finished: row.get(3),
+            success: row.get(4),
+            message: row.get(5),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maintenance_lookahead() {
+        let config = MaintenanceConfig::default();
+
+        assert_eq!(
+            config.lookahead(Duration::from_secs(86400)),
+            Duration::from_secs(3 * 86400)
+        );
+        assert_eq!(
+            config.lookahead(Duration::from_secs(7 * 86400)),
+            Duration::from_secs(14 * 86400)
+        );
+    }
+}
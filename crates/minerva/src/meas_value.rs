@@ -117,6 +117,10 @@ pub enum MeasValue {
     TextArray(Vec<String>),
     Timestamp(chrono::DateTime<chrono::Utc>),
     Numeric(Option<Decimal>),
+    Boolean(Option<bool>),
+    NumericArray(Option<Vec<Decimal>>),
+    /// NULL value of a data type that has no variant with an optional value
+    Null(DataType),
 }
 
 #[must_use]
@@ -281,6 +285,19 @@ impl MeasValue {
                 })),
             },
             MeasValue::Numeric(v) => map_numeric(v, data_type),
+            MeasValue::Boolean(v) => match data_type {
+                DataType::Boolean => Ok(MeasValue::Boolean(*v)),
+                _ => Err(Error::Runtime(crate::error::RuntimeError {
+                    msg: format!("No mapping defined for {v:?} -> {data_type}"),
+                })),
+            },
+            MeasValue::NumericArray(v) => match data_type {
+                DataType::NumericArray => Ok(MeasValue::NumericArray(v.clone())),
+                _ => Err(Error::Runtime(crate::error::RuntimeError {
+                    msg: format!("No mapping defined for {v:?} -> {data_type}"),
+                })),
+            },
+            MeasValue::Null(_) => Ok(MeasValue::Null(data_type)),
         }
     }
 }
@@ -315,6 +332,12 @@ impl fmt::Display for MeasValue {
                 Some(i) => write!(f, "{i}"),
                 None => write!(f, "NULL"),
             },
+            MeasValue::Boolean(v) => match v {
+                Some(b) => write!(f, "{b}"),
+                None => write!(f, "NULL"),
+            },
+            MeasValue::NumericArray(_) => write!(f, "ARRAY(numeric)"),
+            MeasValue::Null(_) => write!(f, "NULL"),
         }
     }
 }
@@ -338,6 +361,9 @@ impl ToSql for MeasValue {
             MeasValue::TextArray(x) => x.to_sql(ty, out),
             MeasValue::Timestamp(x) => x.to_sql(ty, out),
             MeasValue::Numeric(x) => x.to_sql(ty, out),
+            MeasValue::Boolean(x) => x.to_sql(ty, out),
+            MeasValue::NumericArray(x) => x.to_sql(ty, out),
+            MeasValue::Null(_) => Ok(postgres_types::IsNull::Yes),
         }
     }
 
@@ -363,6 +389,9 @@ impl ToSql for MeasValue {
             MeasValue::TextArray(x) => x.to_sql_checked(ty, out),
             MeasValue::Timestamp(x) => x.to_sql_checked(ty, out),
             MeasValue::Numeric(x) => x.to_sql_checked(ty, out),
+            MeasValue::Boolean(x) => x.to_sql_checked(ty, out),
+            MeasValue::NumericArray(x) => x.to_sql_checked(ty, out),
+            MeasValue::Null(_) => Ok(postgres_types::IsNull::Yes),
         }
     }
 }
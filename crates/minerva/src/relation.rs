@@ -1,13 +1,14 @@
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{self, Display};
 use std::path::PathBuf;
 
 use async_trait::async_trait;
-use postgres_protocol::escape::escape_identifier;
+use postgres_protocol::escape::{escape_identifier, escape_literal};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio_postgres::{Client, GenericClient, Transaction};
 
-use crate::change::{ChangeResult, Changed};
+use crate::change::{ChangeResult, Changed, MinervaObjectRef};
 use crate::error::postgres_error_to_string;
 
 use super::change::Change;
@@ -18,6 +19,16 @@ use super::error::{ConfigurationError, DatabaseError, Error, RuntimeError};
 pub struct Relation {
     pub name: String,
     pub query: Option<String>,
+    /// PL/pgSQL body of a `relation_def.<name>()` procedure that populates the relation table.
+    /// This is used for relations that are too complex to define in one view, and can commit
+    /// between stages to prevent long running locks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub procedure: Option<String>,
+    /// Keep the history of the relation in `relation_history.<name>` with a validity interval
+    /// per source/target pair, so that data can be combined with the relation as it was at the
+    /// data timestamp.
+    #[serde(default)]
+    pub history: bool,
 }
 
 impl fmt::Display for Relation {
@@ -26,6 +37,66 @@ impl fmt::Display for Relation {
     }
 }
 
+impl Relation {
+    #[must_use]
+    pub fn diff(&self, other: &Relation) -> Vec<Box<dyn Change + Send>> {
+        let mut changes: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        // A relation without a query is maintained externally, so there is no view to update
+        if let Some(other_query) = &other.query {
+            let query_equals = match &self.query {
+                Some(my_query) => sql_equals(my_query, other_query),
+                None => false,
+            };
+
+            if !query_equals {
+                changes.push(Box::new(UpdateRelationView {
+                    relation_name: other.name.clone(),
+                    view_src: other_query.clone(),
+                }));
+            }
+        } else if let Some(other_procedure) = &other.procedure {
+            let procedure_equals = match &self.procedure {
+                Some(my_procedure) => {
+                    self.query.is_none() && plpgsql_equals(my_procedure, other_procedure)
+                }
+                None => false,
+            };
+
+            if !procedure_equals {
+                changes.push(Box::new(UpdateRelationProcedure {
+                    relation_name: other.name.clone(),
+                    procedure_src: other_procedure.clone(),
+                }));
+            }
+        }
+
+        // History is never removed by a diff, because it can not be regenerated
+        if other.history && !self.history {
+            changes.push(Box::new(AddRelationHistory {
+                relation_name: other.name.clone(),
+            }));
+        }
+
+        changes
+    }
+}
+
+/// Compare procedure bodies ignoring differences in whitespace
+fn plpgsql_equals(first: &str, second: &str) -> bool {
+    first.split_whitespace().eq(second.split_whitespace())
+}
+
+/// Compare SQL queries by their fingerprint, so that formatting differences are ignored
+pub(crate) fn sql_equals(first: &str, second: &str) -> bool {
+    match (pg_query::fingerprint(first), pg_query::fingerprint(second)) {
+        (Ok(first_fingerprint), Ok(second_fingerprint)) => {
+            first_fingerprint.value == second_fingerprint.value
+        }
+        _ => false,
+    }
+}
+
 pub fn load_relation_from_file(path: &PathBuf) -> Result<Relation, Error> {
     let f = std::fs::File::open(path).map_err(|e| {
         ConfigurationError::from_msg(format!(
@@ -69,7 +140,8 @@ pub async fn load_relation_from_db<T: GenericClient + Send + Sync>(
     name: &str,
 ) -> Result<Relation, String> {
     let query = concat!(
-        "select r.name, pg_get_viewdef(ev_class) ",
+        "select r.name, pg_get_viewdef(ev_class), proc_def.prosrc, ",
+        "to_regclass(format('relation_history.%I', r.name)) is not null ",
         "from relation_directory.\"type\" r ",
         "left join (",
         "select c.relname as view_name, ev_class ",
@@ -77,6 +149,12 @@ pub async fn load_relation_from_db<T: GenericClient + Send + Sync>(
         "join pg_rewrite rw on c.oid = rw.ev_class ",
         "join pg_namespace nsp on nsp.oid = c.relnamespace and nspname = 'relation_def'",
         ") view_def on view_def.view_name = r.name ",
+        "left join (",
+        "select p.proname, p.prosrc ",
+        "from pg_proc p ",
+        "join pg_namespace nsp on nsp.oid = p.pronamespace and nspname = 'relation_def' ",
+        "where p.prokind = 'p' and p.pronargs = 0",
+        ") proc_def on proc_def.proname = r.name ",
         "where r.name = $1",
     );
 
@@ -91,6 +169,8 @@ pub async fn load_relation_from_db<T: GenericClient + Send + Sync>(
     let relation = Relation {
         name: row.get(0),
         query: row.get(1),
+        procedure: row.get(2),
+        history: row.get(3),
     };
 
     Ok(relation)
@@ -100,14 +180,21 @@ pub async fn load_relations_from_db<T: GenericClient + Send + Sync>(
     conn: &mut T,
 ) -> Result<Vec<Relation>, String> {
     let query = concat!(
-        "select r.name, pg_get_viewdef(ev_class) ",
+        "select r.name, pg_get_viewdef(ev_class), proc_def.prosrc, ",
+        "to_regclass(format('relation_history.%I', r.name)) is not null ",
         "from relation_directory.\"type\" r ",
         "left join (",
         "select c.relname as view_name, ev_class ",
         "from pg_class c ",
         "join pg_rewrite rw on c.oid = rw.ev_class ",
         "join pg_namespace nsp on nsp.oid = c.relnamespace and nspname = 'relation_def'",
-        ") view_def on view_def.view_name = r.name",
+        ") view_def on view_def.view_name = r.name ",
+        "left join (",
+        "select p.proname, p.prosrc ",
+        "from pg_proc p ",
+        "join pg_namespace nsp on nsp.oid = p.pronamespace and nspname = 'relation_def' ",
+        "where p.prokind = 'p' and p.pronargs = 0",
+        ") proc_def on proc_def.proname = r.name",
     );
 
     let rows = conn.query(query, &[]).await.unwrap();
@@ -117,6 +204,8 @@ pub async fn load_relations_from_db<T: GenericClient + Send + Sync>(
         .map(|row| Relation {
             name: row.get(0),
             query: row.get(1),
+            procedure: row.get(2),
+            history: row.get(3),
         })
         .collect();
 
@@ -195,7 +284,9 @@ impl fmt::Display for UpdateRelationView {
 #[typetag::serde]
 impl Change for UpdateRelationView {
     async fn apply(&self, client: &mut Client) -> ChangeResult {
-        let tx = client.transaction().await?;
+        let mut tx = client.transaction().await?;
+
+        let original_relation = load_relation_from_db(&mut tx, &self.relation_name).await?;
 
         let query = format!(
             "CREATE OR REPLACE VIEW relation_def.{} AS {}",
@@ -211,14 +302,21 @@ impl Change for UpdateRelationView {
 
         Ok(Box::new(UpdatedRelation {
             relation_name: self.relation_name.clone(),
+            original_view_src: original_relation.query,
         }))
     }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::Relation(self.relation_name.clone()))
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub struct UpdatedRelation {
     pub relation_name: String,
+    #[serde(default)]
+    pub original_view_src: Option<String>,
 }
 
 impl Display for UpdatedRelation {
@@ -229,6 +327,153 @@ impl Display for UpdatedRelation {
 
 #[typetag::serde]
 impl Changed for UpdatedRelation {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        self.original_view_src.as_ref().map(|view_src| {
+            Box::new(UpdateRelationView {
+                relation_name: self.relation_name.clone(),
+                view_src: view_src.clone(),
+            }) as Box<dyn Change>
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdateRelationProcedure {
+    pub relation_name: String,
+    pub procedure_src: String,
+}
+
+impl fmt::Display for UpdateRelationProcedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UpdateRelationProcedure({})", self.relation_name)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for UpdateRelationProcedure {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let original_relation = load_relation_from_db(&mut tx, &self.relation_name).await?;
+
+        create_relation_procedure(&mut tx, &self.relation_name, &self.procedure_src)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!("Error updating relation procedure: {e}"))
+            })?;
+
+        // A view takes precedence over the procedure, so it must be removed for the procedure to
+        // be used.
+        let query = format!(
+            "DROP VIEW IF EXISTS relation_def.{}",
+            escape_identifier(&self.relation_name)
+        );
+
+        tx.execute(&query, &[])
+            .await
+            .map_err(|e| DatabaseError::from_msg(format!("Error dropping relation view: {e}")))?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(UpdatedRelationProcedure {
+            relation_name: self.relation_name.clone(),
+            original_view_src: original_relation.query,
+            original_procedure_src: original_relation.procedure,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::Relation(self.relation_name.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdatedRelationProcedure {
+    pub relation_name: String,
+    pub original_view_src: Option<String>,
+    pub original_procedure_src: Option<String>,
+}
+
+impl Display for UpdatedRelationProcedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Updated relation procedure {}", self.relation_name)
+    }
+}
+
+#[typetag::serde]
+impl Changed for UpdatedRelationProcedure {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        if let Some(view_src) = &self.original_view_src {
+            Some(Box::new(UpdateRelationView {
+                relation_name: self.relation_name.clone(),
+                view_src: view_src.clone(),
+            }))
+        } else {
+            self.original_procedure_src.as_ref().map(|procedure_src| {
+                Box::new(UpdateRelationProcedure {
+                    relation_name: self.relation_name.clone(),
+                    procedure_src: procedure_src.clone(),
+                }) as Box<dyn Change>
+            })
+        }
+    }
+}
+
+/// Start keeping the history of an existing relation. The current content of the relation is
+/// used as the initial history.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddRelationHistory {
+    pub relation_name: String,
+}
+
+impl fmt::Display for AddRelationHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AddRelationHistory({})", self.relation_name)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for AddRelationHistory {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        create_relation_history(&mut tx, &self.relation_name)
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!("Error creating relation history: {e}"))
+            })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(AddedRelationHistory {
+            relation_name: self.relation_name.clone(),
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::Relation(self.relation_name.clone()))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct AddedRelationHistory {
+    pub relation_name: String,
+}
+
+impl Display for AddedRelationHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Added history for relation '{}'", self.relation_name)
+    }
+}
+
+#[typetag::serde]
+impl Changed for AddedRelationHistory {
     fn revert(&self) -> Option<Box<dyn Change>> {
         None
     }
@@ -236,51 +481,410 @@ impl Changed for UpdatedRelation {
 
 #[derive(Error, Debug)]
 pub enum MaterializeRelationError {
-    #[error("Could not delete current relations: {source}")]
-    Delete {
+    #[error("Could not merge relations: {source}")]
+    Merge {
+        #[source]
+        source: tokio_postgres::Error,
+    },
+    #[error("Could not determine changed relations: {source}")]
+    Snapshot {
+        #[source]
+        source: tokio_postgres::Error,
+    },
+    #[error("Could not execute relation procedure: {source}")]
+    Procedure {
+        #[source]
+        source: tokio_postgres::Error,
+    },
+    #[error("Could not update relation history: {source}")]
+    History {
         #[source]
         source: tokio_postgres::Error,
     },
-    #[error("Could not insert new relations: {source}")]
-    Insert {
+    #[error("Could not run relation materialization transaction: {source}")]
+    Transaction {
         #[source]
         source: tokio_postgres::Error,
     },
 }
 
+pub async fn relation_has_history<T: GenericClient + Sync>(
+    client: &T,
+    name: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    let row = client
+        .query_one(
+            "SELECT to_regclass(format('relation_history.%I', $1::text)) IS NOT NULL",
+            &[&name],
+        )
+        .await?;
+
+    Ok(row.get(0))
+}
+
+/// Bring the history of a relation in line with the current content of the relation table. Pairs
+/// that no longer exist are closed and new pairs are opened at the current transaction time.
+async fn update_relation_history<T: GenericClient + Sync>(
+    client: &T,
+    name: &str,
+) -> Result<(), MaterializeRelationError> {
+    let has_history = relation_has_history(client, name)
+        .await
+        .map_err(|e| MaterializeRelationError::History { source: e })?;
+
+    if !has_history {
+        return Ok(());
+    }
+
+    let close_query = format!(
+        concat!(
+            "UPDATE relation_history.{name} h SET valid_to = now() ",
+            "WHERE h.valid_to IS NULL AND NOT EXISTS (",
+            "SELECT 1 FROM relation.{name} r ",
+            "WHERE r.source_id = h.source_id AND r.target_id = h.target_id",
+            ")"
+        ),
+        name = escape_identifier(name)
+    );
+
+    client
+        .execute(&close_query, &[])
+        .await
+        .map_err(|e| MaterializeRelationError::History { source: e })?;
+
+    let open_query = format!(
+        concat!(
+            "INSERT INTO relation_history.{name}(source_id, target_id, valid_from) ",
+            "SELECT DISTINCT r.source_id, r.target_id, now() FROM relation.{name} r ",
+            "WHERE NOT EXISTS (",
+            "SELECT 1 FROM relation_history.{name} h ",
+            "WHERE h.valid_to IS NULL AND h.source_id = r.source_id AND h.target_id = r.target_id",
+            ")"
+        ),
+        name = escape_identifier(name)
+    );
+
+    client
+        .execute(&open_query, &[])
+        .await
+        .map_err(|e| MaterializeRelationError::History { source: e })?;
+
+    Ok(())
+}
+
+async fn create_relation_history<T: GenericClient>(
+    client: &mut T,
+    relation_name: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let name = escape_identifier(relation_name);
+
+    let query = format!(
+        concat!(
+            "CREATE TABLE relation_history.{name}(",
+            "source_id integer NOT NULL, ",
+            "target_id integer NOT NULL, ",
+            "valid_from timestamp with time zone NOT NULL, ",
+            "valid_to timestamp with time zone",
+            ")"
+        ),
+        name = name
+    );
+
+    client.execute(&query, &[]).await?;
+
+    let query = format!(
+        "CREATE UNIQUE INDEX ON relation_history.{name}(source_id, target_id) WHERE valid_to IS NULL"
+    );
+
+    client.execute(&query, &[]).await?;
+
+    let query = format!("CREATE INDEX ON relation_history.{name}(source_id, valid_from)");
+
+    client.execute(&query, &[]).await?;
+
+    // The current content of the relation is valid for all data before the history starts
+    let query = format!(
+        concat!(
+            "INSERT INTO relation_history.{name}(source_id, target_id, valid_from) ",
+            "SELECT DISTINCT source_id, target_id, '-infinity'::timestamptz FROM relation.{name}"
+        ),
+        name = name
+    );
+
+    client.execute(&query, &[]).await?;
+
+    // Make the table available on each of the Citus nodes.
+    let query = format!(
+        "SELECT create_reference_table('relation_history.\"{}\"')",
+        relation_name
+    );
+
+    client.query(&query, &[]).await?;
+
+    Ok(())
+}
+
+/// The kind of object in the `relation_def` schema that defines the content of a relation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationDefinitionKind {
+    View,
+    Procedure,
+}
+
+/// Determine how a relation is defined. A view takes precedence over a procedure, and `None` is
+/// returned when the relation has neither.
+pub async fn relation_definition_kind<T: GenericClient + Sync>(
+    client: &T,
+    name: &str,
+) -> Result<Option<RelationDefinitionKind>, tokio_postgres::Error> {
+    let view_query = concat!(
+        "SELECT 1 FROM pg_catalog.pg_class c ",
+        "JOIN pg_catalog.pg_namespace ns ",
+        "ON c.relnamespace = ns.oid ",
+        "WHERE relname = $1 AND nspname = 'relation_def'"
+    );
+
+    if client.query_opt(view_query, &[&name]).await?.is_some() {
+        return Ok(Some(RelationDefinitionKind::View));
+    }
+
+    let procedure_query = concat!(
+        "SELECT 1 FROM pg_catalog.pg_proc p ",
+        "JOIN pg_catalog.pg_namespace ns ",
+        "ON p.pronamespace = ns.oid ",
+        "WHERE proname = $1 AND nspname = 'relation_def' AND prokind = 'p' AND pronargs = 0"
+    );
+
+    if client.query_opt(procedure_query, &[&name]).await?.is_some() {
+        return Ok(Some(RelationDefinitionKind::Procedure));
+    }
+
+    Ok(None)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MaterializeRelationOptions {
+    /// Publish the added and removed pairs as events in `directory.change_event`, so that
+    /// dependent data can be updated for only the affected entities.
+    pub publish_changes: bool,
+}
+
 pub struct MaterializeRelationResult {
-    pub deleted_count: u64,
-    pub inserted_count: u64,
+    pub added_count: u64,
+    pub removed_count: u64,
+}
+
+/// Type of the events in `directory.change_event` that are published by relation
+/// materialization.
+pub const RELATION_CHANGE_EVENT_TYPE: &str = "relation";
+
+/// Maximum number of added and removed pairs in one relation change event
+pub const RELATION_CHANGE_EVENT_BATCH_SIZE: usize = 1000;
+
+/// Common table expression that publishes the pairs in the `added` and `removed` expressions as
+/// change events of at most `RELATION_CHANGE_EVENT_BATCH_SIZE` pairs, if there are any. Every
+/// event also contains the total number of added and removed pairs.
+fn publish_changes_cte(name: &str) -> String {
+    format!(
+        concat!(
+            ", published AS (",
+            "INSERT INTO directory.change_event(type, data) ",
+            "SELECT {event_type}, jsonb_build_object(",
+            "'relation', {name}, ",
+            "'batch', c.batch, ",
+            "'added_count', (SELECT count(*) FROM added), ",
+            "'removed_count', (SELECT count(*) FROM removed), ",
+            "'added', coalesce(jsonb_agg(jsonb_build_array(c.source_id, c.target_id)) FILTER (WHERE c.added), '[]'), ",
+            "'removed', coalesce(jsonb_agg(jsonb_build_array(c.source_id, c.target_id)) FILTER (WHERE NOT c.added), '[]')",
+            ") ",
+            "FROM (",
+            "SELECT (row_number() OVER (ORDER BY p.added DESC, p.source_id, p.target_id) - 1) / {batch_size} AS batch, p.* ",
+            "FROM (",
+            "SELECT true AS added, source_id, target_id FROM added ",
+            "UNION ALL ",
+            "SELECT false AS added, source_id, target_id FROM removed",
+            ") p",
+            ") c ",
+            "GROUP BY c.batch",
+            ")"
+        ),
+        event_type = escape_literal(RELATION_CHANGE_EVENT_TYPE),
+        name = escape_literal(name),
+        batch_size = RELATION_CHANGE_EVENT_BATCH_SIZE,
+    )
+}
+
+/// Merge the content of the defining view into the relation table, so that only new pairs are
+/// inserted and only vanished pairs are deleted.
+fn merge_relation_query(name: &str, options: &MaterializeRelationOptions) -> String {
+    let publish_cte = if options.publish_changes {
+        publish_changes_cte(name)
+    } else {
+        String::new()
+    };
+
+    format!(
+        concat!(
+            "WITH def AS (SELECT source_id, target_id FROM relation_def.{name}), ",
+            "vanished AS (SELECT source_id, target_id FROM relation.{name} EXCEPT SELECT source_id, target_id FROM def), ",
+            "new AS (SELECT source_id, target_id FROM def EXCEPT SELECT source_id, target_id FROM relation.{name}), ",
+            "removed AS (",
+            "DELETE FROM relation.{name} r USING vanished v ",
+            "WHERE r.source_id = v.source_id AND r.target_id = v.target_id ",
+            "RETURNING r.source_id, r.target_id",
+            "), ",
+            "added AS (",
+            "INSERT INTO relation.{name}(source_id, target_id) ",
+            "SELECT source_id, target_id FROM new ",
+            "RETURNING source_id, target_id",
+            ")",
+            "{publish_cte} ",
+            "SELECT (SELECT count(*) FROM added), (SELECT count(*) FROM removed)"
+        ),
+        name = escape_identifier(name),
+        publish_cte = publish_cte,
+    )
 }
 
 pub async fn materialize_relation(
     tx: &mut Transaction<'_>,
     name: &str,
+    options: &MaterializeRelationOptions,
+) -> Result<MaterializeRelationResult, MaterializeRelationError> {
+    let row = tx
+        .query_one(&merge_relation_query(name, options), &[])
+        .await
+        .map_err(|e| MaterializeRelationError::Merge { source: e })?;
+
+    let added_count: i64 = row.get(0);
+    let removed_count: i64 = row.get(1);
+
+    update_relation_history(&*tx, name).await?;
+
+    Ok(MaterializeRelationResult {
+        added_count: added_count as u64,
+        removed_count: removed_count as u64,
+    })
+}
+
+/// Materialize a relation by calling its `relation_def.<name>()` procedure. The procedure is
+/// responsible for populating the relation table. A snapshot of the relation is taken before the
+/// call to determine what changed.
+///
+/// For a relation with history, the procedure is called in the same transaction as the history
+/// update, so that the history always matches the relation table, and the procedure can not
+/// commit. Otherwise, the procedure is called outside of a transaction block and may commit
+/// between stages.
+pub async fn materialize_relation_by_procedure(
+    client: &mut Client,
+    name: &str,
+    options: &MaterializeRelationOptions,
 ) -> Result<MaterializeRelationResult, MaterializeRelationError> {
-    let delete_query = format!("DELETE FROM relation.{}", escape_identifier(name));
+    let has_history = relation_has_history(&*client, name)
+        .await
+        .map_err(|e| MaterializeRelationError::History { source: e })?;
 
-    let deleted_count = tx
-        .execute(&delete_query, &[])
+    if !has_history {
+        return call_relation_procedure(&*client, name, options).await;
+    }
+
+    let tx = client
+        .transaction()
+        .await
+        .map_err(|e| MaterializeRelationError::Transaction { source: e })?;
+
+    let result = call_relation_procedure(&tx, name, options).await?;
+
+    update_relation_history(&tx, name).await?;
+
+    tx.commit()
         .await
-        .map_err(|e| MaterializeRelationError::Delete { source: e })?;
+        .map_err(|e| MaterializeRelationError::Transaction { source: e })?;
+
+    Ok(result)
+}
 
-    let insert_query = format!(
-        "INSERT INTO relation.{}(source_id, target_id) SELECT source_id, target_id FROM relation_def.{}",
-        escape_identifier(name),
+/// Call the procedure of a relation and determine the changes from a snapshot of the relation
+/// taken before the call
+async fn call_relation_procedure<T: GenericClient + Sync>(
+    client: &T,
+    name: &str,
+    options: &MaterializeRelationOptions,
+) -> Result<MaterializeRelationResult, MaterializeRelationError> {
+    let snapshot_query = format!(
+        "CREATE TEMPORARY TABLE relation_snapshot ON COMMIT PRESERVE ROWS AS SELECT source_id, target_id FROM relation.{}",
         escape_identifier(name)
     );
 
-    let inserted_count = tx
-        .execute(&insert_query, &[])
+    client
+        .execute("DROP TABLE IF EXISTS pg_temp.relation_snapshot", &[])
+        .await
+        .map_err(|e| MaterializeRelationError::Snapshot { source: e })?;
+
+    client
+        .execute(&snapshot_query, &[])
+        .await
+        .map_err(|e| MaterializeRelationError::Snapshot { source: e })?;
+
+    let call_query = format!("CALL relation_def.{}()", escape_identifier(name));
+
+    client
+        .execute(&call_query, &[])
+        .await
+        .map_err(|e| MaterializeRelationError::Procedure { source: e })?;
+
+    let publish_cte = if options.publish_changes {
+        publish_changes_cte(name)
+    } else {
+        String::new()
+    };
+
+    let changes_query = format!(
+        concat!(
+            "WITH added AS (SELECT source_id, target_id FROM relation.{name} EXCEPT SELECT source_id, target_id FROM pg_temp.relation_snapshot), ",
+            "removed AS (SELECT source_id, target_id FROM pg_temp.relation_snapshot EXCEPT SELECT source_id, target_id FROM relation.{name})",
+            "{publish_cte} ",
+            "SELECT (SELECT count(*) FROM added), (SELECT count(*) FROM removed)"
+        ),
+        name = escape_identifier(name),
+        publish_cte = publish_cte,
+    );
+
+    let row = client
+        .query_one(&changes_query, &[])
         .await
-        .map_err(|e| MaterializeRelationError::Insert { source: e })?;
+        .map_err(|e| MaterializeRelationError::Snapshot { source: e })?;
+
+    let added_count: i64 = row.get(0);
+    let removed_count: i64 = row.get(1);
+
+    client
+        .execute("DROP TABLE pg_temp.relation_snapshot", &[])
+        .await
+        .map_err(|e| MaterializeRelationError::Snapshot { source: e })?;
 
     Ok(MaterializeRelationResult {
-        deleted_count,
-        inserted_count,
+        added_count: added_count as u64,
+        removed_count: removed_count as u64,
     })
 }
 
+async fn create_relation_procedure<T: GenericClient>(
+    client: &mut T,
+    relation_name: &str,
+    procedure_src: &str,
+) -> Result<(), tokio_postgres::Error> {
+    let query = format!(
+        "CREATE OR REPLACE PROCEDURE relation_def.{}() LANGUAGE plpgsql AS $procedure${}$procedure$",
+        escape_identifier(relation_name),
+        procedure_src
+    );
+
+    client.execute(&query, &[]).await?;
+
+    Ok(())
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum CreateRelationError {
     #[error("{0}")]
@@ -308,6 +912,12 @@ pub async fn create_relation<T: GenericClient>(
         client.query(&query, &[]).await.map_err(|e| {
             CreateRelationError::Database(format!("Error creating relation view: {e}"))
         })?;
+    } else if let Some(procedure_src) = &relation.procedure {
+        create_relation_procedure(client, &relation.name, procedure_src)
+            .await
+            .map_err(|e| {
+                CreateRelationError::Database(format!("Error creating relation procedure: {e}"))
+            })?;
     }
 
     let query = format!(
@@ -337,6 +947,14 @@ pub async fn create_relation<T: GenericClient>(
         ))
     })?;
 
+    if relation.history {
+        create_relation_history(client, &relation.name)
+            .await
+            .map_err(|e| {
+                CreateRelationError::Database(format!("Error creating relation history: {e}"))
+            })?;
+    }
+
     let query = "SELECT relation_directory.register_type($1)";
 
     client
@@ -384,6 +1002,24 @@ pub async fn remove_relation<T: GenericClient>(
         .await
         .map_err(|e| RemoveRelationError::from_postgres_error("Error dropping relation view", e))?;
 
+    let query = format!(
+        "DROP TABLE IF EXISTS relation_history.{}",
+        escape_identifier(relation_name),
+    );
+
+    client.query(&query, &[]).await.map_err(|e| {
+        RemoveRelationError::from_postgres_error("Error dropping relation history table", e)
+    })?;
+
+    let query = format!(
+        "DROP PROCEDURE IF EXISTS relation_def.{}()",
+        escape_identifier(relation_name)
+    );
+
+    client.query(&query, &[]).await.map_err(|e| {
+        RemoveRelationError::from_postgres_error("Error dropping relation procedure", e)
+    })?;
+
     let query = "DELETE FROM relation_directory.type WHERE name = $1";
 
     let delete_count = client
@@ -446,3 +1082,331 @@ impl Changed for RemovedRelation {
         None
     }
 }
+
+/// Split a relation name following the `<source type>-><target type>` naming convention
+#[must_use]
+pub fn relation_entity_types(relation_name: &str) -> Option<(&str, &str)> {
+    relation_name.split_once("->")
+}
+
+/// Find the shortest chain of relations that leads from one entity type to another, based on
+/// the `<source type>-><target type>` naming convention of the relations.
+#[must_use]
+pub fn find_relation_path(
+    relation_names: &[String],
+    from_entity_type: &str,
+    to_entity_type: &str,
+) -> Option<Vec<String>> {
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue: VecDeque<&str> = VecDeque::from([from_entity_type]);
+
+    while let Some(entity_type) = queue.pop_front() {
+        if entity_type == to_entity_type {
+            let mut path: Vec<String> = Vec::new();
+            let mut current = entity_type;
+
+            while let Some(relation_name) = previous.get(current) {
+                path.push(relation_name.to_string());
+                current = relation_entity_types(relation_name).unwrap().0;
+            }
+
+            path.reverse();
+
+            return Some(path);
+        }
+
+        for relation_name in relation_names {
+            if let Some((source_type, target_type)) = relation_entity_types(relation_name)
+                && source_type == entity_type
+                && target_type != from_entity_type
+                && !previous.contains_key(target_type)
+            {
+                previous.insert(target_type, relation_name);
+                queue.push_back(target_type);
+            }
+        }
+    }
+
+    None
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RelationQueryError {
+    #[error("Relation name '{0}' does not follow the '<source>-><target>' convention")]
+    InvalidName(String),
+    #[error("No chain of relations from '{0}' to '{1}'")]
+    NoPath(String, String),
+    #[error("{0}")]
+    Database(String),
+}
+
+/// Filter for the pairs of a relation, by the name of the source or target entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelationFilter {
+    All,
+    Source(String),
+    Target(String),
+}
+
+/// A pair of related entities, with both the Ids and names of the entities
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedEntities {
+    pub source_id: i32,
+    pub source_name: String,
+    pub target_id: i32,
+    pub target_name: String,
+}
+
+pub async fn load_relation_names<T: GenericClient + Sync>(
+    client: &T,
+) -> Result<Vec<String>, RelationQueryError> {
+    let rows = client
+        .query(
+            "SELECT name FROM relation_directory.type ORDER BY name",
+            &[],
+        )
+        .await
+        .map_err(|e| RelationQueryError::Database(format!("Could not load relation names: {e}")))?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Load the related entities over a chain of relations, where the target of each relation is the
+/// source of the next one. The source of the result is the source of the first relation and the
+/// target of the result is the target of the last relation.
+pub async fn load_related_entities<T: GenericClient + Sync>(
+    client: &T,
+    relation_names: &[String],
+    filter: &RelationFilter,
+) -> Result<Vec<RelatedEntities>, RelationQueryError> {
+    let (Some(first), Some(last)) = (relation_names.first(), relation_names.last()) else {
+        return Ok(Vec::new());
+    };
+
+    let (source_type, _) =
+        relation_entity_types(first).ok_or(RelationQueryError::InvalidName(first.clone()))?;
+    let (_, target_type) =
+        relation_entity_types(last).ok_or(RelationQueryError::InvalidName(last.clone()))?;
+
+    let mut joins: Vec<String> = Vec::new();
+
+    for (index, relation_name) in relation_names.iter().enumerate().skip(1) {
+        joins.push(format!(
+            "JOIN relation.{} r{index} ON r{index}.source_id = r{}.target_id ",
+            escape_identifier(relation_name),
+            index - 1
+        ));
+    }
+
+    let last_index = relation_names.len() - 1;
+
+    let condition = match filter {
+        RelationFilter::All => "",
+        RelationFilter::Source(_) => "WHERE s.name = $1 ",
+        RelationFilter::Target(_) => "WHERE t.name = $1 ",
+    };
+
+    let query = format!(
+        concat!(
+            "SELECT DISTINCT s.id, s.name, t.id, t.name ",
+            "FROM relation.{first} r0 ",
+            "{joins}",
+            "JOIN entity.{source_type} s ON s.id = r0.source_id ",
+            "JOIN entity.{target_type} t ON t.id = r{last_index}.target_id ",
+            "{condition}",
+            "ORDER BY s.name, t.name"
+        ),
+        first = escape_identifier(first),
+        joins = joins.join(""),
+        source_type = escape_identifier(source_type),
+        target_type = escape_identifier(target_type),
+        last_index = last_index,
+        condition = condition,
+    );
+
+    let rows = match filter {
+        RelationFilter::All => client.query(&query, &[]).await,
+        RelationFilter::Source(name) | RelationFilter::Target(name) => {
+            client.query(&query, &[name]).await
+        }
+    }
+    .map_err(|e| RelationQueryError::Database(format!("Could not load related entities: {e}")))?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RelatedEntities {
+            source_id: row.get(0),
+            source_name: row.get(1),
+            target_id: row.get(2),
+            target_name: row.get(3),
+        })
+        .collect())
+}
+
+/// Find the chain of relations from one entity type to another and load the entities that are
+/// reached over it.
+pub async fn load_relation_path<T: GenericClient + Sync>(
+    client: &T,
+    from_entity_type: &str,
+    to_entity_type: &str,
+    filter: &RelationFilter,
+) -> Result<(Vec<String>, Vec<RelatedEntities>), RelationQueryError> {
+    let relation_names = load_relation_names(client).await?;
+
+    let path = find_relation_path(&relation_names, from_entity_type, to_entity_type).ok_or(
+        RelationQueryError::NoPath(from_entity_type.to_string(), to_entity_type.to_string()),
+    )?;
+
+    let related_entities = load_related_entities(client, &path, filter).await?;
+
+    Ok((path, related_entities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_ignores_formatting() {
+        let my_relation = Relation {
+            name: "node->v-network".to_string(),
+            query: Some("SELECT n.id AS source_id, v.id AS target_id FROM entity.node n, entity.\"v-network\" v".to_string()),
+            procedure: None,
+            history: false,
+        };
+
+        let other_relation = Relation {
+            name: "node->v-network".to_string(),
+            query: Some("SELECT\n  n.id AS source_id,\n  v.id AS target_id\nFROM entity.\"node\" n, entity.\"v-network\" v".to_string()),
+            procedure: None,
+            history: false,
+        };
+
+        assert!(my_relation.diff(&other_relation).is_empty());
+    }
+
+    #[test]
+    fn test_diff_changed_query() {
+        let my_relation = Relation {
+            name: "node->v-network".to_string(),
+            query: Some("SELECT n.id AS source_id, v.id AS target_id FROM entity.node n, entity.\"v-network\" v".to_string()),
+            procedure: None,
+            history: false,
+        };
+
+        let other_relation = Relation {
+            name: "node->v-network".to_string(),
+            query: Some("SELECT n.id AS source_id, v.id AS target_id FROM entity.node n JOIN entity.\"v-network\" v ON v.name = n.name".to_string()),
+            procedure: None,
+            history: false,
+        };
+
+        let changes = my_relation.diff(&other_relation);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].to_string(),
+            "UpdateRelationView(node->v-network)"
+        );
+    }
+
+    #[test]
+    fn test_diff_changed_procedure() {
+        let my_relation = Relation {
+            name: "cell->site".to_string(),
+            query: None,
+            procedure: Some("BEGIN\n  CALL relation_def.stage_1();\nEND;".to_string()),
+            history: false,
+        };
+
+        let same_relation = Relation {
+            name: "cell->site".to_string(),
+            query: None,
+            procedure: Some("BEGIN CALL relation_def.stage_1(); END;".to_string()),
+            history: false,
+        };
+
+        assert!(my_relation.diff(&same_relation).is_empty());
+
+        let other_relation = Relation {
+            name: "cell->site".to_string(),
+            query: None,
+            procedure: Some("BEGIN CALL relation_def.stage_2(); END;".to_string()),
+            history: false,
+        };
+
+        let changes = my_relation.diff(&other_relation);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].to_string(),
+            "UpdateRelationProcedure(cell->site)"
+        );
+    }
+
+    #[test]
+    fn test_diff_added_history() {
+        let my_relation = Relation {
+            name: "cell->site".to_string(),
+            query: None,
+            procedure: None,
+            history: false,
+        };
+
+        let other_relation = Relation {
+            history: true,
+            ..my_relation.clone()
+        };
+
+        let changes = my_relation.diff(&other_relation);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "AddRelationHistory(cell->site)");
+
+        assert!(other_relation.diff(&my_relation).is_empty());
+    }
+
+    #[test]
+    fn test_merge_relation_query() {
+        let options = MaterializeRelationOptions {
+            publish_changes: true,
+        };
+
+        let query = merge_relation_query("cell->site", &options);
+
+        assert!(query.contains("DELETE FROM relation.\"cell->site\" r USING vanished v"));
+        assert!(query.contains("INSERT INTO directory.change_event(type, data)"));
+        assert!(query.contains(") / 1000 AS batch"));
+        assert!(query.contains("GROUP BY c.batch"));
+        assert!(pg_query::parse(&query).is_ok());
+
+        let query = merge_relation_query("cell->site", &MaterializeRelationOptions::default());
+
+        assert!(!query.contains("change_event"));
+        assert!(pg_query::parse(&query).is_ok());
+    }
+
+    #[test]
+    fn test_find_relation_path() {
+        let relation_names: Vec<String> = [
+            "cell->site",
+            "site->region",
+            "cell->entity_set",
+            "region->country",
+            "site->cell",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+
+        assert_eq!(
+            find_relation_path(&relation_names, "cell", "region"),
+            Some(vec!["cell->site".to_string(), "site->region".to_string()])
+        );
+        assert_eq!(
+            find_relation_path(&relation_names, "cell", "cell"),
+            Some(vec![])
+        );
+        assert_eq!(find_relation_path(&relation_names, "region", "cell"), None);
+    }
+}
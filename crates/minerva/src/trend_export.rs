@@ -0,0 +1,649 @@
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+#[cfg(feature = "parquet")]
+use arrow_array::builder::{
+    BooleanBuilder, Float32Builder, Float64Builder, Int16Builder, Int32Builder, Int64Builder,
+    StringBuilder, TimestampMicrosecondBuilder,
+};
+#[cfg(feature = "parquet")]
+use arrow_array::{ArrayRef, RecordBatch};
+#[cfg(feature = "parquet")]
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, pin_mut};
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "parquet")]
+use parquet::errors::ParquetError;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+use postgres_protocol::escape::{escape_identifier, escape_literal};
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_postgres::Client;
+#[cfg(feature = "parquet")]
+use tokio_postgres::binary_copy::{BinaryCopyOutRow, BinaryCopyOutStream};
+#[cfg(feature = "parquet")]
+use tokio_postgres::types::Type;
+
+use crate::loading::{ParserConfig, TrendsFrom, TrendsFromHeader};
+use crate::trend_store::{
+    TrendStoreRef, load_trend_store, load_trend_store_part, load_trend_store_ref_for_part,
+};
+
+#[derive(Error, Debug)]
+pub enum TrendExportError {
+    #[error("Could not load trend store: {0}")]
+    TrendStore(String),
+    #[error("{0}")]
+    Database(String),
+    #[error("Could not write export: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0:?} exports can not be loaded, only CSV exports have a parser config")]
+    ExportOnly(TrendExportFormat),
+    #[cfg(feature = "parquet")]
+    #[error("Could not write Parquet: {0}")]
+    Parquet(#[from] ParquetError),
+    #[cfg(feature = "parquet")]
+    #[error("Could not build Parquet data: {0}")]
+    Arrow(#[from] ArrowError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrendExportFormat {
+    /// CSV with a header line, readable by `load_data` with the parser config from
+    /// `TrendExport::parser_config`
+    Csv,
+    /// One JSON object per line. Export only, there is no loader for this format.
+    Jsonl,
+    /// Apache Parquet with typed columns. Numeric trends and trends of types without a Parquet
+    /// counterpart, like arrays, are written as text. Export only, there is no loader for this
+    /// format.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl TrendExportFormat {
+    /// Return true if exports in this format can be loaded again with `load_data`
+    #[must_use]
+    pub fn is_loadable(&self) -> bool {
+        matches!(self, TrendExportFormat::Csv)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrendExportSource {
+    /// All trends of a single trend store part
+    TrendStorePart(String),
+    /// All trends of all parts of a trend store, joined on entity and timestamp
+    TrendStore(TrendStoreRef),
+}
+
+/// Export of the data of a trend store or trend store part for a period. The data is streamed
+/// from the database using `COPY ... TO STDOUT`, with entity Ids resolved to entity names.
+#[derive(Debug, Clone)]
+pub struct TrendExport {
+    pub source: TrendExportSource,
+    pub format: TrendExportFormat,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// The trends of the source of an export, grouped by the trend store part that contains them
+#[derive(Debug, Clone, PartialEq)]
+struct ExportParts {
+    trend_store: TrendStoreRef,
+    parts: Vec<(String, Vec<String>)>,
+}
+
+pub const ENTITY_COLUMN: &str = "entity";
+pub const TIMESTAMP_COLUMN: &str = "timestamp";
+
+/// Number of rows per record batch written to a Parquet export
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH_SIZE: usize = 8192;
+
+/// Type of a column in a Parquet export, with the cast needed to get it from the column in the
+/// query. Numeric values have no fixed precision and scale, so they are written as text to keep
+/// them exact.
+#[cfg(feature = "parquet")]
+fn parquet_column_type(name: &str, column_type: &Type) -> (Type, Option<&'static str>) {
+    if name == TIMESTAMP_COLUMN {
+        return (Type::TIMESTAMPTZ, Some("timestamptz"));
+    }
+
+    match *column_type {
+        Type::INT2
+        | Type::INT4
+        | Type::INT8
+        | Type::FLOAT4
+        | Type::FLOAT8
+        | Type::BOOL
+        | Type::TEXT
+        | Type::TIMESTAMPTZ => (column_type.clone(), None),
+        _ => (Type::TEXT, Some("text")),
+    }
+}
+
+/// Builder for the values of one column of a Parquet export
+#[cfg(feature = "parquet")]
+enum ParquetColumn {
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Boolean(BooleanBuilder),
+    Timestamp(TimestampMicrosecondBuilder),
+    Text(StringBuilder),
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetColumn {
+    fn new(column_type: &Type) -> Self {
+        match *column_type {
+            Type::INT2 => ParquetColumn::Int16(Int16Builder::new()),
+            Type::INT4 => ParquetColumn::Int32(Int32Builder::new()),
+            Type::INT8 => ParquetColumn::Int64(Int64Builder::new()),
+            Type::FLOAT4 => ParquetColumn::Float32(Float32Builder::new()),
+            Type::FLOAT8 => ParquetColumn::Float64(Float64Builder::new()),
+            Type::BOOL => ParquetColumn::Boolean(BooleanBuilder::new()),
+            Type::TIMESTAMPTZ => {
+                ParquetColumn::Timestamp(TimestampMicrosecondBuilder::new().with_timezone("+00:00"))
+            }
+            _ => ParquetColumn::Text(StringBuilder::new()),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            ParquetColumn::Int16(_) => DataType::Int16,
+            ParquetColumn::Int32(_) => DataType::Int32,
+            ParquetColumn::Int64(_) => DataType::Int64,
+            ParquetColumn::Float32(_) => DataType::Float32,
+            ParquetColumn::Float64(_) => DataType::Float64,
+            ParquetColumn::Boolean(_) => DataType::Boolean,
+            ParquetColumn::Timestamp(_) => {
+                DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into()))
+            }
+            ParquetColumn::Text(_) => DataType::Utf8,
+        }
+    }
+
+    fn append(
+        &mut self,
+        row: &BinaryCopyOutRow,
+        index: usize,
+    ) -> Result<(), tokio_postgres::Error> {
+        match self {
+            ParquetColumn::Int16(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Int32(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Int64(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Float32(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Float64(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Boolean(builder) => builder.append_option(row.try_get(index)?),
+            ParquetColumn::Timestamp(builder) => builder.append_option(
+                row.try_get::<Option<DateTime<Utc>>>(index)?
+                    .map(|timestamp| timestamp.timestamp_micros()),
+            ),
+            ParquetColumn::Text(builder) => {
+                builder.append_option(row.try_get::<Option<&str>>(index)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ParquetColumn::Int16(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Int32(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Int64(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Float32(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Float64(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Boolean(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Timestamp(builder) => Arc::new(builder.finish()),
+            ParquetColumn::Text(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+impl TrendExport {
+    #[must_use]
+    pub fn new(source: TrendExportSource, format: TrendExportFormat) -> Self {
+        TrendExport {
+            source,
+            format,
+            from: None,
+            to: None,
+        }
+    }
+
+    /// Only export data with a timestamp from `from` up to and including `to`
+    #[must_use]
+    pub fn with_time_range(
+        mut self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    async fn resolve_parts(&self, client: &Client) -> Result<ExportParts, TrendExportError> {
+        match &self.source {
+            TrendExportSource::TrendStorePart(name) => {
+                let part = load_trend_store_part(client, name)
+                    .await
+                    .map_err(|e| TrendExportError::TrendStore(e.to_string()))?;
+
+                let trend_store = load_trend_store_ref_for_part(client, name)
+                    .await
+                    .map_err(TrendExportError::TrendStore)?;
+
+                Ok(ExportParts {
+                    trend_store,
+                    parts: vec![(
+                        part.name,
+                        part.trends.into_iter().map(|trend| trend.name).collect(),
+                    )],
+                })
+            }
+            TrendExportSource::TrendStore(trend_store_ref) => {
+                let trend_store = load_trend_store(client, trend_store_ref)
+                    .await
+                    .map_err(|e| TrendExportError::TrendStore(e.to_string()))?;
+
+                Ok(ExportParts {
+                    trend_store: trend_store_ref.clone(),
+                    parts: trend_store
+                        .parts
+                        .into_iter()
+                        .map(|part| {
+                            (
+                                part.name,
+                                part.trends.into_iter().map(|trend| trend.name).collect(),
+                            )
+                        })
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    /// Query selecting the entity name, the timestamp in RFC 3339 format and the trends of all
+    /// parts. COPY does not support parameters, so the time range is inlined as literals.
+    fn select_query(&self, export_parts: &ExportParts) -> String {
+        let mut conditions: Vec<String> = Vec::new();
+
+        if let Some(from) = self.from {
+            conditions.push(format!(
+                "timestamp >= {}::timestamptz",
+                escape_literal(&from.to_rfc3339()).trim_start()
+            ));
+        }
+
+        if let Some(to) = self.to {
+            conditions.push(format!(
+                "timestamp <= {}::timestamptz",
+                escape_literal(&to.to_rfc3339()).trim_start()
+            ));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", conditions.join(" AND "))
+        };
+
+        let keys = export_parts
+            .parts
+            .iter()
+            .map(|(part, _)| {
+                format!(
+                    "SELECT entity_id, timestamp FROM trend.{}{where_clause}",
+                    escape_identifier(part)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" UNION ");
+
+        let mut columns = vec![
+            format!("e.name AS {ENTITY_COLUMN}"),
+            format!("to_json(k.timestamp) #>> '{{}}' AS {TIMESTAMP_COLUMN}"),
+        ];
+
+        let mut joins: Vec<String> = Vec::new();
+
+        for (index, (part, trends)) in export_parts.parts.iter().enumerate() {
+            let alias = format!("t{}", index + 1);
+
+            columns.extend(
+                trends
+                    .iter()
+                    .map(|trend| format!("{alias}.{}", escape_identifier(trend))),
+            );
+
+            joins.push(format!(
+                "LEFT JOIN trend.{} {alias} ON {alias}.entity_id = k.entity_id AND {alias}.timestamp = k.timestamp",
+                escape_identifier(part)
+            ));
+        }
+
+        format!(
+            "WITH k AS ({keys}) SELECT {} FROM k JOIN entity.{} e ON e.id = k.entity_id {} ORDER BY k.timestamp, e.name",
+            columns.join(", "),
+            escape_identifier(&export_parts.trend_store.entity_type),
+            joins.join(" ")
+        )
+    }
+
+    fn copy_query(&self, select_query: &str) -> String {
+        match self.format {
+            TrendExportFormat::Csv => {
+                format!("COPY ({select_query}) TO STDOUT WITH (FORMAT csv, HEADER)")
+            }
+            // The text format of COPY would escape the backslashes in the JSON, so the CSV
+            // format is used with quote and delimiter characters that JSON text never contains
+            // unescaped.
+            TrendExportFormat::Jsonl => format!(
+                "COPY (SELECT row_to_json(x)::text FROM ({select_query}) x) TO STDOUT WITH (FORMAT csv, QUOTE E'\\x01', DELIMITER E'\\x02')"
+            ),
+            #[cfg(feature = "parquet")]
+            TrendExportFormat::Parquet => {
+                format!("COPY ({select_query}) TO STDOUT WITH (FORMAT binary)")
+            }
+        }
+    }
+
+    /// Query for a Parquet export, with the columns of the select query cast to types that have
+    /// a Parquet counterpart
+    #[cfg(feature = "parquet")]
+    fn parquet_select_query(select_query: &str, columns: &[(String, Type)]) -> String {
+        let columns = columns
+            .iter()
+            .map(|(name, column_type)| {
+                let column = format!("x.{}", escape_identifier(name));
+
+                match parquet_column_type(name, column_type).1 {
+                    Some(cast) => format!("{column}::{cast} AS {}", escape_identifier(name)),
+                    None => column,
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        format!("SELECT {columns} FROM ({select_query}) x")
+    }
+
+    /// Parser configuration for loading a CSV export with `load_data`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the export is not in a loadable format or the trend store of the
+    /// source cannot be loaded.
+    pub async fn parser_config(&self, client: &Client) -> Result<ParserConfig, TrendExportError> {
+        if !self.format.is_loadable() {
+            return Err(TrendExportError::ExportOnly(self.format));
+        }
+
+        let export_parts = self.resolve_parts(client).await?;
+
+        Ok(ParserConfig {
+            entity_type: export_parts.trend_store.entity_type.clone(),
+            granularity: humantime::format_duration(export_parts.trend_store.granularity)
+                .to_string(),
+            trends: TrendsFrom::Header(TrendsFromHeader {
+                entity_column: ENTITY_COLUMN.to_string(),
+                timestamp_column: TIMESTAMP_COLUMN.to_string(),
+            }),
+            extra: None,
+            null_value: String::new(),
+            strict_entities: false,
+            entity_alias: None,
+        })
+    }
+
+    /// Stream the export to `writer` and return the number of bytes written
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the source cannot be loaded, the data cannot be read or the output
+    /// cannot be written.
+    pub async fn write<W: AsyncWrite + Unpin>(
+        &self,
+        client: &Client,
+        writer: &mut W,
+    ) -> Result<u64, TrendExportError> {
+        let export_parts = self.resolve_parts(client).await?;
+
+        let select_query = self.select_query(&export_parts);
+
+        #[cfg(feature = "parquet")]
+        if self.format == TrendExportFormat::Parquet {
+            return self.write_parquet(client, &select_query, writer).await;
+        }
+
+        let query = self.copy_query(&select_query);
+
+        let stream = client
+            .copy_out(&query)
+            .await
+            .map_err(|e| TrendExportError::Database(format!("Could not export data: {e}")))?;
+
+        pin_mut!(stream);
+
+        let mut byte_count: u64 = 0;
+
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk
+                .map_err(|e| TrendExportError::Database(format!("Could not export data: {e}")))?;
+
+            writer.write_all(&chunk).await?;
+
+            byte_count += chunk.len() as u64;
+        }
+
+        writer.flush().await?;
+
+        Ok(byte_count)
+    }
+
+    /// Stream the export as Parquet to `writer`, using a binary `COPY` to read typed values. The
+    /// encoded data is passed on to `writer` as soon as the Parquet writer has written it.
+    #[cfg(feature = "parquet")]
+    async fn write_parquet<W: AsyncWrite + Unpin>(
+        &self,
+        client: &Client,
+        select_query: &str,
+        writer: &mut W,
+    ) -> Result<u64, TrendExportError> {
+        let statement = client
+            .prepare(select_query)
+            .await
+            .map_err(|e| TrendExportError::Database(format!("Could not prepare export: {e}")))?;
+
+        let columns: Vec<(String, Type)> = statement
+            .columns()
+            .iter()
+            .map(|column| (column.name().to_string(), column.type_().clone()))
+            .collect();
+
+        let types: Vec<Type> = columns
+            .iter()
+            .map(|(name, column_type)| parquet_column_type(name, column_type).0)
+            .collect();
+
+        let mut builders: Vec<ParquetColumn> = types.iter().map(ParquetColumn::new).collect();
+
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .zip(&builders)
+                .map(|((name, _), builder)| Field::new(name, builder.data_type(), true))
+                .collect::<Vec<Field>>(),
+        ));
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+
+        let mut parquet_writer =
+            ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))?;
+
+        let query = self.copy_query(&TrendExport::parquet_select_query(select_query, &columns));
+
+        let stream = client
+            .copy_out(&query)
+            .await
+            .map_err(|e| TrendExportError::Database(format!("Could not export data: {e}")))?;
+
+        let rows = BinaryCopyOutStream::new(stream, &types);
+
+        pin_mut!(rows);
+
+        let mut byte_count: u64 = 0;
+        let mut batch_row_count: usize = 0;
+
+        loop {
+            let row =
+                rows.next().await.transpose().map_err(|e| {
+                    TrendExportError::Database(format!("Could not export data: {e}"))
+                })?;
+
+            if let Some(row) = &row {
+                for (index, builder) in builders.iter_mut().enumerate() {
+                    builder.append(row, index).map_err(|e| {
+                        TrendExportError::Database(format!("Could not read exported value: {e}"))
+                    })?;
+                }
+
+                batch_row_count += 1;
+            }
+
+            if batch_row_count == PARQUET_BATCH_SIZE || (row.is_none() && batch_row_count > 0) {
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    builders.iter_mut().map(ParquetColumn::finish).collect(),
+                )?;
+
+                parquet_writer.write(&batch)?;
+                batch_row_count = 0;
+
+                let encoded = std::mem::take(parquet_writer.inner_mut());
+                writer.write_all(&encoded).await?;
+                byte_count += encoded.len() as u64;
+            }
+
+            if row.is_none() {
+                break;
+            }
+        }
+
+        let encoded = parquet_writer.into_inner()?;
+        writer.write_all(&encoded).await?;
+        byte_count += encoded.len() as u64;
+
+        writer.flush().await?;
+
+        Ok(byte_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn export_parts() -> ExportParts {
+        ExportParts {
+            trend_store: TrendStoreRef {
+                data_source: "hub".to_string(),
+                entity_type: "cell".to_string(),
+                granularity: Duration::from_secs(900),
+            },
+            parts: vec![
+                (
+                    "hub_cell_main_15m".to_string(),
+                    vec!["bytes_up".to_string(), "bytes_down".to_string()],
+                ),
+                ("hub_cell_extra_15m".to_string(), vec!["power".to_string()]),
+            ],
+        }
+    }
+
+    #[test]
+    fn trend_export_csv_query() {
+        let export = TrendExport::new(
+            TrendExportSource::TrendStorePart("hub_cell_main_15m".to_string()),
+            TrendExportFormat::Csv,
+        )
+        .with_time_range(
+            Some(Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()),
+            None,
+        );
+
+        assert_eq!(
+            export.copy_query(&export.select_query(&export_parts())),
+            concat!(
+                "COPY (WITH k AS (",
+                "SELECT entity_id, timestamp FROM trend.\"hub_cell_main_15m\" WHERE timestamp >= '2025-01-01T00:00:00+00:00'::timestamptz",
+                " UNION ",
+                "SELECT entity_id, timestamp FROM trend.\"hub_cell_extra_15m\" WHERE timestamp >= '2025-01-01T00:00:00+00:00'::timestamptz",
+                ") SELECT e.name AS entity, to_json(k.timestamp) #>> '{}' AS timestamp, t1.\"bytes_up\", t1.\"bytes_down\", t2.\"power\" ",
+                "FROM k JOIN entity.\"cell\" e ON e.id = k.entity_id ",
+                "LEFT JOIN trend.\"hub_cell_main_15m\" t1 ON t1.entity_id = k.entity_id AND t1.timestamp = k.timestamp ",
+                "LEFT JOIN trend.\"hub_cell_extra_15m\" t2 ON t2.entity_id = k.entity_id AND t2.timestamp = k.timestamp ",
+                "ORDER BY k.timestamp, e.name) TO STDOUT WITH (FORMAT csv, HEADER)"
+            )
+        );
+    }
+
+    #[test]
+    fn trend_export_jsonl_query() {
+        let export = TrendExport::new(
+            TrendExportSource::TrendStorePart("hub_cell_main_15m".to_string()),
+            TrendExportFormat::Jsonl,
+        );
+
+        let query = export.copy_query(&export.select_query(&export_parts()));
+
+        assert!(query.starts_with("COPY (SELECT row_to_json(x)::text FROM (WITH k AS (SELECT entity_id, timestamp FROM trend.\"hub_cell_main_15m\" UNION"));
+        assert!(
+            query.ends_with(") x) TO STDOUT WITH (FORMAT csv, QUOTE E'\\x01', DELIMITER E'\\x02')")
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn trend_export_parquet_query() {
+        let export = TrendExport::new(
+            TrendExportSource::TrendStorePart("hub_cell_main_15m".to_string()),
+            TrendExportFormat::Parquet,
+        );
+
+        let columns = vec![
+            ("entity".to_string(), Type::TEXT),
+            ("timestamp".to_string(), Type::TEXT),
+            ("bytes_up".to_string(), Type::INT8),
+            ("ratio".to_string(), Type::NUMERIC),
+            ("counters".to_string(), Type::INT4_ARRAY),
+        ];
+
+        assert_eq!(
+            export.copy_query(&TrendExport::parquet_select_query("SELECT 1", &columns)),
+            concat!(
+                "COPY (SELECT x.\"entity\", x.\"timestamp\"::timestamptz AS \"timestamp\", x.\"bytes_up\", ",
+                "x.\"ratio\"::text AS \"ratio\", x.\"counters\"::text AS \"counters\" ",
+                "FROM (SELECT 1) x) TO STDOUT WITH (FORMAT binary)"
+            )
+        );
+    }
+}
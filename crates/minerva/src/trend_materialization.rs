@@ -26,6 +26,10 @@ use super::change::{Change, ChangeResult, InformationOption};
 use super::error::{DatabaseError, Error, RuntimeError};
 use super::interval::parse_interval;
 
+pub mod declarative;
+
+use declarative::TrendDeclarativeMaterialization;
+
 pub const MATERIALIZATION_FUNCTION_SCHEMA: &str = "trend";
 
 #[derive(Serialize, Deserialize)]
@@ -1258,6 +1262,7 @@ impl TrendFunctionMaterialization {
 pub enum TrendMaterialization {
     View(TrendViewMaterialization),
     Function(TrendFunctionMaterialization),
+    Declarative(TrendDeclarativeMaterialization),
 }
 
 impl fmt::Display for TrendMaterialization {
@@ -1273,6 +1278,11 @@ impl fmt::Display for TrendMaterialization {
                 "TrendFunctionMaterialization('{}')",
                 function_materialization.target_trend_store_part
             ),
+            TrendMaterialization::Declarative(declarative_materialization) => write!(
+                f,
+                "TrendDeclarativeMaterialization('{}')",
+                declarative_materialization.target_trend_store_part
+            ),
         }
     }
 }
@@ -1290,6 +1300,11 @@ impl From<&TrendMaterialization> for TrendMaterializationRef {
                     target_trend_store_part: function.target_trend_store_part.clone(),
                 })
             }
+            TrendMaterialization::Declarative(declarative) => {
+                TrendMaterializationRef::Function(TrendFunctionMaterializationRef {
+                    target_trend_store_part: declarative.target_trend_store_part.clone(),
+                })
+            }
         }
     }
 }
@@ -1300,6 +1315,16 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => &m.target_trend_store_part,
             TrendMaterialization::Function(m) => &m.target_trend_store_part,
+            TrendMaterialization::Declarative(m) => &m.target_trend_store_part,
+        }
+    }
+
+    #[must_use]
+    pub fn sources(&self) -> Vec<TrendMaterializationSource> {
+        match self {
+            TrendMaterialization::View(m) => m.sources.clone(),
+            TrendMaterialization::Function(m) => m.sources.clone(),
+            TrendMaterialization::Declarative(m) => m.sources(),
         }
     }
 
@@ -1315,6 +1340,11 @@ impl TrendMaterialization {
                     "Could not dump function materialization: {e}"
                 )))
             }),
+            TrendMaterialization::Declarative(m) => serde_yaml::to_string(m).map_err(|e| {
+                Error::Runtime(RuntimeError::from_msg(format!(
+                    "Could not dump declarative materialization: {e}"
+                )))
+            }),
         }
     }
 
@@ -1406,6 +1436,11 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.update_attributes(client).await,
             TrendMaterialization::Function(m) => m.update_attributes(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization()
+                    .update_attributes(client)
+                    .await
+            }
         }
     }
 
@@ -1416,6 +1451,9 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.update_sources(client).await,
             TrendMaterialization::Function(m) => m.update_sources(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization().update_sources(client).await
+            }
         }
     }
 
@@ -1426,6 +1464,11 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.update_fingerprint_function(client).await,
             TrendMaterialization::Function(m) => m.update_fingerprint_function(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization()
+                    .update_fingerprint_function(client)
+                    .await
+            }
         }
     }
 
@@ -1436,6 +1479,11 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.update_view(client).await,
             TrendMaterialization::Function(m) => m.update_function(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization()
+                    .update_function(client)
+                    .await
+            }
         }
     }
 
@@ -1454,6 +1502,9 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.create(client).await,
             TrendMaterialization::Function(m) => m.create(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization().create(client).await
+            }
         }
     }
 
@@ -1464,6 +1515,9 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => m.delete(client).await,
             TrendMaterialization::Function(m) => m.delete(client).await,
+            TrendMaterialization::Declarative(m) => {
+                m.to_function_materialization().delete(client).await
+            }
         }
     }
 
@@ -1472,7 +1526,7 @@ impl TrendMaterialization {
         match self {
             TrendMaterialization::View(m) => match other {
                 TrendMaterialization::View(other_m) => m.diff(other_m),
-                TrendMaterialization::Function(_) => {
+                TrendMaterialization::Function(_) | TrendMaterialization::Declarative(_) => {
                     println!(
                         "Mismatching materialization type for materialization '{}'",
                         self.name()
@@ -1489,7 +1543,19 @@ impl TrendMaterialization {
                     vec![]
                 }
                 TrendMaterialization::Function(other_m) => m.diff(other_m),
+                TrendMaterialization::Declarative(other_m) => match other_m.validate() {
+                    Ok(()) => m.diff(&other_m.to_function_materialization()),
+                    Err(e) => {
+                        println!("Invalid materialization '{}': {}", self.name(), e.msg);
+                        vec![]
+                    }
+                },
             },
+            TrendMaterialization::Declarative(m) => {
+                // A declarative materialization is implemented as a function materialization, so
+                // compare the generated function materialization.
+                TrendMaterialization::Function(m.to_function_materialization()).diff(other)
+            }
         }
     }
 }
@@ -1506,6 +1572,11 @@ pub fn trend_materialization_from_config(
         serde_yaml::from_reader(f);
 
     match deserialize_result {
+        Ok(TrendMaterialization::Declarative(materialization)) => {
+            materialization.validate()?;
+
+            Ok(TrendMaterialization::Declarative(materialization))
+        }
         Ok(materialization) => Ok(materialization),
         Err(e) => Err(Error::Runtime(RuntimeError::from_msg(format!(
             "could not deserialize materialization: {e}"
@@ -2636,6 +2707,13 @@ pub async fn check_trend_materialization<T: GenericClient + Send + Sync>(
         TrendMaterialization::Function(function_materialization) => {
             check_function_materialization(client, function_materialization).await
         }
+        TrendMaterialization::Declarative(declarative_materialization) => {
+            check_function_materialization(
+                client,
+                &declarative_materialization.to_function_materialization(),
+            )
+            .await
+        }
     }
 }
 
@@ -0,0 +1,489 @@
+use postgres_protocol::escape::{escape_identifier, escape_literal};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConfigurationError;
+
+use super::{
+    TrendFunctionMaterialization, TrendMaterializationAttributes, TrendMaterializationFunction,
+    TrendMaterializationRelationSource, TrendMaterializationSource,
+    TrendMaterializationTrendSource, map_sql_to_plpgsql,
+};
+
+pub const DEFAULT_MAPPING_FUNCTION: &str = "trend.mapping_id";
+
+/// A source trend store part of a declarative materialization. Only the name is required when
+/// the timestamps of the source are the same as those of the target, otherwise the mapping
+/// function from source to target timestamps, e.g. `trend.mapping_15m->1h`, is specified.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum TrendDeclarativeMaterializationSource {
+    TrendStorePart(String),
+    Mapped {
+        trend_store_part: String,
+        mapping_function: String,
+    },
+}
+
+impl TrendDeclarativeMaterializationSource {
+    pub fn trend_store_part(&self) -> &str {
+        match self {
+            TrendDeclarativeMaterializationSource::TrendStorePart(trend_store_part) => {
+                trend_store_part
+            }
+            TrendDeclarativeMaterializationSource::Mapped {
+                trend_store_part, ..
+            } => trend_store_part,
+        }
+    }
+
+    pub fn mapping_function(&self) -> &str {
+        match self {
+            TrendDeclarativeMaterializationSource::TrendStorePart(_) => DEFAULT_MAPPING_FUNCTION,
+            TrendDeclarativeMaterializationSource::Mapped {
+                mapping_function, ..
+            } => mapping_function,
+        }
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.mapping_function() != DEFAULT_MAPPING_FUNCTION
+    }
+
+    /// Expression that maps the timestamp column of the source to the target timestamp
+    fn target_timestamp(&self, column: &str) -> String {
+        if self.is_mapped() {
+            let function: Vec<String> = self
+                .mapping_function()
+                .splitn(2, '.')
+                .map(escape_identifier)
+                .collect();
+
+            format!("{}({column})", function.join("."))
+        } else {
+            column.to_string()
+        }
+    }
+}
+
+/// A trend of the target trend store part that is calculated from an SQL expression over the
+/// trends of the source trend store parts.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TrendDeclarativeMaterializationTrend {
+    pub name: String,
+    pub data_type: String,
+    pub expression: String,
+}
+
+/// Materialization that is defined by expressions for the target trends instead of hand written
+/// SQL. The materialization function, fingerprint function and sources are generated from the
+/// definition.
+///
+/// Without a relation, the expressions are evaluated per entity and can refer directly to the
+/// trends of the source trend store parts. With a relation, the source entities are mapped to
+/// the target entities of the relation and the expressions must be aggregates, e.g.
+/// `sum("bytes")`. With `relation_history`, the relation is used as it was at the data
+/// timestamp. For a `<type>->entity_set` relation, these are the members of the entity sets at
+/// the data timestamp, as recorded in their revisions.
+///
+/// Sources with a mapping function other than `trend.mapping_id` contribute all rows that map
+/// to the target timestamp, so the expressions must then be aggregates as well. Sources with the
+/// same mapping function as the first source are joined on the timestamp, others only on the
+/// entity.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrendDeclarativeMaterialization {
+    pub target_trend_store_part: String,
+    #[serde(flatten)]
+    pub attributes: TrendMaterializationAttributes,
+    pub source_trend_store_parts: Vec<TrendDeclarativeMaterializationSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relation: Option<String>,
+    #[serde(default)]
+    pub relation_history: bool,
+    pub trends: Vec<TrendDeclarativeMaterializationTrend>,
+}
+
+impl TrendDeclarativeMaterialization {
+    pub fn name(&self) -> String {
+        self.target_trend_store_part.clone()
+    }
+
+    /// Check that the definition can be translated into a function materialization.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if there are no source trend store parts.
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        if self.source_trend_store_parts.is_empty() {
+            return Err(ConfigurationError::from_msg(format!(
+                "Declarative materialization '{}' has no source trend store parts",
+                self.target_trend_store_part
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Source alias as used in the generated SQL, e.g. 't1' for the first source.
+    fn source_alias(index: usize) -> String {
+        format!("t{}", index + 1)
+    }
+
+    pub fn sources(&self) -> Vec<TrendMaterializationSource> {
+        let mut sources: Vec<TrendMaterializationSource> = self
+            .source_trend_store_parts
+            .iter()
+            .map(|source| {
+                TrendMaterializationSource::Trend(TrendMaterializationTrendSource {
+                    trend_store_part: source.trend_store_part().to_string(),
+                    mapping_function: source.mapping_function().to_string(),
+                })
+            })
+            .collect();
+
+        if let Some(relation) = &self.relation {
+            sources.push(TrendMaterializationSource::Relation(
+                TrendMaterializationRelationSource {
+                    relation: relation.clone(),
+                },
+            ));
+        }
+
+        sources
+    }
+
+    pub fn return_type(&self) -> String {
+        let mut result_columns = vec![
+            "    \"entity_id\" integer".to_string(),
+            "    \"timestamp\" timestamp with time zone".to_string(),
+        ];
+
+        result_columns.extend(
+            self.trends
+                .iter()
+                .map(|trend| format!("    {} {}", escape_identifier(&trend.name), trend.data_type)),
+        );
+
+        format!("TABLE (\n{}\n)\n", result_columns.join(",\n"))
+    }
+
+    /// Generate the SQL query that produces the materialized data for the timestamp passed as
+    /// parameter $1.
+    pub fn query(&self) -> String {
+        let first_alias = Self::source_alias(0);
+
+        let entity_id_expression = match &self.relation {
+            Some(_) => "r.target_id".to_string(),
+            None => format!("{first_alias}.entity_id"),
+        };
+
+        let mut columns = vec![
+            format!("  {entity_id_expression} AS entity_id"),
+            "  $1 AS timestamp".to_string(),
+        ];
+
+        columns.extend(self.trends.iter().map(|trend| {
+            format!(
+                "  ({})::{} AS {}",
+                trend.expression,
+                trend.data_type,
+                escape_identifier(&trend.name)
+            )
+        }));
+
+        let mut from_lines: Vec<String> = Vec::new();
+
+        for (index, source) in self.source_trend_store_parts.iter().enumerate() {
+            let alias = Self::source_alias(index);
+            let trend_store_part = escape_identifier(source.trend_store_part());
+
+            if index == 0 {
+                from_lines.push(format!("FROM trend.{trend_store_part} {alias}\n"));
+            } else if source.mapping_function()
+                == self.source_trend_store_parts[0].mapping_function()
+            {
+                from_lines.push(format!(
+                    "JOIN trend.{trend_store_part} {alias} ON {alias}.entity_id = {first_alias}.entity_id AND {alias}.timestamp = {first_alias}.timestamp\n"
+                ));
+            } else {
+                from_lines.push(format!(
+                    "JOIN trend.{trend_store_part} {alias} ON {alias}.entity_id = {first_alias}.entity_id AND {} = $1\n",
+                    source.target_timestamp(&format!("{alias}.timestamp"))
+                ));
+            }
+        }
+
+        if let Some(relation) = &self.relation {
+            if self.relation_history
+                && let Some(entity_type) = relation.strip_suffix("->entity_set")
+            {
+                from_lines.push(format!(
+                    concat!(
+                        "JOIN (SELECT m.entity_id AS source_id, es.entity_id AS target_id ",
+                        "FROM directory.entity_set es ",
+                        "JOIN directory.entity_type et ON et.id = es.entity_type_id ",
+                        "CROSS JOIN LATERAL entity_set.get_members_at(es.entity_id, $1) m(entity_id) ",
+                        "WHERE et.name = {}) r ON r.source_id = {}.entity_id\n"
+                    ),
+                    escape_literal(entity_type),
+                    first_alias
+                ));
+            } else if self.relation_history {
+                from_lines.push(format!(
+                    "JOIN relation_history.{} r ON r.source_id = {first_alias}.entity_id AND r.valid_from <= $1 AND (r.valid_to IS NULL OR r.valid_to > $1)\n",
+                    escape_identifier(relation)
+                ));
+            } else {
+                from_lines.push(format!(
+                    "JOIN relation.{} r ON r.source_id = {first_alias}.entity_id\n",
+                    escape_identifier(relation)
+                ));
+            }
+        }
+
+        let mut lines: Vec<String> = vec!["SELECT\n".to_string(), columns.join(",\n"), "\n".into()];
+
+        lines.extend(from_lines);
+        lines.push(format!(
+            "WHERE {} = $1\n",
+            self.source_trend_store_parts[0].target_timestamp(&format!("{first_alias}.timestamp"))
+        ));
+
+        if self.relation.is_some() {
+            lines.push("GROUP BY r.target_id\n".to_string());
+        } else if self
+            .source_trend_store_parts
+            .iter()
+            .any(TrendDeclarativeMaterializationSource::is_mapped)
+        {
+            lines.push(format!("GROUP BY {first_alias}.entity_id\n"));
+        }
+
+        lines.join("")
+    }
+
+    pub fn function(&self) -> TrendMaterializationFunction {
+        TrendMaterializationFunction {
+            return_type: self.return_type(),
+            src: map_sql_to_plpgsql(self.query()),
+            language: "plpgsql".to_string(),
+        }
+    }
+
+    /// Generate a fingerprint function body that combines the modified timestamps of all source
+    /// trend store parts. For a mapped source, this is the last modification of any of the
+    /// source timestamps that map to the target timestamp.
+    pub fn fingerprint_function(&self) -> String {
+        let mut modifieds: Vec<String> = Vec::new();
+        let mut formats: Vec<String> = Vec::new();
+        let mut format_args: Vec<String> = Vec::new();
+        let mut joins: Vec<String> = Vec::new();
+
+        for (i, source) in (1..).zip(self.source_trend_store_parts.iter()) {
+            modifieds.push(format!("modified{i}.last"));
+            formats.push("\"%s\": \"%s\"".to_string());
+            format_args.push(format!("part{i}.name, modified{i}.last"));
+            joins.push(format!(
+                "JOIN trend_directory.trend_store_part part{i} ON part{i}.name = {}\n",
+                escape_literal(source.trend_store_part()),
+            ));
+
+            if source.is_mapped() {
+                joins.push(format!(
+                    "JOIN LATERAL (SELECT max(m.last) AS last FROM trend_directory.modified m WHERE m.trend_store_part_id = part{i}.id AND {} = t.timestamp HAVING count(*) > 0) modified{i} ON true\n",
+                    source.target_timestamp("m.timestamp")
+                ));
+            } else {
+                joins.push(format!(
+                    "JOIN trend_directory.modified modified{i} ON modified{i}.trend_store_part_id = part{i}.id AND modified{i}.timestamp = t.timestamp\n"
+                ));
+            }
+        }
+
+        format!(
+            "SELECT\n  greatest({}),\n  format('{{{}}}', {})::jsonb\nFROM (values($1)) as t(timestamp)\n{}",
+            modifieds.join(", "),
+            formats.join(", "),
+            format_args.join(", "),
+            joins.join("")
+        )
+    }
+
+    /// Translate the declarative definition into the function materialization that implements
+    /// it.
+    pub fn to_function_materialization(&self) -> TrendFunctionMaterialization {
+        TrendFunctionMaterialization {
+            target_trend_store_part: self.target_trend_store_part.clone(),
+            attributes: self.attributes.clone(),
+            sources: self.sources(),
+            function: self.function(),
+            fingerprint_function: self.fingerprint_function(),
+        }
+    }
+}
+
+impl From<&TrendDeclarativeMaterialization> for TrendFunctionMaterialization {
+    fn from(value: &TrendDeclarativeMaterialization) -> Self {
+        value.to_function_materialization()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trend_materialization::TrendMaterialization;
+
+    const DEFINITION: &str = r#"target_trend_store_part: kpi-site_main_15m
+enabled: true
+processing_delay: 30m
+stability_delay: 5m
+reprocessing_period: 3 days
+source_trend_store_parts:
+- hub_cell_main_15m
+- hub_cell_extra_15m
+relation: cell->site
+trends:
+- name: throughput
+  data_type: numeric
+  expression: sum("bytes") / nullif(sum("duration"), 0)
+"#;
+
+    #[test]
+    fn declarative_materialization_deserialization() {
+        let materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        assert_eq!(materialization.target_trend_store_part, "kpi-site_main_15m");
+        assert_eq!(materialization.relation, Some("cell->site".to_string()));
+        assert_eq!(materialization.trends.len(), 1);
+    }
+
+    #[test]
+    fn declarative_materialization_untagged_deserialization() {
+        let materialization: TrendMaterialization = serde_yaml::from_str(DEFINITION).unwrap();
+
+        assert!(matches!(
+            materialization,
+            TrendMaterialization::Declarative(_)
+        ));
+    }
+
+    #[test]
+    fn declarative_materialization_query() {
+        let materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        let expected = concat!(
+            "SELECT\n",
+            "  r.target_id AS entity_id,\n",
+            "  $1 AS timestamp,\n",
+            "  (sum(\"bytes\") / nullif(sum(\"duration\"), 0))::numeric AS \"throughput\"\n",
+            "FROM trend.\"hub_cell_main_15m\" t1\n",
+            "JOIN trend.\"hub_cell_extra_15m\" t2 ON t2.entity_id = t1.entity_id AND t2.timestamp = t1.timestamp\n",
+            "JOIN relation.\"cell->site\" r ON r.source_id = t1.entity_id\n",
+            "WHERE t1.timestamp = $1\n",
+            "GROUP BY r.target_id\n",
+        );
+
+        assert_eq!(materialization.query(), expected);
+    }
+
+    #[test]
+    fn declarative_materialization_sources() {
+        let materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        let function_materialization = materialization.to_function_materialization();
+
+        assert_eq!(function_materialization.sources.len(), 3);
+        assert!(pg_query::parse(&materialization.query().replace("$1", "now()")).is_ok());
+        assert!(pg_query::parse(&materialization.fingerprint_function()).is_ok());
+    }
+
+    #[test]
+    fn declarative_materialization_without_sources() {
+        let mut materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        materialization.source_trend_store_parts.clear();
+
+        assert!(materialization.validate().is_err());
+    }
+
+    #[test]
+    fn declarative_materialization_mapped_source_query() {
+        let definition = r#"target_trend_store_part: hub-cell_main_1h
+enabled: true
+processing_delay: 30m
+stability_delay: 5m
+reprocessing_period: 3 days
+source_trend_store_parts:
+- trend_store_part: hub_cell_main_15m
+  mapping_function: trend.mapping_15m->1h
+- hub_cell_extra_1h
+trends:
+- name: bytes
+  data_type: numeric
+  expression: sum(t1."bytes")
+"#;
+
+        let materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(definition).unwrap();
+
+        let expected = concat!(
+            "SELECT\n",
+            "  t1.entity_id AS entity_id,\n",
+            "  $1 AS timestamp,\n",
+            "  (sum(t1.\"bytes\"))::numeric AS \"bytes\"\n",
+            "FROM trend.\"hub_cell_main_15m\" t1\n",
+            "JOIN trend.\"hub_cell_extra_1h\" t2 ON t2.entity_id = t1.entity_id AND t2.timestamp = $1\n",
+            "WHERE \"trend\".\"mapping_15m->1h\"(t1.timestamp) = $1\n",
+            "GROUP BY t1.entity_id\n",
+        );
+
+        assert_eq!(materialization.query(), expected);
+
+        let function_materialization = materialization.to_function_materialization();
+
+        assert!(matches!(
+            &function_materialization.sources[0],
+            TrendMaterializationSource::Trend(source) if source.mapping_function == "trend.mapping_15m->1h"
+        ));
+        assert!(pg_query::parse(&materialization.query().replace("$1", "now()")).is_ok());
+        assert!(pg_query::parse(&materialization.fingerprint_function()).is_ok());
+    }
+
+    #[test]
+    fn declarative_materialization_relation_history_query() {
+        let mut materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        materialization.relation_history = true;
+
+        let query = materialization.query();
+
+        assert!(query.contains(
+            "JOIN relation_history.\"cell->site\" r ON r.source_id = t1.entity_id AND r.valid_from <= $1 AND (r.valid_to IS NULL OR r.valid_to > $1)\n"
+        ));
+        assert!(pg_query::parse(&query.replace("$1", "now()")).is_ok());
+    }
+
+    #[test]
+    fn declarative_materialization_entity_set_members_at_query() {
+        let mut materialization: TrendDeclarativeMaterialization =
+            serde_yaml::from_str(DEFINITION).unwrap();
+
+        materialization.relation = Some("cell->entity_set".to_string());
+        materialization.relation_history = true;
+
+        let query = materialization.query();
+
+        assert!(query.contains(concat!(
+            "JOIN (SELECT m.entity_id AS source_id, es.entity_id AS target_id ",
+            "FROM directory.entity_set es ",
+            "JOIN directory.entity_type et ON et.id = es.entity_type_id ",
+            "CROSS JOIN LATERAL entity_set.get_members_at(es.entity_id, $1) m(entity_id) ",
+            "WHERE et.name = 'cell') r ON r.source_id = t1.entity_id\n"
+        )));
+        assert!(pg_query::parse(&query.replace("$1", "now()")).is_ok());
+    }
+}
@@ -0,0 +1,538 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use postgres_protocol::escape::escape_identifier;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde_json::Value;
+use thiserror::Error;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{GenericClient, Row};
+
+use crate::entity::{EntityMappingError, alias_table_name};
+use crate::meas_value::{DataType, MeasValue};
+use crate::trend_store::{TrendStoreRef, load_trend_store};
+
+#[derive(Error, Debug)]
+pub enum TrendQueryError {
+    #[error("Could not load trend store: {0}")]
+    TrendStore(String),
+    #[error("No trend '{0}' in trend store")]
+    UnknownTrend(String),
+    #[error("No trends specified")]
+    NoTrends,
+    #[error("No alias type '{0}' for the entity type")]
+    UnknownAliasType(String),
+    #[error("{0}")]
+    Database(String),
+}
+
+/// Selection of the entities to query the data of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrendQueryEntities {
+    All,
+    Names(Vec<String>),
+    EntitySet { owner: String, name: String },
+}
+
+/// Query for trend data of a trend store. The trends are resolved to the parts of the trend
+/// store that contain them, so the caller does not need to know how the trends are divided over
+/// parts. Besides the primary alias, the aliases of named alias types can be resolved.
+#[derive(Debug, Clone)]
+pub struct TrendQuery {
+    pub data_source: String,
+    pub entity_type: String,
+    pub granularity: Duration,
+    pub trends: Vec<String>,
+    pub alias_types: Vec<String>,
+    pub entities: TrendQueryEntities,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendQueryRow {
+    pub entity_id: i64,
+    pub entity: String,
+    pub alias: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub values: Vec<MeasValue>,
+    /// Aliases of the requested alias types, in the same order
+    pub aliases: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendQueryResult {
+    pub trends: Vec<String>,
+    pub alias_types: Vec<String>,
+    pub rows: Vec<TrendQueryRow>,
+}
+
+/// A trend resolved to the trend store part that contains it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResolvedTrend {
+    trend_store_part: String,
+    name: String,
+    data_type: DataType,
+}
+
+impl TrendQuery {
+    #[must_use]
+    pub fn new(data_source: &str, entity_type: &str, granularity: Duration) -> Self {
+        TrendQuery {
+            data_source: data_source.to_string(),
+            entity_type: entity_type.to_string(),
+            granularity,
+            trends: Vec::new(),
+            alias_types: Vec::new(),
+            entities: TrendQueryEntities::All,
+            start: None,
+            end: None,
+            limit: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_trends<S: AsRef<str>>(mut self, trends: &[S]) -> Self {
+        self.trends = trends.iter().map(|t| t.as_ref().to_string()).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn with_alias_types<S: AsRef<str>>(mut self, alias_types: &[S]) -> Self {
+        self.alias_types = alias_types.iter().map(|t| t.as_ref().to_string()).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn with_entities<S: AsRef<str>>(mut self, names: &[S]) -> Self {
+        self.entities =
+            TrendQueryEntities::Names(names.iter().map(|n| n.as_ref().to_string()).collect());
+        self
+    }
+
+    #[must_use]
+    pub fn with_entity_set(mut self, owner: &str, name: &str) -> Self {
+        self.entities = TrendQueryEntities::EntitySet {
+            owner: owner.to_string(),
+            name: name.to_string(),
+        };
+        self
+    }
+
+    /// Only query data with a timestamp from `start` up to and including `end`
+    #[must_use]
+    pub fn with_time_range(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+
+    #[must_use]
+    pub fn with_limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Condition on the `entity_id` column of a trend store part for the entity selection,
+    /// using the parameter $3 (and $4 for an entity set).
+    fn entity_condition(&self) -> Option<String> {
+        match &self.entities {
+            TrendQueryEntities::All => None,
+            TrendQueryEntities::Names(_) => Some(format!(
+                "entity_id IN (SELECT id FROM entity.{} WHERE name = ANY($3))",
+                escape_identifier(&self.entity_type)
+            )),
+            TrendQueryEntities::EntitySet { .. } => Some(format!(
+                concat!(
+                    "entity_id IN (SELECT r.source_id FROM relation.{} r ",
+                    "JOIN attribute.minerva_entity_set es ON es.entity_id = r.target_id ",
+                    "WHERE es.owner = $3 AND es.name = $4)"
+                ),
+                escape_identifier(&format!("{}->entity_set", self.entity_type))
+            )),
+        }
+    }
+
+    /// Generate the query for the resolved trends and the tables of the alias types. The
+    /// timestamps of the range are parameters $1 and $2, and can be NULL for an open range.
+    fn query(
+        &self,
+        trends: &[ResolvedTrend],
+        alias_column: bool,
+        alias_tables: &[String],
+    ) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+
+        for trend in trends {
+            if !parts.contains(&trend.trend_store_part.as_str()) {
+                parts.push(&trend.trend_store_part);
+            }
+        }
+
+        let mut conditions = vec![
+            "($1::timestamptz IS NULL OR timestamp >= $1)".to_string(),
+            "($2::timestamptz IS NULL OR timestamp <= $2)".to_string(),
+        ];
+
+        if let Some(entity_condition) = self.entity_condition() {
+            conditions.push(entity_condition);
+        }
+
+        let keys = parts
+            .iter()
+            .map(|part| {
+                format!(
+                    "SELECT entity_id, timestamp FROM trend.{} WHERE {}",
+                    escape_identifier(part),
+                    conditions.join(" AND ")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" UNION ");
+
+        let mut columns = vec![
+            "k.entity_id::bigint".to_string(),
+            "e.name".to_string(),
+            if alias_column {
+                "e.primary_alias".to_string()
+            } else {
+                "NULL::text".to_string()
+            },
+            "k.timestamp".to_string(),
+        ];
+
+        columns.extend(trends.iter().map(|trend| {
+            let index = parts
+                .iter()
+                .position(|part| *part == trend.trend_store_part)
+                .unwrap_or_default();
+
+            format!("t{}.{}", index + 1, escape_identifier(&trend.name))
+        }));
+
+        columns.extend((1..=alias_tables.len()).map(|i| format!("a{i}.alias")));
+
+        let joins = parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                format!(
+                    "LEFT JOIN trend.{} t{i} ON t{i}.entity_id = k.entity_id AND t{i}.timestamp = k.timestamp",
+                    escape_identifier(part),
+                    i = index + 1
+                )
+            })
+            .chain(alias_tables.iter().enumerate().map(|(index, alias_table)| {
+                format!(
+                    "LEFT JOIN alias.{} a{i} ON a{i}.entity_id = k.entity_id",
+                    escape_identifier(alias_table),
+                    i = index + 1
+                )
+            }))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let mut query = format!(
+            "WITH k AS ({keys}) SELECT {} FROM k JOIN entity.{} e ON e.id = k.entity_id {joins} ORDER BY k.timestamp, e.name",
+            columns.join(", "),
+            escape_identifier(&self.entity_type),
+        );
+
+        if let Some(limit) = self.limit {
+            query.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        query
+    }
+
+    async fn resolve_trends<T: GenericClient + Sync>(
+        &self,
+        client: &T,
+    ) -> Result<Vec<ResolvedTrend>, TrendQueryError> {
+        if self.trends.is_empty() {
+            return Err(TrendQueryError::NoTrends);
+        }
+
+        let trend_store_ref = TrendStoreRef {
+            data_source: self.data_source.clone(),
+            entity_type: self.entity_type.clone(),
+            granularity: self.granularity,
+        };
+
+        let trend_store = load_trend_store(client, &trend_store_ref)
+            .await
+            .map_err(|e| TrendQueryError::TrendStore(e.to_string()))?;
+
+        self.trends
+            .iter()
+            .map(|trend_name| {
+                trend_store
+                    .parts
+                    .iter()
+                    .find_map(|part| {
+                        part.trends
+                            .iter()
+                            .find(|trend| trend.name == *trend_name)
+                            .map(|trend| ResolvedTrend {
+                                trend_store_part: part.name.clone(),
+                                name: trend.name.clone(),
+                                data_type: trend.data_type,
+                            })
+                    })
+                    .ok_or_else(|| TrendQueryError::UnknownTrend(trend_name.clone()))
+            })
+            .collect()
+    }
+
+    pub async fn execute<T: GenericClient + Sync>(
+        &self,
+        client: &T,
+    ) -> Result<TrendQueryResult, TrendQueryError> {
+        let trends = self.resolve_trends(client).await?;
+
+        let alias_row = client
+            .query_one(
+                "SELECT primary_alias IS NOT NULL FROM directory.entity_type WHERE name = $1",
+                &[&self.entity_type],
+            )
+            .await
+            .map_err(|e| TrendQueryError::Database(format!("Could not load entity type: {e}")))?;
+
+        let mut alias_tables: Vec<String> = Vec::new();
+
+        for alias_type in &self.alias_types {
+            let alias_table = alias_table_name(client, &self.entity_type, alias_type)
+                .await
+                .map_err(|e| match e {
+                    EntityMappingError::UnknownAliasTypeError(name) => {
+                        TrendQueryError::UnknownAliasType(name)
+                    }
+                    e => TrendQueryError::Database(format!("Could not load alias type: {e}")),
+                })?;
+
+            alias_tables.push(alias_table);
+        }
+
+        let query = self.query(&trends, alias_row.get(0), &alias_tables);
+
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![&self.start, &self.end];
+
+        match &self.entities {
+            TrendQueryEntities::All => {}
+            TrendQueryEntities::Names(names) => params.push(names),
+            TrendQueryEntities::EntitySet { owner, name } => {
+                params.push(owner);
+                params.push(name);
+            }
+        }
+
+        let rows = client
+            .query(&query, &params)
+            .await
+            .map_err(|e| TrendQueryError::Database(format!("Could not query trend data: {e}")))?;
+
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let values = trends
+                    .iter()
+                    .enumerate()
+                    .map(|(index, trend)| meas_value_from_row(row, index + 4, trend.data_type))
+                    .collect::<Result<Vec<MeasValue>, tokio_postgres::Error>>()?;
+
+                let aliases = (0..alias_tables.len())
+                    .map(|index| row.try_get(index + 4 + trends.len()))
+                    .collect::<Result<Vec<Option<String>>, tokio_postgres::Error>>()?;
+
+                Ok(TrendQueryRow {
+                    entity_id: row.get(0),
+                    entity: row.get(1),
+                    alias: row.get(2),
+                    timestamp: row.get(3),
+                    values,
+                    aliases,
+                })
+            })
+            .collect::<Result<Vec<TrendQueryRow>, tokio_postgres::Error>>()
+            .map_err(|e| TrendQueryError::Database(format!("Could not read trend data: {e}")))?;
+
+        Ok(TrendQueryResult {
+            trends: trends.into_iter().map(|trend| trend.name).collect(),
+            alias_types: self.alias_types.clone(),
+            rows,
+        })
+    }
+}
+
+fn meas_value_from_row(
+    row: &Row,
+    index: usize,
+    data_type: DataType,
+) -> Result<MeasValue, tokio_postgres::Error> {
+    Ok(match data_type {
+        DataType::Int2 => MeasValue::Int2(row.try_get(index)?),
+        DataType::Integer => MeasValue::Integer(row.try_get(index)?),
+        DataType::Int8 => MeasValue::Int8(row.try_get(index)?),
+        DataType::Real => MeasValue::Real(row.try_get(index)?),
+        DataType::Double => MeasValue::Double(row.try_get(index)?),
+        DataType::Numeric => MeasValue::Numeric(row.try_get::<_, Option<Decimal>>(index)?),
+        DataType::Timestamp => row
+            .try_get::<_, Option<DateTime<Utc>>>(index)?
+            .map_or(MeasValue::Null(data_type), MeasValue::Timestamp),
+        DataType::TextArray => row
+            .try_get::<_, Option<Vec<String>>>(index)?
+            .map_or(MeasValue::Null(data_type), MeasValue::TextArray),
+        DataType::Text => row
+            .try_get::<_, Option<String>>(index)?
+            .map_or(MeasValue::Null(data_type), MeasValue::Text),
+        DataType::Boolean => MeasValue::Boolean(row.try_get(index)?),
+        DataType::NumericArray => {
+            MeasValue::NumericArray(row.try_get::<_, Option<Vec<Decimal>>>(index)?)
+        }
+    })
+}
+
+/// JSON representation of a value, with NULL values as JSON null
+#[must_use]
+pub fn meas_value_to_json(value: &MeasValue) -> Value {
+    match value {
+        MeasValue::Int2(v) => v.map_or(Value::Null, Value::from),
+        MeasValue::Integer(v) => v.map_or(Value::Null, Value::from),
+        MeasValue::Int8(v) => v.map_or(Value::Null, Value::from),
+        MeasValue::Real(v) => v.map_or(Value::Null, |v| Value::from(f64::from(v))),
+        MeasValue::Double(v) => v.map_or(Value::Null, Value::from),
+        MeasValue::Numeric(v) => v.and_then(|v| v.to_f64()).map_or(Value::Null, Value::from),
+        MeasValue::Text(v) => Value::from(v.clone()),
+        MeasValue::TextArray(v) => Value::from(v.clone()),
+        MeasValue::Timestamp(v) => Value::from(v.to_rfc3339()),
+        MeasValue::Boolean(v) => v.map_or(Value::Null, Value::from),
+        MeasValue::NumericArray(v) => v.as_ref().map_or(Value::Null, |values| {
+            values
+                .iter()
+                .map(|value| value.to_f64().map_or(Value::Null, Value::from))
+                .collect()
+        }),
+        MeasValue::Null(_) => Value::Null,
+    }
+}
+
+/// Text representation of a value for tabular output, with NULL values as empty strings
+#[must_use]
+pub fn meas_value_to_text(value: &MeasValue) -> String {
+    match value {
+        MeasValue::TextArray(v) => v.join(","),
+        MeasValue::Timestamp(v) => v.to_rfc3339(),
+        MeasValue::NumericArray(Some(v)) => v
+            .iter()
+            .map(Decimal::to_string)
+            .collect::<Vec<String>>()
+            .join(","),
+        value => match meas_value_to_json(value) {
+            Value::Null => String::new(),
+            _ => value.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trends() -> Vec<ResolvedTrend> {
+        vec![
+            ResolvedTrend {
+                trend_store_part: "hub_cell_main_15m".to_string(),
+                name: "bytes_up".to_string(),
+                data_type: DataType::Int8,
+            },
+            ResolvedTrend {
+                trend_store_part: "hub_cell_extra_15m".to_string(),
+                name: "errors".to_string(),
+                data_type: DataType::Integer,
+            },
+        ]
+    }
+
+    #[test]
+    fn trend_query_over_parts() {
+        let query = TrendQuery::new("hub", "cell", Duration::from_secs(900))
+            .with_entity_set("ops", "highway")
+            .with_limit(10)
+            .query(&trends(), true, &[]);
+
+        assert!(
+            query.contains("SELECT entity_id, timestamp FROM trend.\"hub_cell_main_15m\" WHERE")
+        );
+        assert!(query.contains(
+            " UNION SELECT entity_id, timestamp FROM trend.\"hub_cell_extra_15m\" WHERE"
+        ));
+        assert!(query.contains("relation.\"cell->entity_set\" r"));
+        assert!(
+            query.contains("e.primary_alias, k.timestamp, t1.\"bytes_up\", t2.\"errors\" FROM k")
+        );
+        assert!(query.ends_with("ORDER BY k.timestamp, e.name LIMIT 10"));
+        assert!(pg_query::parse(&query).is_ok());
+    }
+
+    #[test]
+    fn trend_query_for_entity_names() {
+        let query = TrendQuery::new("hub", "cell", Duration::from_secs(900))
+            .with_entities(&["c1", "c2"])
+            .query(&trends()[..1], false, &[]);
+
+        assert!(
+            query.contains("entity_id IN (SELECT id FROM entity.\"cell\" WHERE name = ANY($3))")
+        );
+        assert!(query.contains("NULL::text, k.timestamp"));
+        assert!(!query.contains("UNION"));
+        assert!(pg_query::parse(&query).is_ok());
+    }
+
+    #[test]
+    fn trend_query_with_alias_types() {
+        let query = TrendQuery::new("hub", "cell", Duration::from_secs(900))
+            .with_alias_types(&["vendor_id"])
+            .query(&trends()[..1], true, &["cell_vendor_id".to_string()]);
+
+        assert!(query.contains("t1.\"bytes_up\", a1.alias FROM k"));
+        assert!(query.contains(
+            "LEFT JOIN alias.\"cell_vendor_id\" a1 ON a1.entity_id = k.entity_id ORDER BY"
+        ));
+        assert!(pg_query::parse(&query).is_ok());
+    }
+
+    #[test]
+    fn meas_value_json() {
+        assert_eq!(
+            meas_value_to_json(&MeasValue::Int8(Some(42))),
+            Value::from(42)
+        );
+        assert_eq!(meas_value_to_json(&MeasValue::Double(None)), Value::Null);
+        assert_eq!(meas_value_to_text(&MeasValue::Integer(None)), "");
+        assert_eq!(
+            meas_value_to_json(&MeasValue::Null(DataType::Timestamp)),
+            Value::Null
+        );
+        assert_eq!(
+            meas_value_to_json(&MeasValue::Boolean(Some(true))),
+            Value::from(true)
+        );
+        assert_eq!(
+            meas_value_to_json(&MeasValue::NumericArray(Some(vec![
+                Decimal::new(15, 1),
+                Decimal::from(2)
+            ]))),
+            serde_json::json!([1.5, 2.0])
+        );
+        assert_eq!(
+            meas_value_to_text(&MeasValue::NumericArray(Some(vec![
+                Decimal::new(15, 1),
+                Decimal::from(2)
+            ]))),
+            "1.5,2"
+        );
+        assert_eq!(meas_value_to_text(&MeasValue::Null(DataType::Text)), "");
+    }
+}
@@ -22,9 +22,10 @@ use rust_decimal::prelude::*;
 use async_trait::async_trait;
 
 use crate::changes::trend_store::{
-    AddAliasColumn, AddTrendStorePart, AddTrends, ModifyTrendDataType, ModifyTrendDataTypes,
-    ModifyTrendExtraData, ModifyTrendStoreData, RemoveAliasColumn, RemoveTrendStorePart,
-    RemoveTrends, StageTrendsForDeletion,
+    AddAliasColumn, AddGeneratedTrend, AddTrendStorePart, AddTrends, ModifyGeneratedTrend,
+    ModifyTrendDataType, ModifyTrendDataTypes, ModifyTrendExtraData, ModifyTrendStoreData,
+    RemoveAliasColumn, RemoveGeneratedTrend, RemoveTrendStorePart, RemoveTrends,
+    StageTrendsForDeletion,
 };
 use crate::entity::{EntityIdType, EntityMapping, default_entity_id_type};
 use crate::instance::DeploymentIgnore;
@@ -37,10 +38,19 @@ use super::error::{ConfigurationError, DatabaseError, Error, RuntimeError};
 use super::interval::parse_interval;
 
 pub mod create;
+pub mod delete;
+pub mod generated;
+pub mod health;
+pub mod recommend;
 pub mod remove;
+pub mod reorganize;
+pub mod retention;
 
 type PostgresName = String;
 
+/// Columns that are present in every trend store part table next to the trends
+pub const TREND_STORE_PART_COLUMNS: [&str; 4] = ["entity_id", "timestamp", "created", "job_id"];
+
 #[derive(thiserror::Error, Debug)]
 pub enum RawMeasurementStoreError {
     #[error("{0}")]
@@ -1354,6 +1364,89 @@ impl TrendStorePart {
             }
         }
 
+        // The data type of a trend cannot be changed while a generated trend uses it, so these
+        // generated trends are removed before and added after the data type change
+        let uses_modified_trend = |generated_trend: &GeneratedTrend| {
+            generated::expression_references(&generated_trend.expression).is_ok_and(|references| {
+                alter_trend_data_types
+                    .iter()
+                    .any(|modification| references.contains(&modification.trend_name))
+            })
+        };
+
+        let mut generated_trend_additions: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        for other_generated_trend in &other.generated_trends {
+            match self
+                .generated_trends
+                .iter()
+                .find(|my_generated_trend| my_generated_trend.name == other_generated_trend.name)
+            {
+                Some(my_generated_trend) => {
+                    if uses_modified_trend(my_generated_trend) {
+                        changes.push(Box::new(RemoveGeneratedTrend {
+                            trend_store_part_name: self.name.clone(),
+                            generated_trend_name: my_generated_trend.name.clone(),
+                        }));
+                        generated_trend_additions.push(Box::new(AddGeneratedTrend {
+                            trend_store_part_name: self.name.clone(),
+                            generated_trend: other_generated_trend.clone(),
+                        }));
+                    } else if my_generated_trend != other_generated_trend {
+                        let modification = Box::new(ModifyGeneratedTrend {
+                            trend_store_part_name: self.name.clone(),
+                            from: my_generated_trend.clone(),
+                            to: other_generated_trend.clone(),
+                        });
+
+                        if uses_modified_trend(other_generated_trend) {
+                            generated_trend_additions.push(modification);
+                        } else {
+                            changes.push(modification);
+                        }
+                    }
+                }
+                None => {
+                    let addition = Box::new(AddGeneratedTrend {
+                        trend_store_part_name: self.name.clone(),
+                        generated_trend: other_generated_trend.clone(),
+                    });
+
+                    if uses_modified_trend(other_generated_trend) {
+                        generated_trend_additions.push(addition);
+                    } else {
+                        changes.push(addition);
+                    }
+                }
+            }
+        }
+
+        // Generated trends are removed before any trends, because a trend cannot be removed
+        // while a generated trend uses it
+        for my_generated_trend in &self.generated_trends {
+            if !other
+                .generated_trends
+                .iter()
+                .any(|other_generated_trend| other_generated_trend.name == my_generated_trend.name)
+            {
+                if !options.ignore_deletions {
+                    changes.push(Box::new(RemoveGeneratedTrend {
+                        trend_store_part_name: self.name.clone(),
+                        generated_trend_name: my_generated_trend.name.clone(),
+                    }));
+                } else if uses_modified_trend(my_generated_trend) {
+                    changes.push(Box::new(RemoveGeneratedTrend {
+                        trend_store_part_name: self.name.clone(),
+                        generated_trend_name: my_generated_trend.name.clone(),
+                    }));
+                    generated_trend_additions.push(Box::new(AddGeneratedTrend {
+                        trend_store_part_name: self.name.clone(),
+                        generated_trend: my_generated_trend.clone(),
+                    }));
+                }
+            }
+        }
+
         if !self.has_alias_column && other.has_alias_column {
             changes.push(Box::new(AddAliasColumn {
                 trend_store_part_name: self.name.clone(),
@@ -1388,6 +1481,8 @@ impl TrendStorePart {
             }));
         }
 
+        changes.append(&mut generated_trend_additions);
+
         changes
     }
 }
@@ -1420,7 +1515,7 @@ impl TrendStoreDiffOptions {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct TrendStoreRef {
     pub data_source: String,
     pub entity_type: String,
@@ -1789,7 +1884,7 @@ pub async fn load_trend_store_part<T: GenericClient>(
     let trend_query = concat!(
         "SELECT name, data_type, description, entity_aggregation, time_aggregation, extra_data ",
         "FROM trend_directory.table_trend ",
-        "WHERE trend_store_part_id = $1",
+        "WHERE trend_store_part_id = $1 AND deleted IS NULL",
     );
 
     let trend_result = conn.query(trend_query, &[&trend_store_part_id]).await?;
@@ -1814,15 +1909,42 @@ pub async fn load_trend_store_part<T: GenericClient>(
         });
     }
 
+    let generated_trends = load_generated_trends(conn, trend_store_part_id).await?;
+
     Ok(TrendStorePart {
         name: String::from(name),
         trends,
-        generated_trends: Vec::new(),
+        generated_trends,
         has_alias_column,
         entity_id_type,
     })
 }
 
+async fn load_generated_trends<T: GenericClient>(
+    conn: &T,
+    trend_store_part_id: i32,
+) -> Result<Vec<GeneratedTrend>, tokio_postgres::Error> {
+    let query = concat!(
+        "SELECT name::text, data_type, description, expression, extra_data ",
+        "FROM trend_directory.generated_table_trend ",
+        "WHERE trend_store_part_id = $1 ",
+        "ORDER BY id",
+    );
+
+    let rows = conn.query(query, &[&trend_store_part_id]).await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| GeneratedTrend {
+            name: row.get(0),
+            data_type: row.get(1),
+            description: row.get(2),
+            expression: row.get(3),
+            extra_data: row.get(4),
+        })
+        .collect())
+}
+
 async fn load_trend_store_parts<T: GenericClient>(
     conn: &T,
     trend_store_id: i32,
@@ -1896,10 +2018,14 @@ async fn load_trend_store_parts<T: GenericClient>(
             });
         }
 
+        let generated_trends = load_generated_trends(conn, trend_store_part_id)
+            .await
+            .unwrap();
+
         parts.push(TrendStorePart {
             name: String::from(trend_store_part_name),
             trends,
-            generated_trends: Vec::new(),
+            generated_trends,
             has_alias_column,
             entity_id_type,
         });
@@ -2299,6 +2425,59 @@ pub async fn analyze_trend_store_part(
 mod tests {
     use super::*;
 
+    #[test]
+    fn diff_recreates_generated_trends_around_data_type_change() {
+        let trend = |name: &str, data_type: DataType| Trend {
+            name: name.to_string(),
+            data_type,
+            description: String::new(),
+            entity_aggregation: "SUM".to_string(),
+            time_aggregation: "SUM".to_string(),
+            extra_data: json!({}),
+        };
+
+        let generated_trend = |name: &str, expression: &str| GeneratedTrend {
+            name: name.to_string(),
+            data_type: "numeric".to_string(),
+            description: String::new(),
+            expression: expression.to_string(),
+            extra_data: json!({}),
+        };
+
+        let my_part = TrendStorePart {
+            name: "hub_node_main_15m".to_string(),
+            has_alias_column: false,
+            trends: vec![
+                trend("power", DataType::Integer),
+                trend("duration", DataType::Integer),
+            ],
+            generated_trends: vec![
+                generated_trend("power_kw", "power / 1000"),
+                generated_trend("duration_min", "duration / 60"),
+            ],
+            entity_id_type: EntityIdType::I32,
+        };
+
+        let mut other_part = my_part.clone();
+        other_part.trends[0].data_type = DataType::Int8;
+
+        let changes: Vec<String> = my_part
+            .diff(&other_part, &TrendStorePartDiffOptions::default())
+            .iter()
+            .map(|change| change.to_string())
+            .collect();
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[0],
+            "RemoveGeneratedTrend(TrendStorePart(hub_node_main_15m), power_kw)"
+        );
+        assert!(changes[1].starts_with("ModifyTrendDataTypes(hub_node_main_15m"));
+        assert!(
+            changes[2].starts_with("AddGeneratedTrend(TrendStorePart(hub_node_main_15m), power_kw")
+        );
+    }
+
     #[test]
     fn deserialize_trend_with_defaults() {
         let trend_def = concat!(
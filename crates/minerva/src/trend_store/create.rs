@@ -270,7 +270,7 @@ fn trend_column_spec(trend: &Trend) -> String {
     format!("{} {}", escape_identifier(&trend.name), trend.data_type)
 }
 
-fn generated_trend_column_spec(generated_trend: &GeneratedTrend) -> String {
+pub(crate) fn generated_trend_column_spec(generated_trend: &GeneratedTrend) -> String {
     format!(
         "{} {} GENERATED ALWAYS AS ({}) STORED",
         escape_identifier(&generated_trend.name),
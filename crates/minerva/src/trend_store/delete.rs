@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use postgres_protocol::escape::escape_identifier;
+use tokio_postgres::Client;
+
+use crate::error::{DatabaseError, Error, RuntimeError};
+use crate::trend_store::{MeasurementStore, load_trend_store_part, load_trend_store_ref_for_part};
+
+/// Deletion of the data of a trend store part for a range of timestamps, optionally limited to a
+/// subset of entities and trends. The affected timestamps are marked modified, so that dependent
+/// materializations are recomputed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteTrendData {
+    pub trend_store_part: String,
+    /// Start of the range (inclusive)
+    pub from: DateTime<Utc>,
+    /// End of the range (inclusive)
+    pub to: DateTime<Utc>,
+    /// Names of the entities to delete the data of, all entities when `None`
+    pub entities: Option<Vec<String>>,
+    /// Trends to delete the values of, complete rows when `None`
+    pub trends: Option<Vec<String>>,
+}
+
+/// Result of a `DeleteTrendData` operation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeletedTrendData {
+    /// Affected timestamps with the number of affected rows for each
+    pub timestamps: Vec<(DateTime<Utc>, i64)>,
+}
+
+impl DeletedTrendData {
+    #[must_use]
+    pub fn row_count(&self) -> i64 {
+        self.timestamps.iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl DeleteTrendData {
+    #[must_use]
+    pub fn new(trend_store_part: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        DeleteTrendData {
+            trend_store_part: trend_store_part.to_string(),
+            from,
+            to,
+            entities: None,
+            trends: None,
+        }
+    }
+
+    /// Query that deletes the data and returns the number of affected rows per timestamp. When
+    /// trends are specified, only their values are set to NULL and rows without a value for any
+    /// of them are left alone.
+    fn query(&self, entity_type: &str) -> String {
+        let mut conditions = vec!["timestamp BETWEEN $1 AND $2".to_string()];
+
+        if self.entities.is_some() {
+            conditions.push(format!(
+                "entity_id IN (SELECT id FROM entity.{} WHERE name = ANY($3))",
+                escape_identifier(entity_type)
+            ));
+        }
+
+        let table = format!("trend.{}", escape_identifier(&self.trend_store_part));
+
+        let modification = match &self.trends {
+            None => format!("DELETE FROM {table} WHERE {}", conditions.join(" AND ")),
+            Some(trends) => {
+                let columns: Vec<String> = trends
+                    .iter()
+                    .map(|trend| escape_identifier(trend))
+                    .collect();
+
+                conditions.push(format!(
+                    "({})",
+                    columns
+                        .iter()
+                        .map(|column| format!("{column} IS NOT NULL"))
+                        .collect::<Vec<String>>()
+                        .join(" OR ")
+                ));
+
+                format!(
+                    "UPDATE {table} SET {} WHERE {}",
+                    columns
+                        .iter()
+                        .map(|column| format!("{column} = NULL"))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    conditions.join(" AND ")
+                )
+            }
+        };
+
+        format!(
+            "WITH affected AS ({modification} RETURNING timestamp) SELECT timestamp, count(*) FROM affected GROUP BY timestamp ORDER BY timestamp"
+        )
+    }
+
+    /// Delete the data in a single transaction and mark the affected timestamps modified.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the trend store part or one of the trends does not exist, or if the
+    /// data cannot be deleted.
+    pub async fn execute(&self, client: &mut Client) -> Result<DeletedTrendData, Error> {
+        if self.from > self.to {
+            return Err(RuntimeError::from_msg(format!(
+                "Start of range {} is after end {}",
+                self.from, self.to
+            ))
+            .into());
+        }
+
+        let part = load_trend_store_part(client, &self.trend_store_part)
+            .await
+            .map_err(|e| DatabaseError::from_msg(e.to_string()))?;
+
+        if let Some(trends) = &self.trends {
+            if trends.is_empty() {
+                return Err(RuntimeError::from_msg("No trends specified".to_string()).into());
+            }
+
+            if let Some(unknown) = trends
+                .iter()
+                .find(|name| !part.trends.iter().any(|trend| &trend.name == *name))
+            {
+                return Err(RuntimeError::from_msg(format!(
+                    "No trend '{unknown}' in trend store part '{}'",
+                    self.trend_store_part
+                ))
+                .into());
+            }
+        }
+
+        let trend_store = load_trend_store_ref_for_part(client, &self.trend_store_part)
+            .await
+            .map_err(RuntimeError::from_msg)?;
+
+        let query = self.query(&trend_store.entity_type);
+
+        let tx = client.transaction().await?;
+
+        let rows = match &self.entities {
+            Some(entities) => tx.query(&query, &[&self.from, &self.to, entities]).await,
+            None => tx.query(&query, &[&self.from, &self.to]).await,
+        }
+        .map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Could not delete data of '{}': {e}",
+                self.trend_store_part
+            ))
+        })?;
+
+        let timestamps: Vec<(DateTime<Utc>, i64)> =
+            rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+
+        for (timestamp, _) in &timestamps {
+            part.mark_modified(&tx, timestamp)
+                .await
+                .map_err(|e| DatabaseError::from_msg(e.to_string()))?;
+        }
+
+        tx.commit().await?;
+
+        Ok(DeletedTrendData { timestamps })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn delete_trend_data_query() {
+        let mut delete = DeleteTrendData::new(
+            "hub_cell_main_15m",
+            Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2025, 3, 1, 6, 0, 0).unwrap(),
+        );
+
+        assert_eq!(
+            delete.query("cell"),
+            concat!(
+                "WITH affected AS (DELETE FROM trend.\"hub_cell_main_15m\" WHERE timestamp BETWEEN $1 AND $2 RETURNING timestamp) ",
+                "SELECT timestamp, count(*) FROM affected GROUP BY timestamp ORDER BY timestamp"
+            )
+        );
+
+        delete.entities = Some(vec!["cell_1".to_string()]);
+        delete.trends = Some(vec!["bytes_up".to_string(), "bytes_down".to_string()]);
+
+        assert_eq!(
+            delete.query("cell"),
+            concat!(
+                "WITH affected AS (UPDATE trend.\"hub_cell_main_15m\" SET \"bytes_up\" = NULL, \"bytes_down\" = NULL ",
+                "WHERE timestamp BETWEEN $1 AND $2 ",
+                "AND entity_id IN (SELECT id FROM entity.\"cell\" WHERE name = ANY($3)) ",
+                "AND (\"bytes_up\" IS NOT NULL OR \"bytes_down\" IS NOT NULL) RETURNING timestamp) ",
+                "SELECT timestamp, count(*) FROM affected GROUP BY timestamp ORDER BY timestamp"
+            )
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use pg_query::{NodeEnum, NodeRef};
+use thiserror::Error;
+
+use super::{GeneratedTrend, TREND_STORE_PART_COLUMNS, TrendStorePart};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GeneratedTrendError {
+    #[error("Invalid expression of generated trend '{0}': {1}")]
+    Parse(String, String),
+    #[error("Generated trend '{0}' uses unknown trend '{1}'")]
+    UnknownTrend(String, String),
+    #[error("Generated trend '{0}' uses generated trend '{1}', which is not supported")]
+    GeneratedTrendReference(String, String),
+    #[error("Generated trend '{0}' contains a subquery, which is not supported")]
+    Subquery(String),
+    #[error("Generated trend '{0}' has the same name as a trend")]
+    DuplicateName(String),
+}
+
+/// Names of the columns that an expression references, in order of first use
+///
+/// # Errors
+///
+/// Will return `Err` if the expression cannot be parsed.
+pub fn expression_references(expression: &str) -> Result<Vec<String>, pg_query::Error> {
+    let parse_result = pg_query::parse(&format!("SELECT ({expression})"))?;
+
+    let mut references: Vec<String> = Vec::new();
+
+    for (node, _depth, _context, _has_filter_columns) in parse_result.protobuf.nodes() {
+        let NodeRef::ColumnRef(column_ref) = node else {
+            continue;
+        };
+
+        let column = column_ref
+            .fields
+            .iter()
+            .rev()
+            .find_map(|field| match &field.node {
+                Some(NodeEnum::String(s)) => Some(s.sval.clone()),
+                _ => None,
+            });
+
+        if let Some(column) = column
+            && !references.contains(&column)
+        {
+            references.push(column);
+        }
+    }
+
+    Ok(references)
+}
+
+fn has_subquery(expression: &str) -> bool {
+    pg_query::parse(&format!("SELECT ({expression})")).is_ok_and(|parse_result| {
+        parse_result
+            .protobuf
+            .nodes()
+            .iter()
+            .any(|(node, _depth, _context, _has_filter_columns)| {
+                matches!(node, NodeRef::SubLink(_))
+            })
+    })
+}
+
+/// Validate the expression of a generated trend against the trends of its trend store part and
+/// return the trends it uses.
+///
+/// # Errors
+///
+/// Will return `Err` if the expression cannot be parsed or refers to anything else than the
+/// trends and base columns of the part.
+pub fn validate_generated_trend(
+    trend_store_part: &TrendStorePart,
+    generated_trend: &GeneratedTrend,
+) -> Result<Vec<String>, GeneratedTrendError> {
+    if trend_store_part
+        .trends
+        .iter()
+        .any(|trend| trend.name == generated_trend.name)
+    {
+        return Err(GeneratedTrendError::DuplicateName(
+            generated_trend.name.clone(),
+        ));
+    }
+
+    let references = expression_references(&generated_trend.expression)
+        .map_err(|e| GeneratedTrendError::Parse(generated_trend.name.clone(), e.to_string()))?;
+
+    if has_subquery(&generated_trend.expression) {
+        return Err(GeneratedTrendError::Subquery(generated_trend.name.clone()));
+    }
+
+    let mut trends = Vec::new();
+
+    for reference in references {
+        if trend_store_part
+            .trends
+            .iter()
+            .any(|trend| trend.name == reference)
+        {
+            trends.push(reference);
+        } else if trend_store_part
+            .generated_trends
+            .iter()
+            .any(|other| other.name == reference)
+        {
+            return Err(GeneratedTrendError::GeneratedTrendReference(
+                generated_trend.name.clone(),
+                reference,
+            ));
+        } else if !TREND_STORE_PART_COLUMNS.contains(&reference.as_str()) {
+            return Err(GeneratedTrendError::UnknownTrend(
+                generated_trend.name.clone(),
+                reference,
+            ));
+        }
+    }
+
+    Ok(trends)
+}
+
+/// Validate all generated trends of a trend store part and return the invalid ones with their
+/// error
+#[must_use]
+pub fn validate_generated_trends(
+    trend_store_part: &TrendStorePart,
+) -> Vec<(&GeneratedTrend, GeneratedTrendError)> {
+    trend_store_part
+        .generated_trends
+        .iter()
+        .filter_map(|generated_trend| {
+            validate_generated_trend(trend_store_part, generated_trend)
+                .err()
+                .map(|e| (generated_trend, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::entity::EntityIdType;
+    use crate::meas_value::DataType;
+    use crate::trend_store::Trend;
+
+    fn trend(name: &str) -> Trend {
+        Trend {
+            name: name.to_string(),
+            data_type: DataType::Int8,
+            description: String::new(),
+            time_aggregation: "SUM".to_string(),
+            entity_aggregation: "SUM".to_string(),
+            extra_data: json!({}),
+        }
+    }
+
+    fn generated_trend(name: &str, expression: &str) -> GeneratedTrend {
+        GeneratedTrend {
+            name: name.to_string(),
+            data_type: "numeric".to_string(),
+            description: String::new(),
+            expression: expression.to_string(),
+            extra_data: json!({}),
+        }
+    }
+
+    #[test]
+    fn generated_trend_validation() {
+        let part = TrendStorePart {
+            name: "hub_cell_main_15m".to_string(),
+            has_alias_column: false,
+            trends: vec![trend("bytes_up"), trend("bytes_down")],
+            generated_trends: vec![generated_trend("bytes_total", "bytes_up + bytes_down")],
+            entity_id_type: EntityIdType::I32,
+        };
+
+        assert_eq!(
+            validate_generated_trend(
+                &part,
+                &generated_trend(
+                    "up_ratio",
+                    "CASE WHEN bytes_up + bytes_down > 0 THEN bytes_up::numeric / (bytes_up + bytes_down) END"
+                )
+            ),
+            Ok(vec!["bytes_up".to_string(), "bytes_down".to_string()])
+        );
+
+        assert_eq!(
+            validate_generated_trend(&part, &generated_trend("x", "bytes_upp * 2")),
+            Err(GeneratedTrendError::UnknownTrend(
+                "x".to_string(),
+                "bytes_upp".to_string()
+            ))
+        );
+
+        assert_eq!(
+            validate_generated_trend(&part, &generated_trend("x", "bytes_total / 2")),
+            Err(GeneratedTrendError::GeneratedTrendReference(
+                "x".to_string(),
+                "bytes_total".to_string()
+            ))
+        );
+
+        assert!(matches!(
+            validate_generated_trend(&part, &generated_trend("x", "bytes_up +")),
+            Err(GeneratedTrendError::Parse(_, _))
+        ));
+
+        assert_eq!(
+            validate_generated_trend(&part, &generated_trend("bytes_up", "1")),
+            Err(GeneratedTrendError::DuplicateName("bytes_up".to_string()))
+        );
+
+        assert!(validate_generated_trends(&part).is_empty());
+    }
+}
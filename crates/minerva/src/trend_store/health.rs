@@ -0,0 +1,303 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use postgres_protocol::escape::escape_identifier;
+use tokio_postgres::GenericClient;
+
+use crate::error::{DatabaseError, Error, RuntimeError};
+
+/// Number of periods that is reported when no start of the period is specified
+pub const DEFAULT_HEALTH_PERIODS: u32 = 24;
+
+/// Data health of one timestamp of a trend store part
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampHealth {
+    pub timestamp: DateTime<Utc>,
+    /// Number of entities with data for the timestamp
+    pub entity_count: i64,
+    /// Highest number of entities with data in the baseline periods before the timestamp
+    pub expected_count: Option<i64>,
+    /// Number of distinct jobs that stored the current data of the timestamp
+    pub job_count: i64,
+    /// Number of rows that were stored by another job than the first job of the timestamp,
+    /// so redelivered data that overwrote earlier data or data that arrived in a later delivery
+    pub redelivered_count: i64,
+    /// Average delay between the timestamp and the time the data was stored
+    pub avg_delay: Option<Duration>,
+    /// Maximum delay between the timestamp and the time the data was stored
+    pub max_delay: Option<Duration>,
+}
+
+impl TimestampHealth {
+    /// Ratio of the entity count to the expected count, if there is an expectation
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn completeness(&self) -> Option<f64> {
+        match self.expected_count {
+            Some(expected) if expected > 0 => Some(self.entity_count as f64 / expected as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendNullRatio {
+    pub trend: String,
+    /// Ratio of rows in the period with a NULL value for the trend
+    pub null_ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendStorePartHealth {
+    pub trend_store_part: String,
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+    pub timestamps: Vec<TimestampHealth>,
+    pub trends: Vec<TrendNullRatio>,
+}
+
+/// Query for the health per timestamp of a trend store part, with parameters $1 and $2 for
+/// the start and end of the period and $3 for the granularity. The baseline periods before
+/// the start are included in the series so that the expected count is also available for the
+/// first timestamps of the period.
+///
+/// The start of the period does not have to be a timestamp of the trend store part, so the
+/// series is generated in both directions from the last timestamp with data in the period to
+/// keep it aligned with the data, also for granularities of months.
+fn timestamp_health_query(trend_store_part: &str, baseline_periods: u32) -> String {
+    let table = format!("trend.{}", escape_identifier(trend_store_part));
+
+    format!(
+        concat!(
+            "WITH anchor AS (",
+            "SELECT coalesce(max(timestamp), $2::timestamptz) AS timestamp FROM {table} ",
+            "WHERE timestamp >= $1::timestamptz - {baseline} * $3::text::interval AND timestamp <= $2",
+            "), t AS (",
+            "SELECT generate_series(a.timestamp, $1::timestamptz - {baseline} * $3::text::interval, -($3::text::interval)) AS timestamp FROM anchor a ",
+            "UNION ",
+            "SELECT generate_series(a.timestamp, $2::timestamptz, $3::text::interval) FROM anchor a",
+            "), first_job AS (",
+            "SELECT timestamp, min(job_id) AS job_id FROM {table} ",
+            "WHERE timestamp >= $1::timestamptz - {baseline} * $3::text::interval AND timestamp <= $2 ",
+            "GROUP BY timestamp",
+            "), stats AS (",
+            "SELECT p.timestamp, count(*) AS entity_count, count(DISTINCT p.job_id) AS job_count, ",
+            "count(*) FILTER (WHERE p.job_id <> f.job_id) AS redelivered_count, ",
+            "avg(extract(epoch FROM p.created - p.timestamp))::float8 AS avg_delay, ",
+            "max(extract(epoch FROM p.created - p.timestamp))::float8 AS max_delay ",
+            "FROM {table} p JOIN first_job f ON f.timestamp = p.timestamp ",
+            "WHERE p.timestamp >= $1::timestamptz - {baseline} * $3::text::interval AND p.timestamp <= $2 ",
+            "GROUP BY p.timestamp",
+            "), health AS (",
+            "SELECT t.timestamp, coalesce(s.entity_count, 0) AS entity_count, ",
+            "max(coalesce(s.entity_count, 0)) OVER (ORDER BY t.timestamp ROWS BETWEEN {baseline} PRECEDING AND 1 PRECEDING) AS expected_count, ",
+            "coalesce(s.job_count, 0) AS job_count, coalesce(s.redelivered_count, 0) AS redelivered_count, ",
+            "s.avg_delay, s.max_delay ",
+            "FROM t LEFT JOIN stats s ON s.timestamp = t.timestamp",
+            ") ",
+            "SELECT timestamp, entity_count, expected_count, job_count, redelivered_count, avg_delay, max_delay ",
+            "FROM health WHERE timestamp >= $1 ORDER BY timestamp"
+        ),
+        table = table,
+        baseline = baseline_periods
+    )
+}
+
+fn null_ratio_query(trend_store_part: &str, trends: &[String]) -> String {
+    let mut columns = vec!["count(*)".to_string()];
+
+    columns.extend(
+        trends
+            .iter()
+            .map(|trend| format!("count({})", escape_identifier(trend))),
+    );
+
+    format!(
+        "SELECT {} FROM trend.{} WHERE timestamp >= $1 AND timestamp <= $2",
+        columns.join(", "),
+        escape_identifier(trend_store_part)
+    )
+}
+
+fn seconds_to_duration(seconds: Option<f64>) -> Option<Duration> {
+    seconds.map(|seconds| Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+/// Report on the completeness of the data of a trend store part over a period. The expected
+/// entity count of a timestamp is the highest entity count of the `baseline_periods` before it.
+///
+/// When no end is specified, the period ends at the last timestamp with data, and when no start
+/// is specified, the period covers `DEFAULT_HEALTH_PERIODS` periods.
+///
+/// # Errors
+///
+/// Will return `Err` if the trend store part does not exist or the data cannot be queried.
+pub async fn trend_store_part_health<T: GenericClient>(
+    client: &T,
+    name: &str,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    baseline_periods: u32,
+) -> Result<TrendStorePartHealth, Error> {
+    let query = concat!(
+        "SELECT ts.granularity::text, array_agg(tt.name::text ORDER BY tt.id) FILTER (WHERE tt.id IS NOT NULL) ",
+        "FROM trend_directory.trend_store_part tsp ",
+        "JOIN trend_directory.trend_store ts ON ts.id = tsp.trend_store_id ",
+        "LEFT JOIN trend_directory.table_trend tt ON tt.trend_store_part_id = tsp.id ",
+        "AND tt.deleted IS NULL AND tt.staged_for_deletion IS NULL ",
+        "WHERE tsp.name = $1 ",
+        "GROUP BY ts.granularity"
+    );
+
+    let row = client
+        .query_opt(query, &[&name])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load trend store part: {e}")))?
+        .ok_or_else(|| RuntimeError::from_msg(format!("No such trend store part '{name}'")))?;
+
+    let granularity: String = row.get(0);
+    let trends: Vec<String> = row.get::<_, Option<Vec<String>>>(1).unwrap_or_default();
+
+    let period_query = format!(
+        concat!(
+            "SELECT coalesce($1::timestamptz, e - $3::integer * $4::text::interval), e ",
+            "FROM (SELECT coalesce($2::timestamptz, max(timestamp)) AS e FROM trend.{}) x"
+        ),
+        escape_identifier(name)
+    );
+
+    #[allow(clippy::cast_possible_wrap)]
+    let default_periods = (DEFAULT_HEALTH_PERIODS - 1) as i32;
+
+    let row = client
+        .query_one(
+            &period_query,
+            &[&start, &end, &default_periods, &granularity],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not determine period: {e}")))?;
+
+    let start: Option<DateTime<Utc>> = row.get(0);
+    let end: Option<DateTime<Utc>> = row.get(1);
+
+    let (Some(period_start), Some(period_end)) = (start, end) else {
+        // Without a specified end and without any data, there is nothing to report
+        return Ok(TrendStorePartHealth {
+            trend_store_part: name.to_string(),
+            start,
+            end,
+            timestamps: Vec::new(),
+            trends: Vec::new(),
+        });
+    };
+
+    let rows = client
+        .query(
+            &timestamp_health_query(name, baseline_periods),
+            &[&period_start, &period_end, &granularity],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not query data health: {e}")))?;
+
+    let timestamps = rows
+        .iter()
+        .map(|row| TimestampHealth {
+            timestamp: row.get(0),
+            entity_count: row.get(1),
+            expected_count: row.get(2),
+            job_count: row.get(3),
+            redelivered_count: row.get(4),
+            avg_delay: seconds_to_duration(row.get(5)),
+            max_delay: seconds_to_duration(row.get(6)),
+        })
+        .collect();
+
+    let row = client
+        .query_one(
+            &null_ratio_query(name, &trends),
+            &[&period_start, &period_end],
+        )
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not query NULL ratios: {e}")))?;
+
+    let row_count: i64 = row.get(0);
+
+    let trends = trends
+        .into_iter()
+        .enumerate()
+        .map(|(index, trend)| {
+            let value_count: i64 = row.get(index + 1);
+
+            #[allow(clippy::cast_precision_loss)]
+            let null_ratio = if row_count == 0 {
+                0.0
+            } else {
+                (row_count - value_count) as f64 / row_count as f64
+            };
+
+            TrendNullRatio { trend, null_ratio }
+        })
+        .collect();
+
+    Ok(TrendStorePartHealth {
+        trend_store_part: name.to_string(),
+        start,
+        end,
+        timestamps,
+        trends,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn timestamp_health_completeness() {
+        let mut health = TimestampHealth {
+            timestamp: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            entity_count: 90,
+            expected_count: Some(120),
+            job_count: 1,
+            redelivered_count: 0,
+            avg_delay: None,
+            max_delay: None,
+        };
+
+        assert_eq!(health.completeness(), Some(0.75));
+
+        health.expected_count = Some(0);
+
+        assert_eq!(health.completeness(), None);
+    }
+
+    #[test]
+    fn trend_store_part_health_queries() {
+        let query = timestamp_health_query("hub_cell_main_15m", 4);
+
+        assert!(query.contains(
+            "generate_series(a.timestamp, $1::timestamptz - 4 * $3::text::interval, -($3::text::interval))"
+        ));
+        assert!(
+            query.contains("generate_series(a.timestamp, $2::timestamptz, $3::text::interval)")
+        );
+        assert!(query.contains("ROWS BETWEEN 4 PRECEDING AND 1 PRECEDING"));
+        assert!(query.contains(concat!(
+            "FROM trend.\"hub_cell_main_15m\" p JOIN first_job f ON f.timestamp = p.timestamp ",
+            "WHERE p.timestamp >= $1::timestamptz - 4 * $3::text::interval AND p.timestamp <= $2 "
+        )));
+
+        assert_eq!(
+            null_ratio_query(
+                "hub_cell_main_15m",
+                &["bytes_up".to_string(), "bytes_down".to_string()]
+            ),
+            concat!(
+                "SELECT count(*), count(\"bytes_up\"), count(\"bytes_down\") ",
+                "FROM trend.\"hub_cell_main_15m\" WHERE timestamp >= $1 AND timestamp <= $2"
+            )
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use std::time::Duration;
+
+use postgres_protocol::escape::escape_identifier;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use tokio_postgres::GenericClient;
+
+use crate::changes::trend_store::{ModifyTrendDataType, ModifyTrendDataTypes};
+use crate::error::{DatabaseError, Error, RuntimeError};
+use crate::meas_value::{DataType, MeasValue, parse_meas_value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecommendOptions {
+    /// Fraction of the range of a type from which the values are at risk of overflowing
+    pub overflow_ratio: f64,
+    /// Fraction of the range of the smaller type within which the values must stay to
+    /// recommend the smaller type
+    pub downsize_ratio: f64,
+    /// Period before now of which the actual values are checked to convert losslessly
+    pub check_period: Duration,
+}
+
+impl Default for RecommendOptions {
+    fn default() -> Self {
+        RecommendOptions {
+            overflow_ratio: 0.8,
+            downsize_ratio: 0.1,
+            check_period: Duration::from_secs(7 * 86400),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecommendationReason {
+    OverflowRisk,
+    Oversized,
+}
+
+impl fmt::Display for RecommendationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecommendationReason::OverflowRisk => write!(f, "overflow risk"),
+            RecommendationReason::Oversized => write!(f, "oversized"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeRecommendation {
+    pub trend: String,
+    pub from_type: DataType,
+    pub to_type: DataType,
+    pub reason: RecommendationReason,
+    /// Minimum and maximum from the trend statistics
+    pub min: Option<Decimal>,
+    pub max: Option<Decimal>,
+    /// Result of converting the actual minimum and maximum values to the recommended type and
+    /// back, `None` when the conversion is lossless
+    pub conversion_error: Option<String>,
+}
+
+fn integer_limit(data_type: DataType) -> Option<f64> {
+    match data_type {
+        DataType::Int2 => Some(f64::from(i16::MAX)),
+        DataType::Integer => Some(f64::from(i32::MAX)),
+        #[allow(clippy::cast_precision_loss)]
+        DataType::Int8 => Some(i64::MAX as f64),
+        _ => None,
+    }
+}
+
+fn larger_type(data_type: DataType) -> Option<DataType> {
+    match data_type {
+        DataType::Int2 => Some(DataType::Integer),
+        DataType::Integer => Some(DataType::Int8),
+        DataType::Int8 => Some(DataType::Numeric),
+        _ => None,
+    }
+}
+
+/// Recommend a data type for an integer trend based on the minimum and maximum of its
+/// statistics, or `None` when the current type fits.
+#[must_use]
+pub fn recommend_data_type(
+    data_type: DataType,
+    min: Option<Decimal>,
+    max: Option<Decimal>,
+    options: &RecommendOptions,
+) -> Option<(DataType, RecommendationReason)> {
+    let limit = integer_limit(data_type)?;
+
+    let magnitude = [min, max]
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.abs().to_f64())
+        .reduce(f64::max)?;
+
+    if magnitude >= options.overflow_ratio * limit {
+        return larger_type(data_type).map(|to_type| (to_type, RecommendationReason::OverflowRisk));
+    }
+
+    // Only bigint trends are downsized, smallint columns save too little to be worth the
+    // reduced headroom.
+    if data_type == DataType::Int8 && magnitude <= options.downsize_ratio * f64::from(i32::MAX) {
+        return Some((DataType::Integer, RecommendationReason::Oversized));
+    }
+
+    None
+}
+
+/// Check that a value survives the conversion to another type and back unchanged
+fn check_lossless(value: &MeasValue, from_type: DataType, to_type: DataType) -> Result<(), String> {
+    let converted = value
+        .to_value_of(to_type)
+        .and_then(|converted| converted.to_value_of(from_type))
+        .map_err(|e| e.to_string())?;
+
+    if converted == *value {
+        Ok(())
+    } else {
+        Err(format!("{value} does not convert losslessly to {to_type}"))
+    }
+}
+
+/// Recommend data types for the trends of a trend store part based on the trend statistics.
+/// For each recommendation, the actual minimum and maximum values of the trend in the check
+/// period are checked to convert losslessly to the recommended type.
+///
+/// # Errors
+///
+/// Will return `Err` if the trend statistics or values cannot be queried.
+pub async fn recommend_trend_data_types<T: GenericClient>(
+    client: &T,
+    trend_store_part: &str,
+    options: &RecommendOptions,
+) -> Result<(Vec<TypeRecommendation>, usize), Error> {
+    let query = concat!(
+        "SELECT tt.name::text, tt.data_type, tts.min, tts.max ",
+        "FROM trend_directory.table_trend tt ",
+        "JOIN trend_directory.trend_store_part tsp ON tsp.id = tt.trend_store_part_id ",
+        "LEFT JOIN trend_directory.table_trend_statistics tts ON tts.table_trend_id = tt.id ",
+        "WHERE tsp.name = $1 AND tt.deleted IS NULL AND tt.staged_for_deletion IS NULL ",
+        "ORDER BY tt.id"
+    );
+
+    let rows = client
+        .query(query, &[&trend_store_part])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load trend statistics: {e}")))?;
+
+    if rows.is_empty() {
+        return Err(RuntimeError::from_msg(format!(
+            "No trends found for trend store part '{trend_store_part}'"
+        ))
+        .into());
+    }
+
+    let check_period = humantime::format_duration(options.check_period).to_string();
+
+    let mut recommendations = Vec::new();
+
+    for row in &rows {
+        let trend: String = row.get(0);
+        let from_type = DataType::from(row.get::<_, &str>(1));
+        let min: Option<Decimal> = row.get(2);
+        let max: Option<Decimal> = row.get(3);
+
+        let Some((to_type, reason)) = recommend_data_type(from_type, min, max, options) else {
+            continue;
+        };
+
+        let values_query = format!(
+            "SELECT min({trend})::text, max({trend})::text FROM trend.{} WHERE timestamp >= now() - $1::text::interval",
+            escape_identifier(trend_store_part),
+            trend = escape_identifier(&trend)
+        );
+
+        let values_row = client
+            .query_one(&values_query, &[&check_period])
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!("Could not query values of trend '{trend}': {e}"))
+            })?;
+
+        let conversion_error = [
+            values_row.get::<_, Option<String>>(0),
+            values_row.get::<_, Option<String>>(1),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|value| parse_meas_value(from_type, &value))
+        .find_map(|value| check_lossless(&value, from_type, to_type).err());
+
+        recommendations.push(TypeRecommendation {
+            trend,
+            from_type,
+            to_type,
+            reason,
+            min,
+            max,
+            conversion_error,
+        });
+    }
+
+    Ok((recommendations, rows.len()))
+}
+
+/// Change for the recommendations that convert losslessly, if any
+#[must_use]
+pub fn recommendations_to_change(
+    trend_store_part: &str,
+    recommendations: &[TypeRecommendation],
+    total_trend_count: usize,
+) -> Option<ModifyTrendDataTypes> {
+    let modifications: Vec<ModifyTrendDataType> = recommendations
+        .iter()
+        .filter(|recommendation| recommendation.conversion_error.is_none())
+        .map(|recommendation| ModifyTrendDataType {
+            trend_name: recommendation.trend.clone(),
+            from_type: recommendation.from_type,
+            to_type: recommendation.to_type,
+        })
+        .collect();
+
+    if modifications.is_empty() {
+        None
+    } else {
+        Some(ModifyTrendDataTypes {
+            trend_store_part_name: trend_store_part.to_string(),
+            modifications,
+            total_trend_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recommend_data_type_overflow_and_oversized() {
+        let options = RecommendOptions::default();
+
+        assert_eq!(
+            recommend_data_type(
+                DataType::Integer,
+                Some(Decimal::from(0)),
+                Some(Decimal::from(2_000_000_000)),
+                &options
+            ),
+            Some((DataType::Int8, RecommendationReason::OverflowRisk))
+        );
+
+        assert_eq!(
+            recommend_data_type(
+                DataType::Integer,
+                Some(Decimal::from(-1000)),
+                Some(Decimal::from(1000)),
+                &options
+            ),
+            None
+        );
+
+        assert_eq!(
+            recommend_data_type(
+                DataType::Int8,
+                Some(Decimal::from(0)),
+                Some(Decimal::from(1000)),
+                &options
+            ),
+            Some((DataType::Integer, RecommendationReason::Oversized))
+        );
+
+        assert_eq!(
+            recommend_data_type(DataType::Int8, None, None, &options),
+            None
+        );
+
+        assert_eq!(
+            recommend_data_type(
+                DataType::Double,
+                Some(Decimal::from(0)),
+                Some(Decimal::from(1000)),
+                &options
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn lossless_conversion_check() {
+        assert!(
+            check_lossless(
+                &MeasValue::Int8(Some(1000)),
+                DataType::Int8,
+                DataType::Integer
+            )
+            .is_ok()
+        );
+
+        assert!(
+            check_lossless(
+                &MeasValue::Int8(Some(5_000_000_000)),
+                DataType::Int8,
+                DataType::Integer
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use log::debug;
+use postgres_protocol::escape::escape_identifier;
+use regex::{NoExpand, Regex};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::GenericClient;
+
+use crate::error::{DatabaseError, Error, RuntimeError};
+
+/// Query that copies the values of trends from `source_table` into the target trend store part.
+/// Rows of the source without any value for the trends are skipped. Rows that already exist in
+/// the target only get the values of the trends updated, so that copying is idempotent.
+fn copy_query(source_table: &str, target_part: &str, trends: &[String]) -> String {
+    let trend_columns: Vec<String> = trends
+        .iter()
+        .map(|trend| escape_identifier(trend))
+        .collect();
+
+    format!(
+        concat!(
+            "INSERT INTO trend.{target}(entity_id, timestamp, created, job_id, {columns}) ",
+            "SELECT entity_id, timestamp, created, job_id, {columns} FROM {source} ",
+            "WHERE ({condition}) ",
+            "ON CONFLICT (entity_id, timestamp) DO UPDATE SET {updates}"
+        ),
+        target = escape_identifier(target_part),
+        columns = trend_columns.join(", "),
+        source = source_table,
+        condition = trend_columns
+            .iter()
+            .map(|column| format!("{column} IS NOT NULL"))
+            .collect::<Vec<String>>()
+            .join(" OR "),
+        updates = trend_columns
+            .iter()
+            .map(|column| format!("{column} = EXCLUDED.{column}"))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Copy the data of trends from one trend store part to another of the same trend store, one
+/// partition of the source at a time. Missing partitions of the target are created along the
+/// way. Run this in a transaction that locks the source table against writes, so that no data
+/// is missed. Returns the number of copied rows.
+///
+/// # Errors
+///
+/// Will return `Err` if a partition cannot be created or copied.
+pub async fn copy_trend_data<T: GenericClient>(
+    client: &T,
+    source_part: &str,
+    target_part: &str,
+    trends: &[String],
+) -> Result<u64, Error> {
+    let query = concat!(
+        "SELECT p.name::text, p.index ",
+        "FROM trend_directory.partition p ",
+        "JOIN trend_directory.trend_store_part tsp ON tsp.id = p.trend_store_part_id ",
+        "WHERE tsp.name = $1 ",
+        "ORDER BY p.index"
+    );
+
+    let rows = client
+        .query(query, &[&source_part])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load partitions: {e}")))?;
+
+    let create_partition_query = concat!(
+        "SELECT trend_directory.create_partition(tsp, $2::integer) ",
+        "FROM trend_directory.trend_store_part tsp ",
+        "WHERE tsp.name = $1 AND NOT EXISTS (",
+        "SELECT 1 FROM trend_directory.partition p ",
+        "WHERE p.trend_store_part_id = tsp.id AND p.index = $2",
+        ")"
+    );
+
+    let mut row_count: u64 = 0;
+
+    for row in &rows {
+        let partition: String = row.get(0);
+        let index: i32 = row.get(1);
+
+        client
+            .execute(create_partition_query, &[&target_part, &index])
+            .await
+            .map_err(|e| {
+                DatabaseError::from_msg(format!(
+                    "Could not create partition {index} of '{target_part}': {e}"
+                ))
+            })?;
+
+        let query = copy_query(
+            &format!("trend_partition.{}", escape_identifier(&partition)),
+            target_part,
+            trends,
+        );
+
+        let count = client.execute(&query, &[]).await.map_err(|e| {
+            DatabaseError::from_msg(format!("Could not copy data of '{partition}': {e}"))
+        })?;
+
+        debug!("Copied {count} rows of '{partition}' to '{target_part}'");
+
+        row_count += count;
+    }
+
+    Ok(row_count)
+}
+
+/// A view or function, like a materialization or trigger rule, whose definition refers to both a
+/// trend store part and one of its trends
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrendReference {
+    pub name: String,
+    /// Statement that creates or replaces the view or function with its current definition
+    pub definition: String,
+}
+
+/// Views and functions, like materializations and trigger rules, whose definition refers to both
+/// the trend store part and one of the trends. Their definitions would break when the trends are
+/// moved to another part.
+///
+/// # Errors
+///
+/// Will return `Err` if the definitions cannot be loaded or a materialized view uses the trends,
+/// because its definition cannot be replaced in place.
+pub async fn trend_references<T: GenericClient>(
+    client: &T,
+    trend_store_part: &str,
+    trends: &[String],
+) -> Result<Vec<TrendReference>, Error> {
+    let query = concat!(
+        "WITH definition AS (",
+        "SELECT c.oid::regclass::text AS name, pg_get_viewdef(c.oid) AS src, ",
+        "CASE WHEN c.relkind = 'v' ",
+        "THEN format('CREATE OR REPLACE VIEW %I.%I AS %s', n.nspname, c.relname, pg_get_viewdef(c.oid)) ",
+        "END AS statement ",
+        "FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace ",
+        "WHERE c.relkind IN ('v', 'm') AND n.nspname NOT IN ('pg_catalog', 'information_schema') ",
+        "UNION ALL ",
+        "SELECT p.oid::regprocedure::text, p.prosrc, pg_get_functiondef(p.oid) ",
+        "FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace ",
+        "WHERE p.prokind IN ('f', 'p') AND n.nspname NOT IN ('pg_catalog', 'information_schema')",
+        ") ",
+        "SELECT name, src, statement FROM definition WHERE position($1 in src) > 0 ORDER BY name"
+    );
+
+    let rows = client
+        .query(query, &[&trend_store_part])
+        .await
+        .map_err(|e| DatabaseError::from_msg(format!("Could not load definitions: {e}")))?;
+
+    let part_pattern = part_reference_pattern(trend_store_part);
+    let trend_pattern = trend_reference_pattern(trends);
+
+    rows.iter()
+        .filter(|row| {
+            let src: &str = row.get(1);
+
+            part_pattern.is_match(src) && trend_pattern.is_match(src)
+        })
+        .map(|row| {
+            let name: String = row.get(0);
+            let definition: Option<String> = row.get(2);
+
+            match definition {
+                Some(definition) => Ok(TrendReference { name, definition }),
+                None => Err(RuntimeError::from_msg(format!(
+                    "Materialized view {name} uses trends of '{trend_store_part}' and cannot be updated in place"
+                ))
+                .into()),
+            }
+        })
+        .collect()
+}
+
+/// Definition of a view or function with the references to the source trend store part replaced
+/// by references to the target part. Columns that are qualified with the name of the source part,
+/// as `pg_get_viewdef` does for tables without an alias, are qualified with the target part.
+#[must_use]
+pub fn replace_part_reference(definition: &str, source_part: &str, target_part: &str) -> String {
+    let target = escape_identifier(target_part);
+
+    let definition = part_reference_pattern(source_part)
+        .replace_all(definition, NoExpand(&format!("trend.{target}")))
+        .into_owned();
+
+    Regex::new(&format!(
+        r#"(^|[^.\w"])(?:"{name}"|{name})\."#,
+        name = regex::escape(source_part)
+    ))
+    .unwrap()
+    .replace_all(&definition, |captures: &regex::Captures| {
+        format!("{}{target}.", &captures[1])
+    })
+    .into_owned()
+}
+
+/// Return true if the definition of a view or function refers to one of the trends
+#[must_use]
+pub fn uses_trends(definition: &str, trends: &[String]) -> bool {
+    !trends.is_empty() && trend_reference_pattern(trends).is_match(definition)
+}
+
+fn part_reference_pattern(trend_store_part: &str) -> Regex {
+    Regex::new(&format!(
+        r#"trend\.(?:"{name}"|{name}\b)"#,
+        name = regex::escape(trend_store_part)
+    ))
+    .unwrap()
+}
+
+fn trend_reference_pattern(trends: &[String]) -> Regex {
+    Regex::new(&format!(
+        r"\b(?:{})\b",
+        trends
+            .iter()
+            .map(|trend| regex::escape(trend))
+            .collect::<Vec<String>>()
+            .join("|")
+    ))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trend_data_copy_query() {
+        assert_eq!(
+            copy_query(
+                "trend_partition.\"hub_cell_main_15m_1234\"",
+                "hub_cell_extra_15m",
+                &["bytes_up".to_string(), "bytes_down".to_string()],
+            ),
+            concat!(
+                "INSERT INTO trend.\"hub_cell_extra_15m\"(entity_id, timestamp, created, job_id, \"bytes_up\", \"bytes_down\") ",
+                "SELECT entity_id, timestamp, created, job_id, \"bytes_up\", \"bytes_down\" FROM trend_partition.\"hub_cell_main_15m_1234\" ",
+                "WHERE (\"bytes_up\" IS NOT NULL OR \"bytes_down\" IS NOT NULL) ",
+                "ON CONFLICT (entity_id, timestamp) DO UPDATE SET \"bytes_up\" = EXCLUDED.\"bytes_up\", \"bytes_down\" = EXCLUDED.\"bytes_down\""
+            )
+        );
+    }
+
+    #[test]
+    fn trend_reference_patterns() {
+        let part_pattern = part_reference_pattern("hub_node_main_15m");
+
+        assert!(part_pattern.is_match("SELECT power FROM trend.hub_node_main_15m t"));
+        assert!(part_pattern.is_match("FROM trend.\"hub_node_main_15m\" t"));
+        assert!(!part_pattern.is_match("FROM trend.hub_node_main_15m_staging t"));
+
+        let trend_pattern = trend_reference_pattern(&["power".to_string()]);
+
+        assert!(trend_pattern.is_match("SELECT t.power, t.\"power\" FROM"));
+        assert!(!trend_pattern.is_match("SELECT t.power_kwh FROM"));
+    }
+
+    #[test]
+    fn part_reference_replacement() {
+        assert_eq!(
+            replace_part_reference(
+                "SELECT t.power FROM trend.hub_node_main_15m t JOIN trend.hub_node_main_15m_staging s USING (entity_id)",
+                "hub_node_main_15m",
+                "hub_node_power_15m"
+            ),
+            "SELECT t.power FROM trend.\"hub_node_power_15m\" t JOIN trend.hub_node_main_15m_staging s USING (entity_id)"
+        );
+
+        assert_eq!(
+            replace_part_reference(
+                "SELECT hub_node_main_15m.power FROM trend.hub_node_main_15m",
+                "hub_node_main_15m",
+                "hub_node_power_15m"
+            ),
+            "SELECT \"hub_node_power_15m\".power FROM trend.\"hub_node_power_15m\""
+        );
+
+        assert!(uses_trends("SELECT t.power FROM", &["power".to_string()]));
+        assert!(!uses_trends("SELECT t.power FROM", &[]));
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use humantime::format_duration;
+use tokio_postgres::GenericClient;
+
+use crate::changes::trend_store::ModifyTrendStoreData;
+use crate::error::Error;
+use crate::instance::InstanceConfig;
+use crate::trend_store::{TrendStore, TrendStoreRef};
+
+/// A partition with data that is older than the retention period of its trend store
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpiredPartition {
+    pub trend_store_part: String,
+    pub name: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Row count estimate from the planner statistics
+    pub row_count: i64,
+    /// Total size on disk in bytes, including indexes and TOAST data
+    pub size: i64,
+}
+
+/// Find the partitions with data older than the retention period of their trend store,
+/// optionally limited to one trend store.
+///
+/// A partition is expired when it starts more than the retention period plus two partition
+/// sizes ago, the same margin that is used when removing partitions in bulk.
+///
+/// # Errors
+///
+/// Will return `Err` if the partitions cannot be queried.
+pub async fn expired_partitions<T: GenericClient>(
+    client: &T,
+    trend_store: Option<&TrendStoreRef>,
+) -> Result<Vec<ExpiredPartition>, Error> {
+    expired_partitions_with_retention(client, trend_store, &[]).await
+}
+
+/// Find the partitions that would be expired when the trend stores had the specified
+/// retention periods instead of their current ones, e.g. to report the effect of changing them
+/// before doing so.
+///
+/// # Errors
+///
+/// Will return `Err` if the partitions cannot be queried.
+pub async fn expired_partitions_with_retention<T: GenericClient>(
+    client: &T,
+    trend_store: Option<&TrendStoreRef>,
+    retention_periods: &[(TrendStoreRef, Duration)],
+) -> Result<Vec<ExpiredPartition>, Error> {
+    let query = concat!(
+        "SELECT tsp.name, p.name::text, p.from, p.to, ",
+        "greatest(coalesce(c.reltuples, 0), 0)::bigint, ",
+        "coalesce(pg_total_relation_size(c.oid), 0) ",
+        "FROM trend_directory.partition p ",
+        "JOIN trend_directory.trend_store_part tsp ON tsp.id = p.trend_store_part_id ",
+        "JOIN trend_directory.trend_store ts ON ts.id = tsp.trend_store_id ",
+        "JOIN directory.data_source ds ON ds.id = ts.data_source_id ",
+        "JOIN directory.entity_type et ON et.id = ts.entity_type_id ",
+        "LEFT JOIN pg_class c ON c.oid = to_regclass(format('trend_partition.%I', p.name)) ",
+        "LEFT JOIN unnest($4::text[], $5::text[], $6::text[], $7::text[]) ",
+        "r(data_source, entity_type, granularity, retention_period) ",
+        "ON r.data_source = ds.name AND r.entity_type = et.name AND r.granularity::interval = ts.granularity ",
+        "WHERE p.from < (now() - coalesce(r.retention_period::interval, ts.retention_period) - ts.partition_size - ts.partition_size) ",
+        "AND ($1::text IS NULL OR ds.name = $1) ",
+        "AND ($2::text IS NULL OR et.name = $2) ",
+        "AND ($3::text IS NULL OR ts.granularity = $3::text::interval) ",
+        "ORDER BY tsp.name, p.from"
+    );
+
+    let data_source = trend_store.map(|t| t.data_source.clone());
+    let entity_type = trend_store.map(|t| t.entity_type.clone());
+    let granularity = trend_store.map(|t| format_duration(t.granularity).to_string());
+
+    let retention_data_sources: Vec<&str> = retention_periods
+        .iter()
+        .map(|(t, _)| t.data_source.as_str())
+        .collect();
+    let retention_entity_types: Vec<&str> = retention_periods
+        .iter()
+        .map(|(t, _)| t.entity_type.as_str())
+        .collect();
+    let retention_granularities: Vec<String> = retention_periods
+        .iter()
+        .map(|(t, _)| format_duration(t.granularity).to_string())
+        .collect();
+    let retention_durations: Vec<String> = retention_periods
+        .iter()
+        .map(|(_, retention_period)| format_duration(*retention_period).to_string())
+        .collect();
+
+    let rows = client
+        .query(
+            query,
+            &[
+                &data_source,
+                &entity_type,
+                &granularity,
+                &retention_data_sources,
+                &retention_entity_types,
+                &retention_granularities,
+                &retention_durations,
+            ],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ExpiredPartition {
+            trend_store_part: row.get(0),
+            name: row.get(1),
+            from: row.get(2),
+            to: row.get(3),
+            row_count: row.get(4),
+            size: row.get(5),
+        })
+        .collect())
+}
+
+/// Changes that set the retention period of trend stores to the default for their
+/// granularity from the instance configuration, for the trend stores that differ from it.
+#[must_use]
+pub fn retention_default_changes(
+    trend_stores: &[TrendStore],
+    instance_config: &InstanceConfig,
+) -> Vec<ModifyTrendStoreData> {
+    trend_stores
+        .iter()
+        .filter_map(|trend_store| {
+            instance_config
+                .granularity_to_retention(trend_store.granularity)
+                .filter(|retention_period| *retention_period != trend_store.retention_period)
+                .map(|retention_period| ModifyTrendStoreData {
+                    trend_store: TrendStoreRef {
+                        data_source: trend_store.data_source.clone(),
+                        entity_type: trend_store.entity_type.clone(),
+                        granularity: trend_store.granularity,
+                    },
+                    partition_size: None,
+                    retention_period: Some(retention_period),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::RetentionConfig;
+
+    fn trend_store(granularity: &str, retention_period: &str) -> TrendStore {
+        TrendStore {
+            title: None,
+            data_source: "hub".to_string(),
+            entity_type: "cell".to_string(),
+            granularity: humantime::parse_duration(granularity).unwrap(),
+            partition_size: Duration::from_secs(86400),
+            retention_period: humantime::parse_duration(retention_period).unwrap(),
+            parts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn retention_defaults_for_differing_trend_stores() {
+        let instance_config = InstanceConfig {
+            retention: Some(vec![
+                RetentionConfig {
+                    granularity: humantime::parse_duration("15m").unwrap(),
+                    retention_period: humantime::parse_duration("14d").unwrap(),
+                },
+                RetentionConfig {
+                    granularity: humantime::parse_duration("1h").unwrap(),
+                    retention_period: humantime::parse_duration("30d").unwrap(),
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let trend_stores = vec![
+            trend_store("15m", "30d"),
+            trend_store("1h", "30d"),
+            trend_store("1d", "30d"),
+        ];
+
+        let changes = retention_default_changes(&trend_stores, &instance_config);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].trend_store.granularity, Duration::from_secs(900));
+        assert_eq!(
+            changes[0].retention_period,
+            Some(humantime::parse_duration("14d").unwrap())
+        );
+        assert_eq!(changes[0].partition_size, None);
+    }
+}
@@ -6,7 +6,8 @@ use std::{io::Read, path::PathBuf};
 use serde::{Deserialize, Serialize};
 use tokio_postgres::{Client, GenericClient};
 
-use crate::change::Changed;
+use crate::change::{Changed, MinervaObjectRef};
+use crate::relation::sql_equals;
 
 use super::change::{Change, ChangeResult};
 use super::error::{ConfigurationError, DatabaseError, Error};
@@ -23,6 +24,21 @@ impl fmt::Display for VirtualEntity {
     }
 }
 
+impl VirtualEntity {
+    #[must_use]
+    pub fn diff(&self, other: &VirtualEntity) -> Vec<Box<dyn Change + Send>> {
+        let mut changes: Vec<Box<dyn Change + Send>> = Vec::new();
+
+        if !sql_equals(&self.sql, &other.sql) {
+            changes.push(Box::new(UpdateVirtualEntity {
+                virtual_entity: other.clone(),
+            }));
+        }
+
+        changes
+    }
+}
+
 pub fn load_virtual_entity_from_yaml_file(path: &PathBuf) -> Result<VirtualEntity, Error> {
     let f = std::fs::File::open(path).map_err(|e| {
         ConfigurationError::from_msg(format!(
@@ -242,3 +258,77 @@ impl Changed for RemovedVirtualEntity {
         }))
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdateVirtualEntity {
+    pub virtual_entity: VirtualEntity,
+}
+
+impl fmt::Display for UpdateVirtualEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UpdateVirtualEntity({})", self.virtual_entity)
+    }
+}
+
+#[async_trait]
+#[typetag::serde]
+impl Change for UpdateVirtualEntity {
+    async fn apply(&self, client: &mut Client) -> ChangeResult {
+        let mut tx = client.transaction().await?;
+
+        let original_virtual_entity =
+            load_virtual_entity_from_db(&mut tx, &self.virtual_entity.name).await?;
+
+        let query = format!(
+            "CREATE OR REPLACE VIEW virtual_entity.{} AS {}",
+            escape_identifier(&self.virtual_entity.name),
+            self.virtual_entity.sql
+        );
+
+        tx.execute(&query, &[]).await.map_err(|e| {
+            DatabaseError::from_msg(format!(
+                "Error updating virtual entity '{}': {e}",
+                self.virtual_entity.name
+            ))
+        })?;
+
+        tx.commit().await?;
+
+        Ok(Box::new(UpdatedVirtualEntity {
+            virtual_entity: self.virtual_entity.name.clone(),
+            original_sql: original_virtual_entity.sql,
+        }))
+    }
+
+    fn existing_object(&self) -> Option<MinervaObjectRef> {
+        Some(MinervaObjectRef::VirtualEntity(
+            self.virtual_entity.name.clone(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct UpdatedVirtualEntity {
+    pub virtual_entity: String,
+    pub original_sql: String,
+}
+
+impl Display for UpdatedVirtualEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Updated virtual entity {}", self.virtual_entity)
+    }
+}
+
+#[typetag::serde]
+impl Changed for UpdatedVirtualEntity {
+    fn revert(&self) -> Option<Box<dyn Change>> {
+        Some(Box::new(UpdateVirtualEntity {
+            virtual_entity: VirtualEntity {
+                name: self.virtual_entity.clone(),
+                sql: self.original_sql.clone(),
+            },
+        }))
+    }
+}